@@ -0,0 +1,14 @@
+-- Your SQL goes here
+CREATE TABLE two_factor_secrets (
+    user_id INTEGER PRIMARY KEY REFERENCES Users(user_id),
+    secret VARCHAR(64) NOT NULL,
+    confirmed_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE TABLE recovery_codes (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES Users(user_id),
+    code_hash VARCHAR(128) NOT NULL,
+    used_at TIMESTAMPTZ
+);
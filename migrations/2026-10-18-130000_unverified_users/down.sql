@@ -0,0 +1,2 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE Users ALTER COLUMN is_active SET DEFAULT TRUE;
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+-- new accounts stay inactive until their email address is verified
+ALTER TABLE Users ALTER COLUMN is_active SET DEFAULT FALSE;
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX chats_receiver_sender_idx;
+DROP INDEX chats_sender_receiver_idx;
@@ -0,0 +1,4 @@
+-- Your SQL goes here
+-- conversations look chats up by both of their participants
+CREATE INDEX chats_sender_receiver_idx ON Chats (sender_id, receiver_id);
+CREATE INDEX chats_receiver_sender_idx ON Chats (receiver_id, sender_id);
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE room_messages;
+DROP TABLE room_members;
+DROP TABLE rooms;
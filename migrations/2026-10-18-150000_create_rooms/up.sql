@@ -0,0 +1,26 @@
+-- Your SQL goes here
+CREATE TABLE rooms (
+    room_id SERIAL PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+-- every room has exactly one owner, everyone else is a member
+CREATE TABLE room_members (
+    room_id INTEGER NOT NULL REFERENCES rooms(room_id),
+    user_id INTEGER NOT NULL REFERENCES Users(user_id),
+    role VARCHAR(16) NOT NULL DEFAULT 'member' CHECK (role IN ('owner', 'member')),
+    joined_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    PRIMARY KEY (room_id, user_id)
+);
+CREATE UNIQUE INDEX room_members_owner_idx ON room_members (room_id) WHERE role = 'owner';
+CREATE INDEX room_members_user_idx ON room_members (user_id);
+
+CREATE TABLE room_messages (
+    message_id SERIAL PRIMARY KEY,
+    room_id INTEGER NOT NULL REFERENCES rooms(room_id),
+    sender_id INTEGER NOT NULL REFERENCES Users(user_id),
+    message VARCHAR(1000) NOT NULL,
+    timestamp TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+CREATE INDEX room_messages_room_idx ON room_messages (room_id, message_id);
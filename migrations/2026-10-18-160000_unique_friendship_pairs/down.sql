@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX friendships_block_unique;
+DROP INDEX friendships_pair_unique;
+ALTER TABLE Friendships DROP CONSTRAINT friendships_not_self;
@@ -0,0 +1,21 @@
+-- Your SQL goes here
+-- nobody is their own friend
+DELETE FROM Friendships WHERE user_id = friend_id;
+ALTER TABLE Friendships ADD CONSTRAINT friendships_not_self CHECK (user_id <> friend_id);
+
+-- one friendship or friend request per pair of users, whoever sent it, the oldest one is kept
+DELETE FROM Friendships f USING Friendships g
+WHERE f.status <> 'blocked' AND g.status <> 'blocked'
+  AND LEAST(f.user_id, f.friend_id) = LEAST(g.user_id, g.friend_id)
+  AND GREATEST(f.user_id, f.friend_id) = GREATEST(g.user_id, g.friend_id)
+  AND f.friendship_id > g.friendship_id;
+CREATE UNIQUE INDEX friendships_pair_unique ON Friendships (LEAST(user_id, friend_id), GREATEST(user_id, friend_id))
+WHERE status <> 'blocked';
+
+-- blocks go one way, both users may block each other
+DELETE FROM Friendships f USING Friendships g
+WHERE f.status = 'blocked' AND g.status = 'blocked'
+  AND f.user_id = g.user_id AND f.friend_id = g.friend_id
+  AND f.friendship_id > g.friendship_id;
+CREATE UNIQUE INDEX friendships_block_unique ON Friendships (user_id, friend_id)
+WHERE status = 'blocked';
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE currency_transactions;
+DROP FUNCTION currency_transactions_append_only;
@@ -0,0 +1,27 @@
+-- Your SQL goes here
+-- append only ledger, every change of a currency amount is booked with its reason
+CREATE TABLE currency_transactions (
+    transaction_id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES Users(user_id),
+    currency_type VARCHAR(50) NOT NULL,
+    delta INTEGER NOT NULL CHECK (delta <> 0),
+    reason VARCHAR(50) NOT NULL,
+    reference VARCHAR(255),
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX currency_transactions_user_idx ON currency_transactions (user_id, transaction_id);
+
+-- bookings are never changed, corrections are new bookings
+CREATE FUNCTION currency_transactions_append_only() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'currency_transactions is append only';
+END;
+$$ LANGUAGE plpgsql;
+CREATE TRIGGER currency_transactions_no_update BEFORE UPDATE ON currency_transactions
+    FOR EACH ROW EXECUTE FUNCTION currency_transactions_append_only();
+
+-- the balances so far become opening bookings
+INSERT INTO currency_transactions (user_id, currency_type, delta, reason)
+SELECT user_id, currency_type, amount, 'opening_balance'
+FROM Currency
+WHERE user_id IS NOT NULL AND currency_type IS NOT NULL AND amount IS NOT NULL AND amount <> 0;
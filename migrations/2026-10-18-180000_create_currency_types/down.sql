@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE Currency
+    DROP CONSTRAINT currency_user_type_unique,
+    DROP CONSTRAINT currency_currency_type_fkey;
+DROP TABLE currency_types;
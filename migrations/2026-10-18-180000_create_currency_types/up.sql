@@ -0,0 +1,46 @@
+-- Your SQL goes here
+-- the currency types of the game, every wallet holds one of them
+CREATE TABLE currency_types (
+    code VARCHAR(50) PRIMARY KEY CHECK (code <> '' AND code = lower(btrim(code))),
+    display_name VARCHAR(255) NOT NULL,
+    -- no limit when null
+    max_balance INTEGER CHECK (max_balance >= 0),
+    tradeable BOOLEAN NOT NULL DEFAULT true,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- "Gold", "gold " and "gold" are one type, a blank type is none
+UPDATE Currency SET currency_type = NULLIF(lower(btrim(currency_type)), '');
+
+-- the history gets the same codes, the only time bookings are ever changed
+ALTER TABLE currency_transactions DISABLE TRIGGER currency_transactions_no_update;
+UPDATE currency_transactions SET currency_type = lower(btrim(currency_type)) WHERE currency_type <> lower(btrim(currency_type));
+ALTER TABLE currency_transactions ENABLE TRIGGER currency_transactions_no_update;
+
+-- one wallet per user and type, duplicates are merged into the oldest one,
+-- the balance of the wallet stays what it was so the ledger still adds up
+UPDATE Currency SET amount = merged.amount, last_updated = merged.last_updated
+FROM (
+    SELECT min(currency_id) AS currency_id, sum(COALESCE(amount, 0))::INTEGER AS amount, max(last_updated) AS last_updated
+    FROM Currency
+    WHERE user_id IS NOT NULL AND currency_type IS NOT NULL
+    GROUP BY user_id, currency_type
+    HAVING count(*) > 1
+) merged
+WHERE Currency.currency_id = merged.currency_id;
+DELETE FROM Currency duplicate USING Currency oldest
+WHERE duplicate.user_id = oldest.user_id
+    AND duplicate.currency_type = oldest.currency_type
+    AND duplicate.currency_id > oldest.currency_id;
+
+-- gold and gems to start with, plus whatever types are in use
+INSERT INTO currency_types (code, display_name) VALUES ('gold', 'Gold'), ('gems', 'Gems');
+INSERT INTO currency_types (code, display_name)
+SELECT DISTINCT currency_type, initcap(currency_type)
+FROM Currency
+WHERE currency_type IS NOT NULL
+ON CONFLICT (code) DO NOTHING;
+
+ALTER TABLE Currency
+    ADD CONSTRAINT currency_currency_type_fkey FOREIGN KEY (currency_type) REFERENCES currency_types(code),
+    ADD CONSTRAINT currency_user_type_unique UNIQUE (user_id, currency_type);
@@ -0,0 +1,4 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE Users ALTER COLUMN is_active SET DEFAULT FALSE;
+UPDATE Users SET is_active = FALSE WHERE email_verified_at IS NULL;
+ALTER TABLE Users DROP COLUMN email_verified_at;
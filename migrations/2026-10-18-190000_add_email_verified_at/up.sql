@@ -0,0 +1,7 @@
+-- Your SQL goes here
+-- verification gets its own column, is_active is the admin switch again
+ALTER TABLE Users ADD COLUMN email_verified_at TIMESTAMPTZ;
+-- accounts that could log in so far were verified or predate verification,
+-- inactive ones can't be told apart from deactivated ones and stay inactive
+UPDATE Users SET email_verified_at = COALESCE(registration_date, NOW()) WHERE is_active IS TRUE;
+ALTER TABLE Users ALTER COLUMN is_active SET DEFAULT TRUE;
@@ -0,0 +1,9 @@
+-- Your SQL goes here
+-- members kicked by the owner, they can't join the room again until the ban is lifted
+CREATE TABLE room_bans (
+    room_id INTEGER NOT NULL REFERENCES rooms(room_id),
+    user_id INTEGER NOT NULL REFERENCES Users(user_id),
+    banned_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    PRIMARY KEY (room_id, user_id)
+);
+CREATE INDEX room_bans_user_idx ON room_bans (user_id);
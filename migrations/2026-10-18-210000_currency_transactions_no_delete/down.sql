@@ -0,0 +1,8 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE currency_transactions ALTER COLUMN created_at TYPE TIMESTAMP;
+DROP TRIGGER currency_transactions_no_delete ON currency_transactions;
+CREATE OR REPLACE FUNCTION currency_transactions_append_only() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'currency_transactions is append only';
+END;
+$$ LANGUAGE plpgsql;
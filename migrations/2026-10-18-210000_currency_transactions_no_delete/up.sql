@@ -0,0 +1,16 @@
+-- Your SQL goes here
+-- bookings can't be deleted either, the one exception is erasing a user with everything they own,
+-- UserRepository::delete turns app.ledger_erasure on for its own transaction only
+CREATE OR REPLACE FUNCTION currency_transactions_append_only() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP = 'DELETE' AND current_setting('app.ledger_erasure', true) = 'on' THEN
+        RETURN OLD;
+    END IF;
+    RAISE EXCEPTION 'currency_transactions is append only';
+END;
+$$ LANGUAGE plpgsql;
+CREATE TRIGGER currency_transactions_no_delete BEFORE DELETE ON currency_transactions
+    FOR EACH ROW EXECUTE FUNCTION currency_transactions_append_only();
+
+-- like every other new table, the booking time is stored with its time zone
+ALTER TABLE currency_transactions ALTER COLUMN created_at TYPE TIMESTAMPTZ;
@@ -1,8 +1,14 @@
 use argon2::Argon2;
 use argon2::password_hash::{Error, SaltString, rand_core::OsRng};
 use argon2::{PasswordHash, PasswordVerifier, PasswordHasher};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
 use rand::distributions::Alphanumeric;
-use rand::Rng;
+use rand::{Rng, RngCore};
+use sha2::Sha256;
+use std::sync::OnceLock;
+use totp_rs::{Algorithm, Secret, TOTP};
 
 use crate::models::User;
 
@@ -12,18 +18,75 @@ pub struct Credentials {
     pub password: String
 }
 
+#[derive(serde::Deserialize)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct PasswordChange {
+    pub old_password: String,
+    pub new_password: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct PasswordResetRequest {
+    pub email: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct PasswordReset {
+    pub token: String,
+    pub new_password: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct VerificationResend {
+    pub email: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct TwoFactorCode {
+    pub code: String,
+}
+
+// second step of a login for users with two factor auth enabled,
+// `code` is either the current TOTP code or one of the recovery codes
+#[derive(serde::Deserialize)]
+pub struct TwoFactorLogin {
+    pub challenge: String,
+    pub code: String,
+}
+
 pub fn authorize_user(user: &User, credentials: Credentials) -> Result<String, Error> {
     let argon2 = Argon2::default();
     let db_hash = PasswordHash::new(&user.password_hash)?;
     argon2.verify_password(credentials.password.as_bytes(), &db_hash)?;
 
-    // lets create a session id with rand crate
-    let session_id = rand::thread_rng()
+    Ok(generate_token())
+}
+
+// random 128 character token, used for session ids and refresh tokens
+pub fn generate_token() -> String {
+    rand::thread_rng()
         .sample_iter(&Alphanumeric)
         .take(128)
         .map(char::from)
-        .collect();
-    Ok(session_id)
+        .collect()
+}
+
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+pub const MAX_PASSWORD_LENGTH: usize = 128;
+
+pub fn validate_password(password: &str) -> Result<(), String> {
+    let length = password.chars().count();
+    if length < MIN_PASSWORD_LENGTH {
+        return Err(format!("Password must be at least {} characters", MIN_PASSWORD_LENGTH));
+    }
+    if length > MAX_PASSWORD_LENGTH {
+        return Err(format!("Password must be at most {} characters", MAX_PASSWORD_LENGTH));
+    }
+    Ok(())
 }
 
 pub fn hash_password(password: String) -> Result<String, Error> {
@@ -33,3 +96,89 @@ pub fn hash_password(password: String) -> Result<String, Error> {
     
     Ok(hashed_password.to_string())
 }
+
+pub fn verify_password_hash(password: &str, hash: &str) -> bool {
+    PasswordHash::new(hash)
+        .and_then(|hash| Argon2::default().verify_password(password.as_bytes(), &hash))
+        .is_ok()
+}
+
+// checks the password against the hash of a random one nobody knows, a login for an unknown
+// username then takes as long as one with a wrong password and doesn't give the username away
+pub fn verify_dummy_password(password: &str) -> bool {
+    static DUMMY_HASH: OnceLock<String> = OnceLock::new();
+    let hash = DUMMY_HASH.get_or_init(|| hash_password(generate_token()).expect("hashing the dummy password"));
+    verify_password_hash(password, hash)
+}
+
+// -----------------  Email verification  -----------------
+type HmacSha256 = Hmac<Sha256>;
+
+// "{user_id}:{email}:{expires_at}" and its HMAC-SHA256, both base64url encoded and joined by a dot
+// the email is part of the token so it stops working once the address changes
+pub fn email_verification_token(secret: &str, user_id: i32, email: &str, expires_at: i64) -> String {
+    let payload = format!("{}:{}:{}", user_id, email, expires_at);
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any size");
+    mac.update(payload.as_bytes());
+    format!("{}.{}", URL_SAFE_NO_PAD.encode(&payload), URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes()))
+}
+
+// the user id and email a token was issued for, None if it was tampered with or has expired
+pub fn verify_email_verification_token(secret: &str, token: &str, now: i64) -> Option<(i32, String)> {
+    let (payload, signature) = token.split_once('.')?;
+    let payload = URL_SAFE_NO_PAD.decode(payload).ok()?;
+    let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).ok()?;
+    mac.update(&payload);
+    mac.verify_slice(&signature).ok()?;
+
+    let payload = String::from_utf8(payload).ok()?;
+    let (user_id, rest) = payload.split_once(':')?;
+    let (email, expires_at) = rest.rsplit_once(':')?;
+    if expires_at.parse::<i64>().ok()? < now {
+        return None;
+    }
+    Some((user_id.parse().ok()?, email.to_owned()))
+}
+
+// -----------------  Two factor (RFC 6238)  -----------------
+const TOTP_ISSUER: &str = "BattleGear";
+const RECOVERY_CODE_COUNT: usize = 10;
+
+// 160 bit secret, base32 encoded the way authenticator apps expect it
+pub fn generate_totp_secret() -> String {
+    let mut secret = [0u8; 20];
+    OsRng.fill_bytes(&mut secret);
+    Secret::Raw(secret.to_vec()).to_encoded().to_string()
+}
+
+pub fn totp(secret: &str, account_name: &str) -> Result<TOTP, String> {
+    let secret = Secret::Encoded(secret.to_owned()).to_bytes()
+        .map_err(|e| format!("{:?}", e))?;
+    // ':' separates issuer and account in the provisioning uri
+    let account_name = account_name.replace(':', "_");
+    TOTP::new(Algorithm::SHA1, 6, 1, 30, secret, Some(TOTP_ISSUER.to_owned()), account_name)
+        .map_err(|e| e.to_string())
+}
+
+// accepts the current code and the ones right before and after it to allow for clock drift
+pub fn verify_totp(secret: &str, account_name: &str, code: &str) -> bool {
+    totp(secret, account_name)
+        .ok()
+        .and_then(|totp| totp.check_current(code.trim()).ok())
+        .unwrap_or(false)
+}
+
+// one time codes shown to the user once, only their hashes are stored
+pub fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let code: String = rand::thread_rng()
+                .sample_iter(&Alphanumeric)
+                .take(10)
+                .map(|c| char::from(c).to_ascii_lowercase())
+                .collect();
+            format!("{}-{}", &code[..5], &code[5..])
+        })
+        .collect()
+}
@@ -43,6 +43,13 @@ async fn main() {
                         .arg_required_else_help(true)
                         .arg(Arg::new("username").required(true))
                 )
+                .subcommand(
+                    Command::new("set_active")
+                        .about("Activate or deactivate a user by ID, deactivated users can't log in")
+                        .arg_required_else_help(true)
+                        .arg(Arg::new("ID").required(true).value_parser(value_parser!(i32)))
+                        .arg(Arg::new("active").required(true).value_parser(value_parser!(bool)))
+                )
                 
         )
         .get_matches();
@@ -74,6 +81,12 @@ async fn main() {
                         delete_by_username_matches.get_one::<String>("username").unwrap().to_owned()
                     ).await;
                 }
+                Some(("set_active", set_active_matches)) => {
+                    api_server::commands::set_user_active(
+                        set_active_matches.get_one::<i32>("ID").unwrap().to_owned(),
+                        set_active_matches.get_one::<bool>("active").unwrap().to_owned()
+                    ).await;
+                }
                 _ => unreachable!()
             }
         }
@@ -1,5 +1,6 @@
 extern crate battle_gear as api_server;
 
+use rocket::fairing::AdHoc;
 use rocket_db_pools::Database;
 use std::env;
 
@@ -11,57 +12,133 @@ async fn main() {
         .mount("/", rocket::routes![
             //authorization
             api_server::rocket_routes::authorization::login,
+            api_server::rocket_routes::authorization::login_two_factor,
+            api_server::rocket_routes::authorization::refresh_token,
+            api_server::rocket_routes::authorization::logout,
+            api_server::rocket_routes::authorization::logout_all,
+            //blocks
+            api_server::rocket_routes::blocks::block_user,
+            api_server::rocket_routes::blocks::unblock_user,
             //chats
             api_server::rocket_routes::chats::get_chats,
             api_server::rocket_routes::chats::view_chat,
+            api_server::rocket_routes::chats::stream_chats,
             api_server::rocket_routes::chats::create_chat,
             api_server::rocket_routes::chats::update_chat,
+            api_server::rocket_routes::chats::patch_chat,
             api_server::rocket_routes::chats::delete_chat,
+            //conversations
+            api_server::rocket_routes::conversations::get_conversations,
+            api_server::rocket_routes::conversations::get_conversation_messages,
+            api_server::rocket_routes::conversations::mark_conversation_read,
             //currencies
             api_server::rocket_routes::currency::get_currencies,
             api_server::rocket_routes::currency::view_currency,
             api_server::rocket_routes::currency::create_currency,
             api_server::rocket_routes::currency::update_currency,
+            api_server::rocket_routes::currency::patch_currency,
             api_server::rocket_routes::currency::delete_currency,
+            api_server::rocket_routes::currency::get_currency_transactions,
+            api_server::rocket_routes::currency::credit_currency,
+            api_server::rocket_routes::currency::debit_currency,
+            api_server::rocket_routes::currency::transfer_currency,
+            //currency types
+            api_server::rocket_routes::currency_types::get_currency_types,
+            api_server::rocket_routes::currency_types::view_currency_type,
+            api_server::rocket_routes::currency_types::create_currency_type,
+            api_server::rocket_routes::currency_types::patch_currency_type,
+            api_server::rocket_routes::currency_types::delete_currency_type,
+            //friends
+            api_server::rocket_routes::friends::get_friends,
+            api_server::rocket_routes::friends::unfriend,
+            api_server::rocket_routes::friends::get_mutual_friends,
+            api_server::rocket_routes::friends::get_friend_suggestions,
+            api_server::rocket_routes::friends::send_friend_request,
+            api_server::rocket_routes::friends::get_incoming_friend_requests,
+            api_server::rocket_routes::friends::get_outgoing_friend_requests,
+            api_server::rocket_routes::friends::accept_friend_request,
+            api_server::rocket_routes::friends::decline_friend_request,
             //friendships
             api_server::rocket_routes::friendships::get_friendships,
             api_server::rocket_routes::friendships::view_friendship,
             api_server::rocket_routes::friendships::create_friendship,
             api_server::rocket_routes::friendships::update_friendship,
+            api_server::rocket_routes::friendships::patch_friendship,
             api_server::rocket_routes::friendships::delete_friendship,
             //images
             api_server::rocket_routes::images::get_images,
             api_server::rocket_routes::images::view_image,
             api_server::rocket_routes::images::create_image,
             api_server::rocket_routes::images::update_image,
+            api_server::rocket_routes::images::patch_image,
             api_server::rocket_routes::images::delete_image,
+            //me
+            api_server::rocket_routes::me::get_me,
+            api_server::rocket_routes::me::update_me,
+            api_server::rocket_routes::me::get_my_wallet,
+            //password
+            api_server::rocket_routes::password::change_password,
+            api_server::rocket_routes::password::forgot_password,
+            api_server::rocket_routes::password::reset_password,
+            //rooms
+            api_server::rocket_routes::rooms::get_rooms,
+            api_server::rocket_routes::rooms::view_room,
+            api_server::rocket_routes::rooms::create_room,
+            api_server::rocket_routes::rooms::patch_room,
+            api_server::rocket_routes::rooms::delete_room,
+            api_server::rocket_routes::rooms::get_room_members,
+            api_server::rocket_routes::rooms::join_room,
+            api_server::rocket_routes::rooms::leave_room,
+            api_server::rocket_routes::rooms::kick_room_member,
+            api_server::rocket_routes::rooms::lift_room_ban,
+            api_server::rocket_routes::rooms::get_room_messages,
+            api_server::rocket_routes::rooms::create_room_message,
             //throphies
             api_server::rocket_routes::throphies::get_throphies,
             api_server::rocket_routes::throphies::view_throphy,
             api_server::rocket_routes::throphies::create_throphy,
             api_server::rocket_routes::throphies::update_throphy,
+            api_server::rocket_routes::throphies::patch_throphy,
             api_server::rocket_routes::throphies::delete_throphy,
             //total_throphies
             api_server::rocket_routes::total_throphies::get_total_throphies,
             api_server::rocket_routes::total_throphies::view_total_throphies,
             api_server::rocket_routes::total_throphies::create_total_throphies,
             api_server::rocket_routes::total_throphies::update_total_throphies,
+            api_server::rocket_routes::total_throphies::patch_total_throphies,
             api_server::rocket_routes::total_throphies::delete_total_throphies,
+            //two factor
+            api_server::rocket_routes::two_factor::status,
+            api_server::rocket_routes::two_factor::enroll,
+            api_server::rocket_routes::two_factor::confirm,
+            api_server::rocket_routes::two_factor::disable,
             //user_level
             api_server::rocket_routes::user_level::get_user_levels,
             api_server::rocket_routes::user_level::view_user_levels,
             api_server::rocket_routes::user_level::create_user_levels,
             api_server::rocket_routes::user_level::update_user_levels,
+            api_server::rocket_routes::user_level::patch_user_levels,
             api_server::rocket_routes::user_level::delete_user_levels,
             //users
             api_server::rocket_routes::users::get_users,    
             api_server::rocket_routes::users::view_user,
             api_server::rocket_routes::users::create_user,
             api_server::rocket_routes::users::update_user,
+            api_server::rocket_routes::users::patch_user,
             api_server::rocket_routes::users::delete_user,    
+            api_server::rocket_routes::users::revoke_user_sessions,
             api_server::rocket_routes::users::username_exists,
             api_server::rocket_routes::users::email_exists,     
+            //verification
+            api_server::rocket_routes::verification::verify_email,
+            api_server::rocket_routes::verification::resend_verification,
         ])
+        .register("/", api_server::rocket_routes::error::catchers())
+        .attach(AdHoc::config::<api_server::config::SessionConfig>())
+        .attach(AdHoc::config::<api_server::config::LoginThrottleConfig>())
+        .attach(AdHoc::config::<api_server::config::VerificationConfig>())
+        .attach(api_server::mail::fairing())
+        .attach(api_server::rocket_routes::error::fairing())
         .attach(api_server::rocket_routes::CacheConn::init())
         .attach(api_server::rocket_routes::DbConn::init())
         .launch()
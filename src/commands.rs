@@ -34,6 +34,10 @@ pub async fn create_user(
     let user = repositories::UserRepository::create(&mut c, new_user, role_codes)
         .await
         .unwrap();
+    // users made on the command line don't have to verify their email address
+    let user = repositories::UserRepository::mark_email_verified(&mut c, user.user_id)
+        .await
+        .unwrap();
     println!("Created user: {:?}", user);
     let roles = repositories::RoleRepository::find_by_user(&mut c, &user)
         .await
@@ -67,3 +71,11 @@ pub async fn delete_user_by_username(username: String) {
         .unwrap();
     println!("Deleted user: {:?}", user);
 }
+
+pub async fn set_user_active(id: i32, active: bool) {
+    let mut c = load_db_connection().await;
+    let user = repositories::UserRepository::set_active(&mut c, id, active)
+        .await
+        .unwrap();
+    println!("Updated user: {:?}", user);
+}
@@ -0,0 +1,144 @@
+use serde::Deserialize;
+
+// session lifetimes, read from Rocket.toml or ROCKET_SESSION_TTL / ROCKET_REFRESH_TOKEN_TTL
+// attached in the server with AdHoc::config::<SessionConfig>()
+#[derive(Deserialize, Debug, Clone)]
+pub struct SessionConfig {
+    // seconds a session stays valid after its last use
+    #[serde(default = "default_session_ttl")]
+    pub session_ttl: u64,
+    // seconds a refresh token can be exchanged for a new session
+    #[serde(default = "default_refresh_token_ttl")]
+    pub refresh_token_ttl: u64,
+}
+
+fn default_session_ttl() -> u64 {
+    3*60*60
+}
+
+fn default_refresh_token_ttl() -> u64 {
+    30*24*60*60
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        SessionConfig {
+            session_ttl: default_session_ttl(),
+            refresh_token_ttl: default_refresh_token_ttl(),
+        }
+    }
+}
+
+// brute force protection for POST /login, read from Rocket.toml or ROCKET_MAX_FAILED_LOGINS_PER_USERNAME etc.
+// attached in the server with AdHoc::config::<LoginThrottleConfig>()
+#[derive(Deserialize, Debug, Clone)]
+pub struct LoginThrottleConfig {
+    // failed logins for one username before it gets locked
+    #[serde(default = "default_max_failed_logins_per_username")]
+    pub max_failed_logins_per_username: u64,
+    // failed logins from one ip address before it gets locked
+    #[serde(default = "default_max_failed_logins_per_ip")]
+    pub max_failed_logins_per_ip: u64,
+    // seconds failed logins are remembered for
+    #[serde(default = "default_failed_login_window")]
+    pub failed_login_window: u64,
+    // seconds of the first lockout, every further failure doubles it
+    #[serde(default = "default_login_lockout")]
+    pub login_lockout: u64,
+    // upper bound for the doubling
+    #[serde(default = "default_max_login_lockout")]
+    pub max_login_lockout: u64,
+    // username and email existence checks one ip address can make within failed_login_window
+    #[serde(default = "default_max_existence_checks_per_ip")]
+    pub max_existence_checks_per_ip: u64,
+    // take the client ip from Rocket's ip_header (X-Real-IP) instead of the socket,
+    // only turn it on behind a proxy that sets the header, clients can send any value
+    #[serde(default)]
+    pub trust_ip_header: bool,
+}
+
+fn default_max_failed_logins_per_username() -> u64 {
+    5
+}
+
+fn default_max_failed_logins_per_ip() -> u64 {
+    20
+}
+
+fn default_max_existence_checks_per_ip() -> u64 {
+    60
+}
+
+fn default_failed_login_window() -> u64 {
+    60*60
+}
+
+fn default_login_lockout() -> u64 {
+    30
+}
+
+fn default_max_login_lockout() -> u64 {
+    60*60
+}
+
+impl LoginThrottleConfig {
+    // lockout after the given number of failures, none below the limit
+    pub fn lockout_for(&self, failures: u64, limit: u64) -> Option<u64> {
+        if failures < limit {
+            return None;
+        }
+        let doublings = (failures - limit).min(32) as u32;
+        Some(self.login_lockout.saturating_mul(2u64.saturating_pow(doublings)).min(self.max_login_lockout))
+    }
+}
+
+// mail delivery, read from Rocket.toml or ROCKET_MAIL_OUTBOX
+#[derive(Deserialize, Debug, Clone)]
+pub struct MailConfig {
+    // directory the file mail sender writes mails to
+    #[serde(default = "default_mail_outbox")]
+    pub mail_outbox: String,
+}
+
+fn default_mail_outbox() -> String {
+    "outbox".to_owned()
+}
+
+impl Default for MailConfig {
+    fn default() -> Self {
+        MailConfig {
+            mail_outbox: default_mail_outbox(),
+        }
+    }
+}
+
+// email verification, read from Rocket.toml or ROCKET_VERIFICATION_SECRET etc.
+#[derive(Deserialize, Debug, Clone)]
+pub struct VerificationConfig {
+    // key the verification tokens are signed with, has no default so every instance
+    // signs with the same key and tokens survive restarts, the server won't ignite without it
+    #[serde(deserialize_with = "non_empty_secret")]
+    pub verification_secret: String,
+    // seconds a verification token stays valid
+    #[serde(default = "default_verification_token_ttl")]
+    pub verification_token_ttl: u64,
+    // verification mails one address can ask for within an hour
+    #[serde(default = "default_max_verification_resends")]
+    pub max_verification_resends: u64,
+}
+
+fn non_empty_secret<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let secret = String::deserialize(deserializer)?;
+    if secret.is_empty() {
+        return Err(serde::de::Error::custom("must not be empty"));
+    }
+    Ok(secret)
+}
+
+fn default_verification_token_ttl() -> u64 {
+    24*60*60
+}
+
+fn default_max_verification_resends() -> u64 {
+    3
+}
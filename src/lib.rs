@@ -1,6 +1,10 @@
 mod auth;
 mod models;
+mod pagination;
+mod patch;
 mod schema;
 mod repositories;
 pub mod commands;
+pub mod config;
+pub mod mail;
 pub mod rocket_routes;
@@ -0,0 +1,58 @@
+use std::error::Error;
+use std::path::PathBuf;
+
+use rocket::fairing::AdHoc;
+
+use crate::config::MailConfig;
+
+// a plain text mail
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+// anything that can deliver mails, routes get the managed one as &State<Box<dyn MailSender>>
+#[rocket::async_trait]
+pub trait MailSender: Send + Sync {
+    async fn send(&self, mail: Mail) -> Result<(), Box<dyn Error + Send + Sync>>;
+}
+
+// for local use, every mail is logged and written to its own file in the outbox directory
+pub struct FileMailSender {
+    outbox: PathBuf,
+}
+
+impl FileMailSender {
+    pub fn new(outbox: impl Into<PathBuf>) -> Self {
+        FileMailSender { outbox: outbox.into() }
+    }
+}
+
+#[rocket::async_trait]
+impl MailSender for FileMailSender {
+    async fn send(&self, mail: Mail) -> Result<(), Box<dyn Error + Send + Sync>> {
+        tokio::fs::create_dir_all(&self.outbox).await?;
+
+        // {timestamp}_{recipient}.txt, the recipient is cleaned up so it can't leave the outbox
+        let recipient: String = mail.to.chars()
+            .map(|c| if c.is_ascii_alphanumeric() || "@.-_+".contains(c) { c } else { '_' })
+            .collect();
+        let timestamp = chrono::Utc::now().format("%Y%m%d%H%M%S%f");
+        let path = self.outbox.join(format!("{}_{}.txt", timestamp, recipient));
+
+        let content = format!("To: {}\nSubject: {}\n\n{}\n", mail.to, mail.subject, mail.body);
+        tokio::fs::write(&path, content).await?;
+        rocket::info!("Mail \"{}\" to {} written to {}", mail.subject, mail.to, path.display());
+        Ok(())
+    }
+}
+
+// manages the mail sender, attach it in the server with .attach(mail::fairing())
+pub fn fairing() -> AdHoc {
+    AdHoc::on_ignite("Mail sender", |rocket| async {
+        let config = rocket.figment().extract::<MailConfig>().unwrap_or_default();
+        let sender: Box<dyn MailSender> = Box::new(FileMailSender::new(config.mail_outbox));
+        rocket.manage(sender)
+    })
+}
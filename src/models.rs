@@ -1,16 +1,55 @@
 use chrono::{naive::NaiveDateTime, NaiveDate};
 use serde::{Deserialize, Serialize};
 use diesel::prelude::*;
+use validator::{Validate, ValidationError};
 use crate::schema::*;
+
+// -----------------  Validation  -----------------
+// incoming models derive Validate, the limits follow the columns in the migrations
+// and routes call .validate()? before touching the database, failures become a 422
+// listing every broken rule per field, see ApiError
+
+fn not_blank(value: &str) -> Result<(), ValidationError> {
+    if value.trim().is_empty() {
+        return Err(ValidationError::new("blank").with_message("must not be blank".into()));
+    }
+    Ok(())
+}
+
+fn password_rule(password: &str) -> Result<(), ValidationError> {
+    crate::auth::validate_password(password)
+        .map_err(|e| ValidationError::new("password").with_message(e.into()))
+}
+
+pub const FRIENDSHIP_STATUSES: [&str; 3] = ["pending", "accepted", "blocked"];
+// a friend request is a pending friendship from the sender (user_id) to the recipient (friend_id)
+pub const FRIENDSHIP_PENDING: &str = "pending";
+pub const FRIENDSHIP_ACCEPTED: &str = "accepted";
+// a block is a friendship row from the blocker (user_id) to the blocked user (friend_id)
+pub const FRIENDSHIP_BLOCKED: &str = "blocked";
+
+fn friendship_status(status: &str) -> Result<(), ValidationError> {
+    if !FRIENDSHIP_STATUSES.contains(&status) {
+        return Err(ValidationError::new("friendship_status")
+            .with_message(format!("must be one of {}", FRIENDSHIP_STATUSES.join(", ")).into()));
+    }
+    Ok(())
+}
+
 // -----------------  User  -----------------
-#[derive(Queryable, AsChangeset, Serialize, Deserialize, Debug, Identifiable)]
-#[diesel(primary_key(user_id))]
+#[derive(Queryable, QueryableByName, AsChangeset, Serialize, Deserialize, Debug, Identifiable, Validate)]
+#[diesel(table_name = users, primary_key(user_id))]
 pub struct User {
     #[serde(skip_deserializing)]
     pub user_id: i32,
+    #[validate(length(min = 1, max = 255), custom(function = "not_blank"))]
     pub username: String,
+    #[validate(email, length(max = 254))]
     pub email: String,
+    // only ever set through auth::hash_password, see the password routes
+    #[serde(skip, default)]
     pub password_hash: String,
+    #[validate(length(min = 1, max = 255), custom(function = "not_blank"))]
     pub full_name: String, // changed from Option<String> to String
     pub avatar_id: Option<i32>,
     #[serde(skip_deserializing)]
@@ -20,17 +59,141 @@ pub struct User {
     pub is_active: Option<bool>,
     #[serde(skip_deserializing)]
     pub is_admin: Option<bool>,
+    #[validate(length(min = 1, max = 50), custom(function = "not_blank"))]
     pub timezone: Option<String>,
+    #[validate(length(min = 1, max = 50), custom(function = "not_blank"))]
     pub language: Option<String>,
+    #[validate(length(min = 1, max = 50), custom(function = "not_blank"))]
     pub country: Option<String>,
     pub date_of_birth: NaiveDate, // changed from Option<NaiveDate> to NaiveDate
     pub two_factor_auth_enabled: Option<bool>,
     #[serde(skip_deserializing)]
     pub last_password_change: Option<NaiveDateTime>,
+    // set once the mailed verification link was opened, see rocket_routes::verification
+    #[serde(skip_deserializing)]
+    pub email_verified_at: Option<NaiveDateTime>,
+}
+
+impl User {
+    // active and verified, checked at /login and again on every session and refresh token,
+    // so deactivating an account or changing its email address ends the sessions it has
+    pub fn can_sign_in(&self) -> bool {
+        self.is_active == Some(true) && self.email_verified_at.is_some()
+    }
+}
+
+// -----------------  User views  -----------------
+// responses never serialize User directly, routes pick one of these based on who is asking
+
+// what any logged in player may see about another player
+#[derive(Serialize, Debug)]
+pub struct PublicUserView {
+    pub user_id: i32,
+    pub username: String,
+    pub full_name: String,
+    pub avatar_id: Option<i32>,
+    pub country: Option<String>,
+    pub registration_date: Option<NaiveDateTime>,
+}
+
+// what a user sees about their own account
+#[derive(Serialize, Debug)]
+pub struct SelfUserView {
+    pub user_id: i32,
+    pub username: String,
+    pub email: String,
+    pub full_name: String,
+    pub avatar_id: Option<i32>,
+    pub registration_date: Option<NaiveDateTime>,
+    pub last_login: Option<NaiveDateTime>,
+    pub timezone: Option<String>,
+    pub language: Option<String>,
+    pub country: Option<String>,
+    pub date_of_birth: NaiveDate,
+    pub two_factor_auth_enabled: Option<bool>,
+    pub last_password_change: Option<NaiveDateTime>,
+    pub email_verified_at: Option<NaiveDateTime>,
+}
+
+// what an admin sees, everything except credentials
+#[derive(Serialize, Debug)]
+pub struct AdminUserView {
+    pub user_id: i32,
+    pub username: String,
+    pub email: String,
+    pub full_name: String,
+    pub avatar_id: Option<i32>,
+    pub registration_date: Option<NaiveDateTime>,
+    pub last_login: Option<NaiveDateTime>,
+    pub is_active: Option<bool>,
+    pub is_admin: Option<bool>,
+    pub timezone: Option<String>,
+    pub language: Option<String>,
+    pub country: Option<String>,
+    pub date_of_birth: NaiveDate,
+    pub two_factor_auth_enabled: Option<bool>,
+    pub last_password_change: Option<NaiveDateTime>,
+    pub email_verified_at: Option<NaiveDateTime>,
+}
+
+impl From<User> for PublicUserView {
+    fn from(user: User) -> Self {
+        PublicUserView {
+            user_id: user.user_id,
+            username: user.username,
+            full_name: user.full_name,
+            avatar_id: user.avatar_id,
+            country: user.country,
+            registration_date: user.registration_date,
+        }
+    }
+}
+
+impl From<User> for SelfUserView {
+    fn from(user: User) -> Self {
+        SelfUserView {
+            user_id: user.user_id,
+            username: user.username,
+            email: user.email,
+            full_name: user.full_name,
+            avatar_id: user.avatar_id,
+            registration_date: user.registration_date,
+            last_login: user.last_login,
+            timezone: user.timezone,
+            language: user.language,
+            country: user.country,
+            date_of_birth: user.date_of_birth,
+            two_factor_auth_enabled: user.two_factor_auth_enabled,
+            last_password_change: user.last_password_change,
+            email_verified_at: user.email_verified_at,
+        }
+    }
 }
 
+impl From<User> for AdminUserView {
+    fn from(user: User) -> Self {
+        AdminUserView {
+            user_id: user.user_id,
+            username: user.username,
+            email: user.email,
+            full_name: user.full_name,
+            avatar_id: user.avatar_id,
+            registration_date: user.registration_date,
+            last_login: user.last_login,
+            is_active: user.is_active,
+            is_admin: user.is_admin,
+            timezone: user.timezone,
+            language: user.language,
+            country: user.country,
+            date_of_birth: user.date_of_birth,
+            two_factor_auth_enabled: user.two_factor_auth_enabled,
+            last_password_change: user.last_password_change,
+            email_verified_at: user.email_verified_at,
+        }
+    }
+}
 
-#[derive(Insertable, Deserialize)]
+#[derive(Insertable)]
 #[diesel(table_name=users)]
 pub struct NewUser {
     pub username: String,
@@ -41,7 +204,88 @@ pub struct NewUser {
     pub date_of_birth: Option<NaiveDate>,
 }
 
+// registration payload, the password is hashed server side before it becomes a NewUser
+// unknown fields are rejected so a client can never hand us its own password_hash
+#[derive(Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct RegisterUser {
+    #[validate(length(min = 1, max = 255), custom(function = "not_blank"))]
+    pub username: String,
+    #[validate(email, length(max = 254))]
+    pub email: String,
+    #[validate(custom(function = "password_rule"))]
+    pub password: String,
+    #[validate(length(min = 1, max = 255), custom(function = "not_blank"))]
+    pub full_name: Option<String>,
+    #[validate(length(min = 1, max = 50), custom(function = "not_blank"))]
+    pub country: Option<String>,
+    pub date_of_birth: Option<NaiveDate>,
+}
+
+// filters of GET /users, see pagination.rs for paging
+#[derive(Default)]
+pub struct UserFilter {
+    pub country: Option<String>,
+    // registered at or after
+    pub since: Option<NaiveDateTime>,
+    // left out, the users blocked by or blocking the caller
+    pub hidden_ids: Vec<i32>,
+}
+
+// merge patch body of PATCH /users/<id>, see patch.rs
+#[derive(Deserialize, AsChangeset, Validate)]
+#[diesel(table_name=users)]
+#[serde(deny_unknown_fields)]
+pub struct UserPatch {
+    #[serde(default, deserialize_with = "crate::patch::non_null")]
+    #[validate(length(min = 1, max = 255), custom(function = "not_blank"))]
+    pub username: Option<String>,
+    #[serde(default, deserialize_with = "crate::patch::non_null")]
+    #[validate(email, length(max = 254))]
+    pub email: Option<String>,
+    #[serde(default, deserialize_with = "crate::patch::non_null")]
+    #[validate(length(min = 1, max = 255), custom(function = "not_blank"))]
+    pub full_name: Option<String>,
+    #[serde(default, deserialize_with = "crate::patch::nullable")]
+    pub avatar_id: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "crate::patch::nullable")]
+    #[validate(length(min = 1, max = 50), custom(function = "not_blank"))]
+    pub timezone: Option<Option<String>>,
+    #[serde(default, deserialize_with = "crate::patch::nullable")]
+    #[validate(length(min = 1, max = 50), custom(function = "not_blank"))]
+    pub language: Option<Option<String>>,
+    #[serde(default, deserialize_with = "crate::patch::nullable")]
+    #[validate(length(min = 1, max = 50), custom(function = "not_blank"))]
+    pub country: Option<Option<String>>,
+    #[serde(default, deserialize_with = "crate::patch::non_null")]
+    pub date_of_birth: Option<NaiveDate>,
+}
+
+// merge patch body of PATCH /me, only the profile fields a user may edit on their own account,
+// see patch.rs
+#[derive(Deserialize, AsChangeset, Validate)]
+#[diesel(table_name=users)]
+#[serde(deny_unknown_fields)]
+pub struct UpdateProfile {
+    #[serde(default, deserialize_with = "crate::patch::non_null")]
+    #[validate(length(min = 1, max = 255), custom(function = "not_blank"))]
+    pub full_name: Option<String>,
+    #[serde(default, deserialize_with = "crate::patch::nullable")]
+    #[validate(length(min = 1, max = 50), custom(function = "not_blank"))]
+    pub timezone: Option<Option<String>>,
+    #[serde(default, deserialize_with = "crate::patch::nullable")]
+    #[validate(length(min = 1, max = 50), custom(function = "not_blank"))]
+    pub language: Option<Option<String>>,
+    #[serde(default, deserialize_with = "crate::patch::nullable")]
+    #[validate(length(min = 1, max = 50), custom(function = "not_blank"))]
+    pub country: Option<Option<String>>,
+    #[serde(default, deserialize_with = "crate::patch::nullable")]
+    pub avatar_id: Option<Option<i32>>,
+}
+
 // ----------------- Role  -----------------
+pub const ADMIN_ROLE_CODE: &str = "admin";
+
 #[derive(Queryable, Debug)]
 pub struct Role {
     pub id: i32,
@@ -75,12 +319,48 @@ pub struct NewUserRole {
     pub role_id: i32,
 }
 
+// -----------------  TwoFactor  -----------------
+#[derive(Queryable, Associations, Identifiable, Debug)]
+#[diesel(belongs_to(User))]
+#[diesel(primary_key(user_id))]
+#[diesel(table_name=two_factor_secrets)]
+pub struct TwoFactorSecret {
+    pub user_id: i32,
+    pub secret: String,
+    pub confirmed_at: Option<NaiveDateTime>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name=two_factor_secrets)]
+pub struct NewTwoFactorSecret {
+    pub user_id: i32,
+    pub secret: String,
+}
+
+#[derive(Queryable, Associations, Identifiable, Debug)]
+#[diesel(belongs_to(User))]
+#[diesel(table_name=recovery_codes)]
+pub struct RecoveryCode {
+    pub id: i32,
+    pub user_id: i32,
+    pub code_hash: String,
+    pub used_at: Option<NaiveDateTime>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name=recovery_codes)]
+pub struct NewRecoveryCode {
+    pub user_id: i32,
+    pub code_hash: String,
+}
 
 // -----------------  Image  -----------------
-#[derive(Queryable, AsChangeset, Serialize, Deserialize, Debug)]
+#[derive(Queryable, AsChangeset, Serialize, Deserialize, Debug, Validate)]
 pub struct Image {
     #[serde(skip_deserializing)]
     pub image_id: i32,
+    #[validate(url, length(max = 255))]
     pub image_url: String,
     pub description: Option<String>,
     #[serde(skip_deserializing)]
@@ -88,118 +368,472 @@ pub struct Image {
 }
 
 
-#[derive(Insertable, Deserialize)]
+#[derive(Insertable, Deserialize, Validate)]
 #[diesel(table_name=images)]
 pub struct NewImage {
+    #[validate(url, length(max = 255))]
     pub image_url: String,
     pub description: Option<String>
 }
 
+#[derive(Default)]
+pub struct ImageFilter {
+    // uploaded at or after
+    pub since: Option<NaiveDateTime>,
+}
+
+// merge patch bodies, see patch.rs
+#[derive(Deserialize, AsChangeset, Validate)]
+#[diesel(table_name=images)]
+#[serde(deny_unknown_fields)]
+pub struct ImagePatch {
+    #[serde(default, deserialize_with = "crate::patch::non_null")]
+    #[validate(url, length(max = 255))]
+    pub image_url: Option<String>,
+    #[serde(default, deserialize_with = "crate::patch::nullable")]
+    pub description: Option<Option<String>>,
+}
+
 // -----------------  TotalThrophies  -----------------
 
-#[derive(Queryable, Serialize, Deserialize, Debug)]
+#[derive(Queryable, Serialize, Deserialize, Debug, Validate)]
 
 #[diesel(belongs_to(User))]
 pub struct TotalThrophies {
     #[serde(skip_deserializing)]
     pub total_throphies_id: i32,
     pub user_id: Option<i32>,
+    #[validate(range(min = 0))]
     pub total: Option<i32>,
 }
-#[derive(Insertable, Deserialize)]
+#[derive(Insertable, Deserialize, Validate)]
 #[diesel(table_name=total_throphies)]
 
 pub struct NewTotalThrophies {
     pub user_id: Option<i32>,
+    #[validate(range(min = 0))]
     pub total: Option<i32>
 }
 
+#[derive(Default)]
+pub struct TotalThrophiesFilter {
+    pub user_id: Option<i32>,
+}
+
+#[derive(Deserialize, AsChangeset, Validate)]
+#[diesel(table_name=total_throphies)]
+#[serde(deny_unknown_fields)]
+pub struct TotalThrophiesPatch {
+    #[serde(default, deserialize_with = "crate::patch::nullable")]
+    pub user_id: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "crate::patch::nullable")]
+    #[validate(range(min = 0))]
+    pub total: Option<Option<i32>>,
+}
+
 // -----------------  Trophies  -----------------
-#[derive(Queryable, Serialize, Deserialize, Debug)]
+#[derive(Queryable, Serialize, Deserialize, Debug, Validate)]
 
 #[diesel(belongs_to(User))]
 pub struct Trophy {
     #[serde(skip_deserializing)]
     pub trophy_id: i32,
     pub user_id: Option<i32>,
+    #[validate(range(min = 0))]
     pub points: Option<i32>,
     #[serde(skip_deserializing)]
     pub game_timestamp: Option<NaiveDateTime>,
 }
 
-#[derive(Insertable, Deserialize)]
+#[derive(Insertable, Serialize, Deserialize, Validate)]
 #[diesel(table_name=trophies)]
 pub struct NewTrophy {
     pub user_id: Option<i32>,
+    #[validate(range(min = 0))]
     pub points: Option<i32>
 }
 
+#[derive(Default)]
+pub struct TrophyFilter {
+    pub user_id: Option<i32>,
+    // won at or after
+    pub since: Option<NaiveDateTime>,
+}
+
+#[derive(Deserialize, AsChangeset, Validate)]
+#[diesel(table_name=trophies)]
+#[serde(deny_unknown_fields)]
+pub struct TrophyPatch {
+    #[serde(default, deserialize_with = "crate::patch::nullable")]
+    pub user_id: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "crate::patch::nullable")]
+    #[validate(range(min = 0))]
+    pub points: Option<Option<i32>>,
+}
+
 // -----------------  UserLevel  -----------------
-#[derive(Queryable, AsChangeset, Serialize, Deserialize, Debug)]
+#[derive(Queryable, AsChangeset, Serialize, Deserialize, Debug, Validate)]
 
 #[diesel(belongs_to(User))]
 pub struct UserLevel {
     #[serde(skip_deserializing)]
     pub user_level_id: i32,
     pub user_id: Option<i32>,
+    #[validate(range(min = 1))]
     pub level: Option<i32>,
+    #[validate(range(min = 0))]
     pub experience_points: Option<i32>,
 }
 
-#[derive(Insertable, Deserialize)]
+#[derive(Insertable, Serialize, Deserialize, Validate)]
 #[diesel(table_name=user_levels)]
 pub struct NewUserLevel {
     pub user_id: Option<i32>,
+    #[validate(range(min = 1))]
     pub level: Option<i32>,
+    #[validate(range(min = 0))]
     pub experience_points: Option<i32>,
 }
 
+#[derive(Default)]
+pub struct UserLevelFilter {
+    pub user_id: Option<i32>,
+}
+
+#[derive(Deserialize, AsChangeset, Validate)]
+#[diesel(table_name=user_levels)]
+#[serde(deny_unknown_fields)]
+pub struct UserLevelPatch {
+    #[serde(default, deserialize_with = "crate::patch::nullable")]
+    #[validate(range(min = 1))]
+    pub level: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "crate::patch::nullable")]
+    #[validate(range(min = 0))]
+    pub experience_points: Option<Option<i32>>,
+}
+
 // -----------------  Chat  -----------------
-#[derive(Queryable, AsChangeset, Serialize, Deserialize, Debug)]
+#[derive(Queryable, QueryableByName, AsChangeset, Serialize, Deserialize, Debug, Validate)]
 pub struct Chat {
     #[serde(skip_deserializing)]
     pub chat_id: i32,
     pub sender_id: Option<i32>,
     pub receiver_id: Option<i32>,
+    #[validate(length(min = 1, max = 1000))]
     pub message: Option<String>,
     #[serde(skip_deserializing)]
     pub timestamp: Option<NaiveDateTime>,
     pub is_read: Option<bool>,
 }
 
-#[derive(Insertable, Deserialize)]
+#[derive(Insertable, Deserialize, Validate)]
 #[diesel(table_name=chats)]
 pub struct NewChat {
     pub sender_id: Option<i32>,
     pub receiver_id: Option<i32>,
+    #[validate(length(min = 1, max = 1000))]
     pub message: Option<String>,
 }
 
+#[derive(Default)]
+pub struct ChatFilter {
+    // one id: chats the user sent or received, two ids: chats between the two
+    pub participant_ids: Vec<i32>,
+    // sent at or after
+    pub since: Option<NaiveDateTime>,
+}
+
+// one entry of a user's inbox, all chats between them and one other user
+#[derive(QueryableByName, Serialize)]
+pub struct Conversation {
+    // the other participant
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    pub user_id: i32,
+    #[diesel(embed)]
+    pub last_message: Chat,
+    // chats the other participant sent that are not read yet
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub unread_count: i64,
+}
+
+// who sent a message to whom can't be patched
+#[derive(Deserialize, AsChangeset, Validate)]
+#[diesel(table_name=chats)]
+#[serde(deny_unknown_fields)]
+pub struct ChatPatch {
+    #[serde(default, deserialize_with = "crate::patch::nullable")]
+    #[validate(length(min = 1, max = 1000))]
+    pub message: Option<Option<String>>,
+    #[serde(default, deserialize_with = "crate::patch::nullable")]
+    pub is_read: Option<Option<bool>>,
+}
+
+// -----------------  Room  -----------------
+// group chats, see RoomRepository
+pub const ROOM_OWNER: &str = "owner";
+pub const ROOM_MEMBER: &str = "member";
+
+#[derive(Queryable, Serialize, Debug)]
+pub struct Room {
+    pub room_id: i32,
+    pub name: String,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Deserialize, Validate)]
+#[diesel(table_name=rooms)]
+pub struct NewRoom {
+    #[validate(length(min = 1, max = 255), custom(function = "not_blank"))]
+    pub name: String,
+}
+
+#[derive(Deserialize, AsChangeset, Validate)]
+#[diesel(table_name=rooms)]
+#[serde(deny_unknown_fields)]
+pub struct RoomPatch {
+    #[serde(default, deserialize_with = "crate::patch::non_null")]
+    #[validate(length(min = 1, max = 255), custom(function = "not_blank"))]
+    pub name: Option<String>,
+}
+
+#[derive(Default)]
+pub struct RoomFilter {
+    // rooms this user is a member of
+    pub member_id: Option<i32>,
+}
+
+#[derive(Queryable, Serialize, Debug)]
+pub struct RoomMember {
+    pub room_id: i32,
+    pub user_id: i32,
+    pub role: String,
+    pub joined_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name=room_members)]
+pub struct NewRoomMember {
+    pub room_id: i32,
+    pub user_id: i32,
+    pub role: String,
+}
+
+#[derive(Queryable, Serialize, Debug)]
+pub struct RoomMessage {
+    pub message_id: i32,
+    pub room_id: i32,
+    pub sender_id: i32,
+    pub message: String,
+    pub timestamp: NaiveDateTime,
+}
+
+// the body of POST /rooms/<id>/messages, the room comes from the path and the sender is the caller
+#[derive(Insertable, Deserialize, Validate)]
+#[diesel(table_name=room_messages)]
+pub struct NewRoomMessage {
+    #[serde(skip_deserializing)]
+    pub room_id: i32,
+    #[serde(skip_deserializing)]
+    pub sender_id: i32,
+    #[validate(length(min = 1, max = 1000))]
+    pub message: String,
+}
+
+// -----------------  Currency types  -----------------
+// codes are compared trimmed and in lower case, "Gold " is gold
+pub fn normalize_currency_type(code: &str) -> String {
+    code.trim().to_lowercase()
+}
+
+fn currency_code(code: &str) -> Result<(), ValidationError> {
+    if !code.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_') {
+        return Err(ValidationError::new("currency_code")
+            .with_message("must only contain lower case letters, digits and _".into()));
+    }
+    Ok(())
+}
+
+#[derive(Queryable, Serialize, Debug)]
+pub struct CurrencyType {
+    pub code: String,
+    pub display_name: String,
+    // no limit when None
+    pub max_balance: Option<i32>,
+    pub tradeable: bool,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Deserialize, Validate)]
+#[diesel(table_name=currency_types)]
+#[serde(deny_unknown_fields)]
+pub struct NewCurrencyType {
+    #[validate(length(min = 1, max = 50), custom(function = "currency_code"))]
+    pub code: String,
+    #[validate(length(min = 1, max = 255), custom(function = "not_blank"))]
+    pub display_name: String,
+    #[validate(range(min = 0))]
+    pub max_balance: Option<i32>,
+    // tradeable when left out
+    pub tradeable: Option<bool>,
+}
+
+// the code of a type can't be patched
+#[derive(Deserialize, AsChangeset, Validate)]
+#[diesel(table_name=currency_types)]
+#[serde(deny_unknown_fields)]
+pub struct CurrencyTypePatch {
+    #[serde(default, deserialize_with = "crate::patch::non_null")]
+    #[validate(length(min = 1, max = 255), custom(function = "not_blank"))]
+    pub display_name: Option<String>,
+    #[serde(default, deserialize_with = "crate::patch::nullable")]
+    #[validate(range(min = 0))]
+    pub max_balance: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "crate::patch::non_null")]
+    pub tradeable: Option<bool>,
+}
+
+// a line of GET /me/wallet, every type is listed, 0 for the ones the user has no wallet of
+#[derive(Serialize)]
+pub struct WalletBalance {
+    pub currency_type: String,
+    pub display_name: String,
+    pub balance: i32,
+    pub max_balance: Option<i32>,
+    pub tradeable: bool,
+}
+
+impl From<(CurrencyType, Option<i32>)> for WalletBalance {
+    fn from((currency_type, balance): (CurrencyType, Option<i32>)) -> Self {
+        WalletBalance {
+            currency_type: currency_type.code,
+            display_name: currency_type.display_name,
+            balance: balance.unwrap_or(0),
+            max_balance: currency_type.max_balance,
+            tradeable: currency_type.tradeable,
+        }
+    }
+}
+
 // -----------------  Currency  -----------------
-#[derive(Queryable, Serialize, Deserialize, Debug)]
+#[derive(Queryable, Serialize, Deserialize, Debug, Validate)]
 
 #[diesel(belongs_to(User))]
 pub struct Currency {
     #[serde(skip_deserializing)]
     pub currency_id: i32,
     pub user_id: Option<i32>,
+    #[validate(length(min = 1, max = 50), custom(function = "not_blank"))]
     pub currency_type: Option<String>,
+    #[validate(range(min = 0))]
     pub amount: Option<i32>,
     #[serde(skip_deserializing)]
     pub last_updated: Option<NaiveDateTime>,
 }
 
-#[derive(Insertable, Deserialize)]
+#[derive(Insertable, Serialize, Deserialize, Validate)]
 #[diesel(table_name=currency)]
 pub struct NewCurrency {
     pub user_id: Option<i32>,
+    #[validate(length(min = 1, max = 50), custom(function = "not_blank"))]
     pub currency_type: Option<String>,
+    #[validate(range(min = 0))]
     pub amount: Option<i32>,
 }
 
+#[derive(Default)]
+pub struct CurrencyFilter {
+    pub user_id: Option<i32>,
+    pub currency_type: Option<String>,
+}
+
+#[derive(Deserialize, AsChangeset, Validate)]
+#[diesel(table_name=currency)]
+#[serde(deny_unknown_fields)]
+pub struct CurrencyPatch {
+    #[serde(default, deserialize_with = "crate::patch::nullable")]
+    pub user_id: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "crate::patch::nullable")]
+    #[validate(length(min = 1, max = 50), custom(function = "not_blank"))]
+    pub currency_type: Option<Option<String>>,
+    #[serde(default, deserialize_with = "crate::patch::nullable")]
+    #[validate(range(min = 0))]
+    pub amount: Option<Option<i32>>,
+}
+
+// body of POST /users/<id>/currency/credit and /debit, the wallet is the user's currency row of the type
+#[derive(Serialize, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct BalanceChange {
+    #[validate(length(min = 1, max = 50), custom(function = "not_blank"))]
+    pub currency_type: String,
+    #[validate(range(min = 1))]
+    pub amount: i32,
+    // booked as the reason of the transaction, credit or debit when left out
+    #[validate(length(min = 1, max = 50), custom(function = "not_blank"))]
+    pub reason: Option<String>,
+    #[validate(length(max = 255))]
+    pub reference: Option<String>,
+}
+
+// body of POST /users/<id>/currency/transfer
+#[derive(Serialize, Deserialize, Validate)]
+#[serde(deny_unknown_fields)]
+pub struct Transfer {
+    pub to_user_id: i32,
+    #[validate(length(min = 1, max = 50), custom(function = "not_blank"))]
+    pub currency_type: String,
+    #[validate(range(min = 1))]
+    pub amount: i32,
+}
+
+// both wallets after a transfer
+#[derive(Serialize)]
+pub struct TransferResult {
+    pub from: Currency,
+    pub to: Currency,
+}
+
+// -----------------  Currency transactions  -----------------
+// the append only ledger behind the currency amounts, see CurrencyRepository
+// every change of an amount is booked here in the same database transaction
+pub const LEDGER_ADJUSTMENT: &str = "adjustment";
+pub const LEDGER_CREDIT: &str = "credit";
+pub const LEDGER_DEBIT: &str = "debit";
+pub const LEDGER_TRANSFER_OUT: &str = "transfer_out";
+pub const LEDGER_TRANSFER_IN: &str = "transfer_in";
+
+#[derive(Queryable, Serialize, Debug)]
+pub struct CurrencyTransaction {
+    pub transaction_id: i32,
+    pub user_id: i32,
+    pub currency_type: String,
+    pub delta: i32,
+    pub reason: String,
+    // what caused the booking, e.g. currency/12 for a changed currency row
+    pub reference: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name=currency_transactions)]
+pub struct NewCurrencyTransaction {
+    pub user_id: i32,
+    pub currency_type: String,
+    pub delta: i32,
+    pub reason: String,
+    pub reference: Option<String>,
+}
+
+#[derive(Default)]
+pub struct CurrencyTransactionFilter {
+    pub user_id: Option<i32>,
+    pub currency_type: Option<String>,
+    // booked at or after
+    pub since: Option<NaiveDateTime>,
+}
 
 // -----------------  Friendship  -----------------
-#[derive(Queryable, AsChangeset, Serialize, Deserialize, Debug)]
+#[derive(Queryable, AsChangeset, Serialize, Deserialize, Debug, Validate)]
 
 #[diesel(belongs_to(User))]
 pub struct Friendship {
@@ -207,15 +841,79 @@ pub struct Friendship {
     pub friendship_id: i32,
     pub user_id: Option<i32>,
     pub friend_id: Option<i32>,
+    #[validate(custom(function = "friendship_status"))]
     pub status: String,
     #[serde(skip_deserializing)]
     pub friendship_date: Option<NaiveDateTime>,
 }
 
-#[derive(Insertable, Deserialize)]
+#[derive(Insertable, Deserialize, Validate)]
 #[diesel(table_name=friendships)]
 pub struct NewFriendship {
     pub user_id: Option<i32>,
     pub friend_id: Option<i32>,
+    #[validate(custom(function = "friendship_status"))]
     pub status: String,
 }
+
+#[derive(Default)]
+pub struct FriendshipFilter {
+    // one id: friendships of the user, two ids: the friendship between the two
+    pub participant_ids: Vec<i32>,
+    // the side that sent the request (user_id) and the side that received it (friend_id)
+    pub sender_id: Option<i32>,
+    pub recipient_id: Option<i32>,
+    pub status: Option<String>,
+    // created at or after
+    pub since: Option<NaiveDateTime>,
+    // leaves out the blocks of other users against this one, nobody learns who blocked them
+    pub viewer_id: Option<i32>,
+}
+
+// a "people you may know" candidate, see FriendshipRepository::find_suggestions
+#[derive(QueryableByName)]
+pub struct FriendSuggestion {
+    #[diesel(embed)]
+    pub user: User,
+    // friends the candidate shares with the user
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub mutual_friends: i64,
+    #[diesel(sql_type = diesel::sql_types::Integer)]
+    pub score: i32,
+}
+
+#[derive(Serialize)]
+pub struct FriendSuggestionView {
+    pub user: PublicUserView,
+    pub mutual_friends: i64,
+    pub score: i32,
+}
+
+impl From<FriendSuggestion> for FriendSuggestionView {
+    fn from(suggestion: FriendSuggestion) -> Self {
+        FriendSuggestionView {
+            user: PublicUserView::from(suggestion.user),
+            mutual_friends: suggestion.mutual_friends,
+            score: suggestion.score,
+        }
+    }
+}
+
+// the two sides of a friendship can't be patched
+#[derive(Deserialize, AsChangeset, Validate)]
+#[diesel(table_name=friendships)]
+#[serde(deny_unknown_fields)]
+pub struct FriendshipPatch {
+    #[serde(default, deserialize_with = "crate::patch::non_null")]
+    #[validate(custom(function = "friendship_status"))]
+    pub status: Option<String>,
+}
+
+// -----------------  Idempotency  -----------------
+// what is kept in redis for an Idempotency-Key, status and body stay empty while the first request runs
+#[derive(Serialize, Deserialize)]
+pub struct IdempotencyRecord {
+    pub fingerprint: String,
+    pub status: Option<u16>,
+    pub body: Option<serde_json::Value>,
+}
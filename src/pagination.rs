@@ -0,0 +1,355 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use diesel::dsl;
+use diesel::prelude::*;
+use diesel::query_dsl::methods::{FilterDsl, LimitDsl, OrderDsl};
+use diesel::sql_types::{Integer, Nullable};
+use rocket::form::{self, FromFormField, ValueField};
+use serde::Serialize;
+use serde_json::json;
+use validator::Validate;
+
+use crate::models::*;
+use crate::rocket_routes::error::ApiError;
+
+/*
+    Keyset pagination for the list routes
+    - ?limit= rows per page, 1 to 100 (default 50)
+    - ?sort= one of the sort keys of the resource, a leading - sorts descending (default -id, newest first)
+    - ?cursor= the next_cursor of the previous page, it only works with the sort it was made for
+    every page is {"items":[...], "next_cursor":"..."}, next_cursor is null on the last page
+    sort keys are integer columns, nulls sort as 0, ties are broken by the id so no row is skipped
+    or repeated when rows are added between two requests
+    the filters (?user_id=, ?since=, ...) are plain query parameters of each route
+*/
+
+pub const DEFAULT_PAGE_SIZE: i64 = 50;
+pub const MAX_PAGE_SIZE: i64 = 100;
+
+// ?limit=&sort=&cursor=, list routes take it as <page..> after their own filters
+#[derive(rocket::FromForm, Validate, Default)]
+pub struct PageParams {
+    #[validate(range(min = 1, max = MAX_PAGE_SIZE))]
+    pub limit: Option<i64>,
+    pub sort: Option<String>,
+    pub cursor: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Direction {
+    Asc,
+    Desc,
+}
+
+// a checked PageParams
+pub struct Page {
+    pub limit: i64,
+    pub sort: &'static str,
+    pub direction: Direction,
+    // sort value and id of the last row of the previous page
+    after: Option<(i32, i32)>,
+}
+
+// rows after the cursor, (key > value) or (key = value and id > last id), < for descending pages
+type AfterAsc<K, I> = dsl::Or<dsl::Gt<K, i32>, dsl::And<dsl::Eq<K, i32>, dsl::Gt<I, i32>>>;
+type AfterDesc<K, I> = dsl::Or<dsl::Lt<K, i32>, dsl::And<dsl::Eq<K, i32>, dsl::Lt<I, i32>>>;
+
+// nullable sort keys go through coalesce(column, 0)
+sql_function!(fn coalesce(x: Nullable<Integer>, y: Integer) -> Integer);
+
+// the models that can be listed, sort_value has to match the sort key the repository orders by
+pub trait Paginated {
+    const SORT_KEYS: &'static [&'static str];
+
+    fn id(&self) -> i32;
+
+    fn sort_value(&self, sort: &str) -> i32;
+}
+
+impl PageParams {
+    pub fn page<T: Paginated>(self) -> Result<Page, ApiError> {
+        self.validate()?;
+
+        let sort = self.sort.as_deref().unwrap_or("-id");
+        let (direction, key) = match sort.strip_prefix('-') {
+            Some(key) => (Direction::Desc, key),
+            None => (Direction::Asc, sort),
+        };
+        let Some(key) = T::SORT_KEYS.iter().find(|allowed| **allowed == key) else {
+            let allowed: Vec<String> = T::SORT_KEYS.iter().flat_map(|key| [key.to_string(), format!("-{}", key)]).collect();
+            return Err(ApiError::UnprocessableEntity("Invalid sort".to_owned(), json!({
+                "sort": [{ "code": "sort", "message": format!("must be one of {}", allowed.join(", ")) }],
+            })));
+        };
+
+        let after = self.cursor
+            .map(|cursor| decode_cursor(&cursor, sort).ok_or_else(|| ApiError::BadRequest("Invalid cursor".to_owned())))
+            .transpose()?;
+
+        Ok(Page {
+            limit: self.limit.unwrap_or(DEFAULT_PAGE_SIZE),
+            sort: key,
+            direction,
+            after,
+        })
+    }
+}
+
+impl Page {
+    // filters, orders and limits a query to this page, key is the sort key expression and id the primary key
+    // repositories call it once per sort key, the query is usually boxed so every call returns the same type
+    pub fn apply<Q, K, I>(&self, query: Q, key: K, id: I) -> Q
+    where
+        K: Expression<SqlType = Integer> + Clone,
+        I: Expression<SqlType = Integer> + Clone,
+        Q: FilterDsl<AfterAsc<K, I>, Output = Q>
+            + FilterDsl<AfterDesc<K, I>, Output = Q>
+            + OrderDsl<(dsl::Asc<K>, dsl::Asc<I>), Output = Q>
+            + OrderDsl<(dsl::Desc<K>, dsl::Desc<I>), Output = Q>
+            + LimitDsl<Output = Q>,
+    {
+        let query = match (self.direction, self.after) {
+            (_, None) => query,
+            (Direction::Asc, Some((value, last_id))) => FilterDsl::filter(query,
+                key.clone().gt(value).or(key.clone().eq(value).and(id.clone().gt(last_id)))),
+            (Direction::Desc, Some((value, last_id))) => FilterDsl::filter(query,
+                key.clone().lt(value).or(key.clone().eq(value).and(id.clone().lt(last_id)))),
+        };
+        let query = match self.direction {
+            Direction::Asc => OrderDsl::order(query, (key.asc(), id.asc())),
+            Direction::Desc => OrderDsl::order(query, (key.desc(), id.desc())),
+        };
+        // one row more than the limit, that is how finish recognizes the last page
+        LimitDsl::limit(query, self.limit + 1)
+    }
+
+    // sort value and id of the last row of the previous page, for queries apply can't build
+    pub fn after(&self) -> Option<(i32, i32)> {
+        self.after
+    }
+
+    pub fn finish<T: Paginated>(&self, mut rows: Vec<T>) -> PageOf<T> {
+        let has_more = rows.len() as i64 > self.limit;
+        rows.truncate(self.limit as usize);
+        let next_cursor = rows.last()
+            .filter(|_| has_more)
+            .map(|row| self.encode_cursor(row.sort_value(self.sort), row.id()));
+        PageOf { items: rows, next_cursor }
+    }
+
+    fn encode_cursor(&self, value: i32, id: i32) -> String {
+        let sort = match self.direction {
+            Direction::Asc => self.sort.to_owned(),
+            Direction::Desc => format!("-{}", self.sort),
+        };
+        URL_SAFE_NO_PAD.encode(format!("{}:{}:{}", sort, value, id))
+    }
+}
+
+// cursors are base64url("{sort}:{value}:{id}"), they are not signed, a forged one only moves the page
+fn decode_cursor(cursor: &str, sort: &str) -> Option<(i32, i32)> {
+    let decoded = String::from_utf8(URL_SAFE_NO_PAD.decode(cursor).ok()?).ok()?;
+    let mut parts = decoded.rsplitn(3, ':');
+    let id = parts.next()?.parse().ok()?;
+    let value = parts.next()?.parse().ok()?;
+    (parts.next()? == sort).then_some((value, id))
+}
+
+#[derive(Serialize)]
+pub struct PageOf<T> {
+    pub items: Vec<T>,
+    pub next_cursor: Option<String>,
+}
+
+impl<T> PageOf<T> {
+    pub fn map<U>(self, f: impl FnMut(T) -> U) -> PageOf<U> {
+        PageOf { items: self.items.into_iter().map(f).collect(), next_cursor: self.next_cursor }
+    }
+}
+
+//------------- ?since= -------------
+// an RFC 3339 timestamp or a plain date, read as UTC
+pub struct Timestamp(pub NaiveDateTime);
+
+impl<'v> FromFormField<'v> for Timestamp {
+    fn from_value(field: ValueField<'v>) -> form::Result<'v, Self> {
+        DateTime::parse_from_rfc3339(field.value)
+            .map(|timestamp| timestamp.naive_utc())
+            .or_else(|_| NaiveDate::parse_from_str(field.value, "%Y-%m-%d").map(|date| date.and_time(Default::default())))
+            .map(Timestamp)
+            .map_err(|_| form::Error::validation("must be an RFC 3339 timestamp or a date").into())
+    }
+}
+
+//------------- sort keys -------------
+impl Paginated for User {
+    const SORT_KEYS: &'static [&'static str] = &["id"];
+
+    fn id(&self) -> i32 {
+        self.user_id
+    }
+
+    fn sort_value(&self, _sort: &str) -> i32 {
+        self.user_id
+    }
+}
+
+impl Paginated for Image {
+    const SORT_KEYS: &'static [&'static str] = &["id"];
+
+    fn id(&self) -> i32 {
+        self.image_id
+    }
+
+    fn sort_value(&self, _sort: &str) -> i32 {
+        self.image_id
+    }
+}
+
+impl Paginated for TotalThrophies {
+    const SORT_KEYS: &'static [&'static str] = &["id", "total"];
+
+    fn id(&self) -> i32 {
+        self.total_throphies_id
+    }
+
+    fn sort_value(&self, sort: &str) -> i32 {
+        match sort {
+            "total" => self.total.unwrap_or(0),
+            _ => self.total_throphies_id,
+        }
+    }
+}
+
+impl Paginated for Trophy {
+    const SORT_KEYS: &'static [&'static str] = &["id", "points"];
+
+    fn id(&self) -> i32 {
+        self.trophy_id
+    }
+
+    fn sort_value(&self, sort: &str) -> i32 {
+        match sort {
+            "points" => self.points.unwrap_or(0),
+            _ => self.trophy_id,
+        }
+    }
+}
+
+impl Paginated for UserLevel {
+    const SORT_KEYS: &'static [&'static str] = &["id", "level", "experience_points"];
+
+    fn id(&self) -> i32 {
+        self.user_level_id
+    }
+
+    fn sort_value(&self, sort: &str) -> i32 {
+        match sort {
+            "level" => self.level.unwrap_or(0),
+            "experience_points" => self.experience_points.unwrap_or(0),
+            _ => self.user_level_id,
+        }
+    }
+}
+
+impl Paginated for Chat {
+    const SORT_KEYS: &'static [&'static str] = &["id"];
+
+    fn id(&self) -> i32 {
+        self.chat_id
+    }
+
+    fn sort_value(&self, _sort: &str) -> i32 {
+        self.chat_id
+    }
+}
+
+// conversations are listed by their last message
+impl Paginated for Conversation {
+    const SORT_KEYS: &'static [&'static str] = &["id"];
+
+    fn id(&self) -> i32 {
+        self.last_message.chat_id
+    }
+
+    fn sort_value(&self, _sort: &str) -> i32 {
+        self.last_message.chat_id
+    }
+}
+
+impl Paginated for Room {
+    const SORT_KEYS: &'static [&'static str] = &["id"];
+
+    fn id(&self) -> i32 {
+        self.room_id
+    }
+
+    fn sort_value(&self, _sort: &str) -> i32 {
+        self.room_id
+    }
+}
+
+// members are listed by their user id
+impl Paginated for RoomMember {
+    const SORT_KEYS: &'static [&'static str] = &["id"];
+
+    fn id(&self) -> i32 {
+        self.user_id
+    }
+
+    fn sort_value(&self, _sort: &str) -> i32 {
+        self.user_id
+    }
+}
+
+impl Paginated for RoomMessage {
+    const SORT_KEYS: &'static [&'static str] = &["id"];
+
+    fn id(&self) -> i32 {
+        self.message_id
+    }
+
+    fn sort_value(&self, _sort: &str) -> i32 {
+        self.message_id
+    }
+}
+
+impl Paginated for Currency {
+    const SORT_KEYS: &'static [&'static str] = &["id", "amount"];
+
+    fn id(&self) -> i32 {
+        self.currency_id
+    }
+
+    fn sort_value(&self, sort: &str) -> i32 {
+        match sort {
+            "amount" => self.amount.unwrap_or(0),
+            _ => self.currency_id,
+        }
+    }
+}
+
+impl Paginated for CurrencyTransaction {
+    const SORT_KEYS: &'static [&'static str] = &["id"];
+
+    fn id(&self) -> i32 {
+        self.transaction_id
+    }
+
+    fn sort_value(&self, _sort: &str) -> i32 {
+        self.transaction_id
+    }
+}
+
+impl Paginated for Friendship {
+    const SORT_KEYS: &'static [&'static str] = &["id"];
+
+    fn id(&self) -> i32 {
+        self.friendship_id
+    }
+
+    fn sort_value(&self, _sort: &str) -> i32 {
+        self.friendship_id
+    }
+}
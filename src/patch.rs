@@ -0,0 +1,78 @@
+use std::ops::Deref;
+
+use diesel::QueryResult;
+use rocket::data::{self, Data, FromData};
+use rocket::http::Status;
+use rocket::serde::json::{self, Json};
+use rocket::Request;
+use serde::{Deserialize, Deserializer};
+
+/*
+    JSON merge patch (RFC 7396) for the PATCH routes
+    - a member that is left out keeps its column as it is
+    - a member set to null clears the column, only allowed for nullable columns
+    - anything else replaces the column
+    every resource has a *Patch struct in models.rs deriving AsChangeset, its nullable
+    columns are Option<Option<T>> using `nullable`, the others Option<T> using `non_null`
+*/
+
+// diesel can't build an UPDATE without columns, so an empty patch fails before it reaches
+// the database, this turns that into None and the repositories load the row unchanged
+pub fn applied<T>(result: QueryResult<T>) -> QueryResult<Option<T>> {
+    match result {
+        Err(diesel::result::Error::QueryBuilderError(_)) => Ok(None),
+        result => result.map(Some),
+    }
+}
+
+// request body of a PATCH route, only application/merge-patch+json is accepted
+pub struct MergePatch<T>(pub T);
+
+impl<T> MergePatch<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for MergePatch<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+#[rocket::async_trait]
+impl<'r, T: Deserialize<'r>> FromData<'r> for MergePatch<T> {
+    type Error = json::Error<'r>;
+
+    async fn from_data(request: &'r Request<'_>, data: Data<'r>) -> data::Outcome<'r, Self> {
+        let is_merge_patch = request.content_type()
+            .is_some_and(|content_type| content_type.top() == "application" && content_type.sub() == "merge-patch+json");
+        if !is_merge_patch {
+            return data::Outcome::Forward((data, Status::UnsupportedMediaType));
+        }
+        Json::<T>::from_data(request, data).await
+            .map(|json| MergePatch(json.into_inner()))
+    }
+}
+
+// absent -> None, null -> Some(None), value -> Some(Some(value)), use with #[serde(default)]
+pub fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+// absent -> None, value -> Some(value), null is rejected, use with #[serde(default)]
+pub fn non_null<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer)?
+        .map(Some)
+        .ok_or_else(|| serde::de::Error::custom("this field can not be null"))
+}
@@ -1,27 +1,118 @@
 use crate::{models::*, rocket_routes::CacheConn, schema::*};
-use crate::rocket_routes::server_error;
+use crate::config::{LoginThrottleConfig, SessionConfig};
+use crate::pagination::{coalesce, Direction, Page};
+use crate::patch;
+use crate::rocket_routes::error::ApiError;
 use crate::auth::*;
 
-use serde_json::Value;
-use diesel_async::{AsyncPgConnection, RunQueryDsl};
+use chrono::NaiveDateTime;
+use serde_json::json;
+use std::net::IpAddr;
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use scoped_futures::ScopedFutureExt;
 use diesel::prelude::*;
-use rocket::{response::status::Custom};
 use rocket_db_pools::Connection;
-use rocket_db_pools::deadpool_redis::redis::AsyncCommands;
+use rocket_db_pools::deadpool_redis::{self, redis::{self, AsyncCommands}};
 
 
 // ----------------- Seassions  -----------------
+// sessions live in redis under sessions/{session_id} -> user_id and expire after
+// session_ttl of inactivity, every session is issued together with a refresh token
+//   refresh_tokens/{token}          -> {user_id, session_id}
+//   sessions/{session_id}/refresh_token -> token
+// every user also has an index users/{user_id}/sessions holding the ids of their sessions
+// so all of them can be revoked at once
 pub struct SessionRepository;
 
 impl SessionRepository {
-    pub async fn create_session(cache: &mut Connection<CacheConn>, session_id: String, user_id: i32) -> Result<(), Custom<Value>> {
-        cache.set_ex::<String, i32, ()>(
-            format!("sessions/{}", session_id), 
-            user_id, 
-            3*60*60
-        )
-        .await
-        .map_err(|e| server_error(e.into()))
+    fn session_key(session_id: &str) -> String {
+        format!("sessions/{}", session_id)
+    }
+
+    fn session_refresh_token_key(session_id: &str) -> String {
+        format!("sessions/{}/refresh_token", session_id)
+    }
+
+    fn refresh_token_key(refresh_token: &str) -> String {
+        format!("refresh_tokens/{}", refresh_token)
+    }
+
+    fn user_sessions_key(user_id: i32) -> String {
+        format!("users/{}/sessions", user_id)
+    }
+
+    pub async fn create_session(cache: &mut Connection<CacheConn>, config: &SessionConfig, session_id: String, refresh_token: String, user_id: i32) -> Result<(), ApiError> {
+        let session_ttl = config.session_ttl as usize;
+        let refresh_token_ttl = config.refresh_token_ttl as usize;
+        let index_key = Self::user_sessions_key(user_id);
+        let refresh_token_key = Self::refresh_token_key(&refresh_token);
+        redis::pipe()
+            .atomic()
+            .set_ex(Self::session_key(&session_id), user_id, session_ttl).ignore()
+            .set_ex(Self::session_refresh_token_key(&session_id), &refresh_token, refresh_token_ttl).ignore()
+            .hset_multiple(&refresh_token_key, &[("user_id", user_id.to_string()), ("session_id", session_id.clone())]).ignore()
+            .expire(&refresh_token_key, refresh_token_ttl).ignore()
+            .sadd(&index_key, &session_id).ignore()
+            // the index lives as long as the newest refresh token
+            .expire(&index_key, refresh_token_ttl).ignore()
+            .query_async::<_, ()>(&mut **cache)
+            .await
+            .map_err(ApiError::from)
+    }
+
+    // sliding expiry, called every time a session is used
+    pub async fn touch_session(cache: &mut Connection<CacheConn>, config: &SessionConfig, session_id: &str) -> Result<(), ApiError> {
+        cache.expire::<String, ()>(Self::session_key(session_id), config.session_ttl as usize).await
+            .map_err(ApiError::from)
+    }
+
+    // consumes a refresh token, returns the user it belonged to and the session it was issued with
+    pub async fn take_refresh_token(cache: &mut Connection<CacheConn>, refresh_token: &str) -> Result<Option<(i32, String)>, ApiError> {
+        let refresh_token_key = Self::refresh_token_key(refresh_token);
+        let (user_id, session_id): (Option<i32>, Option<String>) = redis::pipe()
+            .atomic()
+            .hget(&refresh_token_key, "user_id")
+            .hget(&refresh_token_key, "session_id")
+            .del(&refresh_token_key).ignore()
+            .query_async(&mut **cache)
+            .await?;
+
+        Ok(user_id.zip(session_id))
+    }
+
+    pub async fn delete_session(cache: &mut Connection<CacheConn>, session_id: &str) -> Result<(), ApiError> {
+        let session_key = Self::session_key(session_id);
+        let session_refresh_token_key = Self::session_refresh_token_key(session_id);
+        let (user_id, refresh_token): (Option<i32>, Option<String>) = redis::pipe()
+            .get(&session_key)
+            .get(&session_refresh_token_key)
+            .query_async(&mut **cache)
+            .await?;
+
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .del(&session_key).ignore()
+            .del(&session_refresh_token_key).ignore();
+        if let Some(refresh_token) = refresh_token {
+            pipe.del(Self::refresh_token_key(&refresh_token)).ignore();
+        }
+        if let Some(user_id) = user_id {
+            pipe.srem(Self::user_sessions_key(user_id), session_id).ignore();
+        }
+        pipe.query_async::<_, ()>(&mut **cache)
+            .await
+            .map_err(ApiError::from)
+    }
+
+    pub async fn revoke_all(cache: &mut Connection<CacheConn>, user_id: i32) -> Result<(), ApiError> {
+        let index_key = Self::user_sessions_key(user_id);
+        let session_ids = cache.smembers::<&str, Vec<String>>(&index_key).await?;
+
+        for session_id in session_ids {
+            Self::delete_session(cache, &session_id).await?;
+        }
+        cache.del::<&str, ()>(&index_key).await
+            .map_err(ApiError::from)
     }
 
 }
@@ -57,8 +148,20 @@ impl UserRepository {  //CRUD operations for user
     pub async fn find(c: &mut AsyncPgConnection, id: i32) -> QueryResult<User> {
         users::table.find(id).get_result(c).await
     }
-    pub async fn find_multiple(c: &mut AsyncPgConnection, limit: i64) -> QueryResult<Vec<User>>{
-        users::table.limit(limit).get_results(c).await
+    // one page of users, see pagination.rs
+    pub async fn find_page(c: &mut AsyncPgConnection, filter: UserFilter, page: &Page) -> QueryResult<Vec<User>> {
+        let mut query = users::table.into_boxed();
+        if let Some(country) = filter.country {
+            query = query.filter(users::country.eq(country));
+        }
+        if let Some(since) = filter.since {
+            query = query.filter(users::registration_date.ge(since));
+        }
+        if !filter.hidden_ids.is_empty() {
+            query = query.filter(users::user_id.ne_all(filter.hidden_ids));
+        }
+        let query = page.apply(query, users::user_id, users::user_id);
+        query.get_results(c).await
     }
 
     pub async fn create(c: &mut AsyncPgConnection, mut new_user: NewUser, role_codes: Vec<String>) -> QueryResult<User> {
@@ -100,59 +203,152 @@ impl UserRepository {  //CRUD operations for user
 
     }
 
+    // a new email address is unverified again, see the users routes for the mail
     pub async fn update(c: &mut AsyncPgConnection, id: i32, user: User) -> QueryResult<User> {
+        c.transaction::<_, diesel::result::Error, _>(|c| async move {
+            Self::clear_email_verification(c, id, &user.email).await?;
+            diesel::update(users::table.find(id))
+                .set((
+                    users::username.eq(user.username),
+                    users::email.eq(user.email),
+                    users::full_name.eq(user.full_name),
+                    users::avatar_id.eq(user.avatar_id),
+                    users::last_login.eq(user.last_login),
+                    users::timezone.eq(user.timezone),
+                    users::language.eq(user.language),
+                    users::country.eq(user.country),
+                ))
+                .get_result(c)
+                .await
+        }.scope_boxed()).await
+    }
+
+    // only the columns present in the merge patch change
+    pub async fn patch(c: &mut AsyncPgConnection, id: i32, patch: UserPatch) -> QueryResult<User> {
+        c.transaction::<_, diesel::result::Error, _>(|c| async move {
+            if let Some(email) = &patch.email {
+                Self::clear_email_verification(c, id, email).await?;
+            }
+            match patch::applied(diesel::update(users::table.find(id)).set(&patch).get_result(c).await)? {
+                Some(row) => Ok(row),
+                None => Self::find(c, id).await,
+            }
+        }.scope_boxed()).await
+    }
+
+    // forgets the verification when `email` is not the address that was verified
+    async fn clear_email_verification(c: &mut AsyncPgConnection, id: i32, email: &str) -> QueryResult<usize> {
+        diesel::update(users::table.find(id).filter(users::email.ne(email)))
+            .set(users::email_verified_at.eq(None::<NaiveDateTime>))
+            .execute(c)
+            .await
+    }
+
+    pub async fn delete(c: &mut AsyncPgConnection, id: i32) -> QueryResult<usize> {
+        // delete all owned tables before deleting the user
+        // as of now user owns: 
+        // - user_roles, total_throphies, throphies, user_levels, currency, currency_transactions, friendships,
+        //   two_factor_secrets, recovery_codes, rooms (the ones they own), room_members, room_bans, room_messages
+
+        // one transaction, so app.ledger_erasure below stays limited to it
+        c.transaction::<_, diesel::result::Error, _>(|c| async move {
+            // delete the rooms the user owns, then what they did in other rooms
+            for room_id in RoomRepository::find_owned_by(c, id).await? {
+                RoomRepository::delete(c, room_id).await?;
+            }
+            diesel::delete(
+                room_messages::table.filter(room_messages::sender_id.eq(id))
+            ).execute(c).await?;
+            diesel::delete(
+                room_members::table.filter(room_members::user_id.eq(id))
+            ).execute(c).await?;
+            diesel::delete(
+                room_bans::table.filter(room_bans::user_id.eq(id))
+            ).execute(c).await?;
+
+            // delete two factor secrets and recovery codes
+            diesel::delete(
+                two_factor_secrets::table.filter(two_factor_secrets::user_id.eq(id))
+            ).execute(c).await?;
+            diesel::delete(
+                recovery_codes::table.filter(recovery_codes::user_id.eq(id))
+            ).execute(c).await?;
+            // delete user roles
+            diesel::delete(
+                users_roles::table.filter(users_roles::user_id.eq(id))
+            ).execute(c).await?;
+            // delete total throphies
+            diesel::delete(
+                total_throphies::table.filter(total_throphies::user_id.eq(id))
+            ).execute(c).await?;
+            // delete throphies
+            diesel::delete(
+                trophies::table.filter(trophies::user_id.eq(id))
+            ).execute(c).await?;
+            // delete user levels
+            diesel::delete(
+                user_levels::table.filter(user_levels::user_id.eq(id))
+            ).execute(c).await?;
+            // delete the currency ledger and currency, erasing a user is the one time bookings
+            // may be deleted, see the currency_transactions_no_delete trigger
+            diesel::sql_query("SET LOCAL app.ledger_erasure = 'on'").execute(c).await?;
+            diesel::delete(
+                currency_transactions::table.filter(currency_transactions::user_id.eq(id))
+            ).execute(c).await?;
+            diesel::delete(
+                currency::table.filter(currency::user_id.eq(id))
+            ).execute(c).await?;
+            // delete friendships and blocks, both the ones the user started and the ones they received
+            diesel::delete(
+                friendships::table.filter(friendships::user_id.eq(id).or(friendships::friend_id.eq(id)))
+            ).execute(c).await?;
+
+            diesel::delete(users::table.find(id)).execute(c).await
+        }.scope_boxed()).await
+    }
+
+    pub async fn update_profile(c: &mut AsyncPgConnection, id: i32, profile: UpdateProfile) -> QueryResult<User> {
+        match patch::applied(diesel::update(users::table.find(id)).set(&profile).get_result(c).await)? {
+            Some(user) => Ok(user),
+            None => Self::find(c, id).await,
+        }
+    }
+
+    // the password hash and its change date only ever change together
+    pub async fn update_password(c: &mut AsyncPgConnection, id: i32, password_hash: String) -> QueryResult<User> {
         diesel::update(users::table.find(id))
             .set((
-                users::username.eq(user.username),
-                users::email.eq(user.email),
-                users::password_hash.eq(user.password_hash),
-                users::full_name.eq(user.full_name),
-                users::avatar_id.eq(user.avatar_id),
-                users::last_login.eq(user.last_login),
-                users::is_active.eq(user.is_active),
-                users::is_admin.eq(user.is_admin),
-                users::timezone.eq(user.timezone),
-                users::language.eq(user.language),
-                users::country.eq(user.country),
-                users::two_factor_auth_enabled.eq(user.two_factor_auth_enabled),
-                users::last_password_change.eq(user.last_password_change),
+                users::password_hash.eq(password_hash),
+                users::last_password_change.eq(chrono::Utc::now().naive_utc()),
             ))
             .get_result(c)
             .await
     }
 
-    pub async fn delete(c: &mut AsyncPgConnection, id: i32) -> QueryResult<usize> {
-        // delete all owned tables before deleting the user
-        // as of now user owns: 
-        // - user_roles, total_throphies, throphies, user_levels, currency, friendships
+    // is_active is for admins only, it is not touched by email verification
+    pub async fn set_active(c: &mut AsyncPgConnection, id: i32, active: bool) -> QueryResult<User> {
+        diesel::update(users::table.find(id))
+            .set(users::is_active.eq(active))
+            .get_result(c)
+            .await
+    }
 
-        // delete user roles
-        diesel::delete(
-            users_roles::table.filter(users_roles::user_id.eq(id))
-        ).execute(c).await?;
-        // delete total throphies
-        diesel::delete(
-            total_throphies::table.filter(total_throphies::user_id.eq(id))
-        ).execute(c).await?;
-        // delete throphies
-        diesel::delete(
-            trophies::table.filter(trophies::user_id.eq(id))
-        ).execute(c).await?;
-        // delete user levels
-        diesel::delete(
-            user_levels::table.filter(user_levels::user_id.eq(id))
-        ).execute(c).await?;
-        // delete currency
-        diesel::delete(
-            currency::table.filter(currency::user_id.eq(id))
-        ).execute(c).await?;
-        // delete friendships
-        diesel::delete(
-            friendships::table.filter(friendships::user_id.eq(id))
-        ).execute(c).await?;
+    pub async fn mark_email_verified(c: &mut AsyncPgConnection, id: i32) -> QueryResult<User> {
+        diesel::update(users::table.find(id))
+            .set(users::email_verified_at.eq(chrono::Utc::now().naive_utc()))
+            .get_result(c)
+            .await
+    }
 
-        diesel::delete(users::table.find(id)).execute(c).await
+    pub async fn find_by_email(c: &mut AsyncPgConnection, email: &str) -> QueryResult<User> {
+        users::table.filter(users::email.eq(email)).get_result(c).await
+    }
 
+    pub async fn set_two_factor_enabled(c: &mut AsyncPgConnection, id: i32, enabled: bool) -> QueryResult<User> {
+        diesel::update(users::table.find(id))
+            .set(users::two_factor_auth_enabled.eq(enabled))
+            .get_result(c)
+            .await
     }
 
     pub async fn delete_by_username(c: &mut AsyncPgConnection, username: &String) -> QueryResult<usize> {
@@ -179,6 +375,379 @@ impl UserRepository {  //CRUD operations for user
 
 }
 
+// ----------------- Login challenges  -----------------
+// first step of a two factor login, login_challenges/{challenge} -> user_id for a few minutes
+// wrong codes are also counted per user in two_factor/{user_id}/failures, a new challenge
+// from POST /login doesn't start the count over
+pub struct LoginChallengeRepository;
+
+const LOGIN_CHALLENGE_TTL: usize = 5*60;
+const LOGIN_CHALLENGE_MAX_ATTEMPTS: i32 = 5;
+const TWO_FACTOR_FAILURE_WINDOW: usize = 15*60;
+const TWO_FACTOR_MAX_FAILURES: i32 = 10;
+
+impl LoginChallengeRepository {
+    fn challenge_key(challenge: &str) -> String {
+        format!("login_challenges/{}", challenge)
+    }
+
+    fn attempts_key(challenge: &str) -> String {
+        format!("login_challenges/{}/attempts", challenge)
+    }
+
+    fn failures_key(user_id: i32) -> String {
+        format!("two_factor/{}/failures", user_id)
+    }
+
+    pub async fn create(cache: &mut Connection<CacheConn>, challenge: &str, user_id: i32) -> Result<(), ApiError> {
+        cache.set_ex::<String, i32, ()>(Self::challenge_key(challenge), user_id, LOGIN_CHALLENGE_TTL).await
+            .map_err(ApiError::from)
+    }
+
+    // consumes the challenge so two requests can't answer it at once,
+    // returns the user it was issued for and the seconds it had left
+    pub async fn take(cache: &mut Connection<CacheConn>, challenge: &str) -> Result<Option<(i32, i64)>, ApiError> {
+        let challenge_key = Self::challenge_key(challenge);
+        let (user_id, ttl): (Option<i32>, i64) = redis::pipe()
+            .atomic()
+            .get(&challenge_key)
+            .ttl(&challenge_key)
+            .del(&challenge_key).ignore()
+            .query_async(&mut **cache)
+            .await?;
+        Ok(user_id.map(|user_id| (user_id, ttl)))
+    }
+
+    // seconds until the user may answer a challenge again, None if they are not locked
+    pub async fn locked_for(cache: &mut Connection<CacheConn>, user_id: i32) -> Result<Option<u64>, ApiError> {
+        let failures_key = Self::failures_key(user_id);
+        let (failures, ttl): (Option<i32>, i64) = redis::pipe()
+            .get(&failures_key)
+            .ttl(&failures_key)
+            .query_async(&mut **cache)
+            .await?;
+        Ok(match failures {
+            Some(failures) if failures >= TWO_FACTOR_MAX_FAILURES && ttl > 0 => Some(ttl as u64),
+            _ => None,
+        })
+    }
+
+    // counts a wrong code for the challenge and the user, the challenge is put back
+    // with the time it had left until it was guessed at too often
+    pub async fn record_failure(cache: &mut Connection<CacheConn>, challenge: &str, user_id: i32, ttl: i64) -> Result<(), ApiError> {
+        let attempts_key = Self::attempts_key(challenge);
+        let failures_key = Self::failures_key(user_id);
+        let (attempts,): (i32,) = redis::pipe()
+            .atomic()
+            .incr(&attempts_key, 1)
+            .expire(&attempts_key, LOGIN_CHALLENGE_TTL).ignore()
+            .incr(&failures_key, 1).ignore()
+            .expire(&failures_key, TWO_FACTOR_FAILURE_WINDOW).ignore()
+            .query_async(&mut **cache)
+            .await?;
+        if attempts < LOGIN_CHALLENGE_MAX_ATTEMPTS && ttl > 0 {
+            cache.set_ex::<String, i32, ()>(Self::challenge_key(challenge), user_id, ttl as usize).await?;
+        } else {
+            cache.del::<String, ()>(attempts_key).await?;
+        }
+        Ok(())
+    }
+
+    // a second factor that was answered right forgets the failures of the challenge and the user
+    pub async fn reset(cache: &mut Connection<CacheConn>, challenge: &str, user_id: i32) -> Result<(), ApiError> {
+        cache.del::<&[String], ()>(&[Self::attempts_key(challenge), Self::failures_key(user_id)]).await
+            .map_err(ApiError::from)
+    }
+
+    // a TOTP code is valid for a whole time step, remember used ones so they can not be replayed
+    pub async fn claim_totp_code(cache: &mut Connection<CacheConn>, user_id: i32, code: &str) -> Result<bool, ApiError> {
+        redis::cmd("SET")
+            .arg(format!("two_factor/{}/used_codes/{}", user_id, code.trim()))
+            .arg(1)
+            .arg("NX")
+            .arg("EX")
+            .arg(90)
+            .query_async::<_, Option<String>>(&mut **cache)
+            .await
+            .map(|claimed| claimed.is_some())
+            .map_err(ApiError::from)
+    }
+}
+
+// -----------------  Password resets  -----------------
+// password_resets/{token} -> user_id, single use and only valid for a while
+// password_resets/user/{user_id} -> token, a user has one active token, a new one replaces it
+// password_reset_requests/{email} -> count, forgotten an hour after the last request
+pub struct PasswordResetRepository;
+
+const PASSWORD_RESET_TTL: usize = 60*60;
+const PASSWORD_RESET_MAX_REQUESTS: u64 = 3;
+
+impl PasswordResetRepository {
+    fn reset_key(token: &str) -> String {
+        format!("password_resets/{}", token)
+    }
+
+    fn user_key(user_id: i32) -> String {
+        format!("password_resets/user/{}", user_id)
+    }
+
+    // the token mailed before stops working
+    pub async fn create(cache: &mut Connection<CacheConn>, token: &str, user_id: i32) -> Result<(), ApiError> {
+        let (previous,): (Option<String>,) = redis::pipe()
+            .atomic()
+            .get(Self::user_key(user_id))
+            .set_ex(Self::user_key(user_id), token, PASSWORD_RESET_TTL).ignore()
+            .set_ex(Self::reset_key(token), user_id, PASSWORD_RESET_TTL).ignore()
+            .query_async(&mut **cache)
+            .await?;
+        if let Some(previous) = previous {
+            cache.del::<String, ()>(Self::reset_key(&previous)).await?;
+        }
+        Ok(())
+    }
+
+    // consumes the token, returns the user it was issued for
+    pub async fn take(cache: &mut Connection<CacheConn>, token: &str) -> Result<Option<i32>, ApiError> {
+        let reset_key = Self::reset_key(token);
+        let (user_id,): (Option<i32>,) = redis::pipe()
+            .atomic()
+            .get(&reset_key)
+            .del(&reset_key).ignore()
+            .query_async(&mut **cache)
+            .await?;
+        if let Some(user_id) = user_id {
+            cache.del::<String, ()>(Self::user_key(user_id)).await?;
+        }
+        Ok(user_id)
+    }
+
+    // counts a reset request, returns the seconds to wait when the address asked too often
+    pub async fn record_request(cache: &mut Connection<CacheConn>, email: &str) -> Result<Option<u64>, ApiError> {
+        let requests = count_request(cache, &format!("password_reset_requests/{}", email), PASSWORD_RESET_TTL).await?;
+        Ok((requests > PASSWORD_RESET_MAX_REQUESTS).then_some(PASSWORD_RESET_TTL as u64))
+    }
+}
+
+// -----------------  Verification resends  -----------------
+// verification_resends/{email} -> count, forgotten an hour after the last request
+pub struct VerificationResendRepository;
+
+const VERIFICATION_RESEND_WINDOW: usize = 60*60;
+
+impl VerificationResendRepository {
+    // counts a resend, returns the seconds to wait when the address asked too often
+    pub async fn record(cache: &mut Connection<CacheConn>, email: &str, limit: u64) -> Result<Option<u64>, ApiError> {
+        let resends = count_request(cache, &format!("verification_resends/{}", email), VERIFICATION_RESEND_WINDOW).await?;
+        Ok((resends > limit).then_some(VERIFICATION_RESEND_WINDOW as u64))
+    }
+}
+
+// counts a request that mails an address, the count is forgotten `window` seconds after the last one
+async fn count_request(cache: &mut Connection<CacheConn>, key: &str, window: usize) -> Result<u64, ApiError> {
+    let (requests,): (u64,) = redis::pipe()
+        .atomic()
+        .incr(key, 1)
+        .expire(key, window).ignore()
+        .query_async(&mut **cache)
+        .await?;
+    Ok(requests)
+}
+
+// -----------------  LoginThrottle  -----------------
+// failed logins are counted per username and per ip address
+//   login_failures/{scope}/{id} -> count, forgotten after failed_login_window
+//   login_lockouts/{scope}/{id} -> 1, expires when the lockout is over
+pub struct LoginThrottleRepository;
+
+impl LoginThrottleRepository {
+    fn failures_key(scope: &str, id: &str) -> String {
+        format!("login_failures/{}/{}", scope, id)
+    }
+
+    fn lockout_key(scope: &str, id: &str) -> String {
+        format!("login_lockouts/{}/{}", scope, id)
+    }
+
+    // seconds until the username and ip may try again, None if neither is locked
+    pub async fn locked_for(cache: &mut Connection<CacheConn>, username: &str, ip: Option<IpAddr>) -> Result<Option<u64>, ApiError> {
+        let mut pipe = redis::pipe();
+        pipe.ttl(Self::lockout_key("username", username));
+        if let Some(ip) = ip {
+            pipe.ttl(Self::lockout_key("ip", &ip.to_string()));
+        }
+        let ttls: Vec<i64> = pipe.query_async(&mut **cache).await?;
+        Ok(ttls.into_iter().filter(|ttl| *ttl > 0).max().map(|ttl| ttl as u64))
+    }
+
+    pub async fn record_failure(cache: &mut Connection<CacheConn>, config: &LoginThrottleConfig, username: &str, ip: Option<IpAddr>) -> Result<(), ApiError> {
+        Self::record(cache, config, "username", username, config.max_failed_logins_per_username).await?;
+        if let Some(ip) = ip {
+            Self::record(cache, config, "ip", &ip.to_string(), config.max_failed_logins_per_ip).await?;
+        }
+        Ok(())
+    }
+
+    async fn record(cache: &mut Connection<CacheConn>, config: &LoginThrottleConfig, scope: &str, id: &str, limit: u64) -> Result<(), ApiError> {
+        let failures_key = Self::failures_key(scope, id);
+        let (failures,): (u64,) = redis::pipe()
+            .atomic()
+            .incr(&failures_key, 1)
+            .expire(&failures_key, config.failed_login_window as usize).ignore()
+            .query_async(&mut **cache)
+            .await?;
+        if let Some(lockout) = config.lockout_for(failures, limit) {
+            cache.set_ex::<String, i32, ()>(Self::lockout_key(scope, id), 1, lockout as usize).await?;
+        }
+        Ok(())
+    }
+
+    // the username and email existence checks tell which accounts exist, so every check
+    // counts against the ip like a failed login, with its own limit
+    pub async fn lookup_locked_for(cache: &mut Connection<CacheConn>, ip: IpAddr) -> Result<Option<u64>, ApiError> {
+        let ttl: i64 = cache.ttl(Self::lockout_key("lookup_ip", &ip.to_string())).await?;
+        Ok((ttl > 0).then_some(ttl as u64))
+    }
+
+    pub async fn record_lookup(cache: &mut Connection<CacheConn>, config: &LoginThrottleConfig, ip: IpAddr) -> Result<(), ApiError> {
+        Self::record(cache, config, "lookup_ip", &ip.to_string(), config.max_existence_checks_per_ip).await
+    }
+
+    // a successful login forgets the failures of the username
+    pub async fn reset(cache: &mut Connection<CacheConn>, username: &str) -> Result<(), ApiError> {
+        cache.del::<String, ()>(Self::failures_key("username", username)).await
+            .map_err(ApiError::from)
+    }
+}
+
+// -----------------  Idempotency  -----------------
+// responses to requests sent with an Idempotency-Key, idempotency/{user_id}/{key} -> IdempotencyRecord as json
+// a key is claimed with an empty record before the request runs, the claim expires quickly
+// so a request that never finished doesn't block its key for long, while the request runs
+// the claim is renewed, see rocket_routes::idempotency
+pub struct IdempotencyRepository;
+
+pub const IDEMPOTENCY_CLAIM_TTL: usize = 60;
+const IDEMPOTENCY_TTL: usize = 24*60*60;
+
+impl IdempotencyRepository {
+    fn idempotency_key(user_id: i32, key: &str) -> String {
+        format!("idempotency/{}/{}", user_id, key)
+    }
+
+    // None if the key was free and is now claimed, otherwise the record already kept under it
+    pub async fn claim(cache: &mut Connection<CacheConn>, user_id: i32, key: &str, fingerprint: &str) -> Result<Option<IdempotencyRecord>, ApiError> {
+        let idempotency_key = Self::idempotency_key(user_id, key);
+        let record = IdempotencyRecord { fingerprint: fingerprint.to_owned(), status: None, body: None };
+        let claimed = redis::cmd("SET")
+            .arg(&idempotency_key)
+            .arg(json!(record).to_string())
+            .arg("NX")
+            .arg("EX")
+            .arg(IDEMPOTENCY_CLAIM_TTL)
+            .query_async::<_, Option<String>>(&mut **cache)
+            .await?;
+        if claimed.is_some() {
+            return Ok(None);
+        }
+        // a claim that expired in between counts as still running
+        let stored = cache.get::<&str, Option<String>>(&idempotency_key).await?;
+        match stored {
+            Some(stored) => serde_json::from_str(&stored)
+                .map(Some)
+                .map_err(|e| ApiError::Internal(e.into())),
+            None => Ok(Some(record)),
+        }
+    }
+
+    // keeps the claim of a request that is still running from expiring
+    pub async fn renew_claim(cache: &mut Connection<CacheConn>, user_id: i32, key: &str) -> Result<(), ApiError> {
+        cache.expire::<String, ()>(Self::idempotency_key(user_id, key), IDEMPOTENCY_CLAIM_TTL).await
+            .map_err(ApiError::from)
+    }
+
+    pub async fn store(cache: &mut Connection<CacheConn>, user_id: i32, key: &str, record: &IdempotencyRecord) -> Result<(), ApiError> {
+        cache.set_ex::<String, String, ()>(Self::idempotency_key(user_id, key), json!(record).to_string(), IDEMPOTENCY_TTL).await
+            .map_err(ApiError::from)
+    }
+
+    // frees the key of a request that failed, so it can be sent again
+    pub async fn release(cache: &mut Connection<CacheConn>, user_id: i32, key: &str) -> Result<(), ApiError> {
+        cache.del::<String, ()>(Self::idempotency_key(user_id, key)).await
+            .map_err(ApiError::from)
+    }
+}
+
+// -----------------  TwoFactor  -----------------
+pub struct TwoFactorRepository;
+
+impl TwoFactorRepository {
+    pub async fn find(c: &mut AsyncPgConnection, user: &User) -> QueryResult<TwoFactorSecret> {
+        TwoFactorSecret::belonging_to(user).get_result(c).await
+    }
+
+    // starts a new enrollment, replacing any secret that was never confirmed
+    pub async fn create(c: &mut AsyncPgConnection, new_secret: NewTwoFactorSecret) -> QueryResult<TwoFactorSecret> {
+        diesel::insert_into(two_factor_secrets::table)
+            .values(&new_secret)
+            .on_conflict(two_factor_secrets::user_id)
+            .do_update()
+            .set((
+                two_factor_secrets::secret.eq(&new_secret.secret),
+                two_factor_secrets::confirmed_at.eq(None::<NaiveDateTime>),
+                two_factor_secrets::created_at.eq(diesel::dsl::now),
+            ))
+            .get_result(c)
+            .await
+    }
+
+    pub async fn confirm(c: &mut AsyncPgConnection, user_id: i32) -> QueryResult<TwoFactorSecret> {
+        diesel::update(two_factor_secrets::table.find(user_id))
+            .set(two_factor_secrets::confirmed_at.eq(diesel::dsl::now))
+            .get_result(c)
+            .await
+    }
+
+    pub async fn delete(c: &mut AsyncPgConnection, user_id: i32) -> QueryResult<usize> {
+        diesel::delete(
+            recovery_codes::table.filter(recovery_codes::user_id.eq(user_id))
+        ).execute(c).await?;
+        diesel::delete(two_factor_secrets::table.find(user_id)).execute(c).await
+    }
+
+    // replaces every recovery code of the user
+    pub async fn replace_recovery_codes(c: &mut AsyncPgConnection, user_id: i32, code_hashes: Vec<String>) -> QueryResult<usize> {
+        diesel::delete(
+            recovery_codes::table.filter(recovery_codes::user_id.eq(user_id))
+        ).execute(c).await?;
+
+        let new_codes: Vec<NewRecoveryCode> = code_hashes.into_iter()
+            .map(|code_hash| NewRecoveryCode { user_id, code_hash })
+            .collect();
+        diesel::insert_into(recovery_codes::table)
+            .values(&new_codes)
+            .execute(c)
+            .await
+    }
+
+    pub async fn find_recovery_codes(c: &mut AsyncPgConnection, user: &User) -> QueryResult<Vec<RecoveryCode>> {
+        RecoveryCode::belonging_to(user).load(c).await
+    }
+
+    // marks the code as used, false when it was already used in the meantime
+    pub async fn use_recovery_code(c: &mut AsyncPgConnection, id: i32) -> QueryResult<bool> {
+        diesel::update(
+            recovery_codes::table
+                .find(id)
+                .filter(recovery_codes::used_at.is_null())
+        )
+            .set(recovery_codes::used_at.eq(diesel::dsl::now))
+            .execute(c)
+            .await
+            .map(|updated| updated == 1)
+    }
+}
+
 // -----------------  Role  -----------------
 pub struct RoleRepository;
 
@@ -216,8 +785,13 @@ impl ImageRepository {  //CRUD operations for image
         images::table.find(id).get_result(c).await
     }
 
-    pub async fn find_multiple(c: &mut AsyncPgConnection, limit: i64) -> QueryResult<Vec<Image>>{
-        images::table.limit(limit).get_results(c).await
+    pub async fn find_page(c: &mut AsyncPgConnection, filter: ImageFilter, page: &Page) -> QueryResult<Vec<Image>> {
+        let mut query = images::table.into_boxed();
+        if let Some(since) = filter.since {
+            query = query.filter(images::upload_date.ge(since));
+        }
+        let query = page.apply(query, images::image_id, images::image_id);
+        query.get_results(c).await
     }
 
     pub async fn create(c: &mut AsyncPgConnection, new_image: NewImage) -> QueryResult<Image> {
@@ -237,6 +811,13 @@ impl ImageRepository {  //CRUD operations for image
             .await
     }
 
+    pub async fn patch(c: &mut AsyncPgConnection, id: i32, patch: ImagePatch) -> QueryResult<Image> {
+        match patch::applied(diesel::update(images::table.find(id)).set(&patch).get_result(c).await)? {
+            Some(row) => Ok(row),
+            None => Self::find(c, id).await,
+        }
+    }
+
     pub async fn delete(c: &mut AsyncPgConnection, id: i32) -> QueryResult<usize> {
         diesel::delete(images::table.find(id)).execute(c).await
     }
@@ -252,8 +833,16 @@ impl TotalThrophiesRepository {  //CRUD operations for total throphies
         total_throphies::table.find(id).get_result(c).await
     }
 
-    pub async fn find_multiple(c: &mut AsyncPgConnection, limit: i64) -> QueryResult<Vec<TotalThrophies>>{
-        total_throphies::table.limit(limit).get_results(c).await
+    pub async fn find_page(c: &mut AsyncPgConnection, filter: TotalThrophiesFilter, page: &Page) -> QueryResult<Vec<TotalThrophies>> {
+        let mut query = total_throphies::table.into_boxed();
+        if let Some(user_id) = filter.user_id {
+            query = query.filter(total_throphies::user_id.eq(user_id));
+        }
+        let query = match page.sort {
+            "total" => page.apply(query, coalesce(total_throphies::total, 0), total_throphies::total_throphies_id),
+            _ => page.apply(query, total_throphies::total_throphies_id, total_throphies::total_throphies_id),
+        };
+        query.get_results(c).await
     }
 
     pub async fn create(c: &mut AsyncPgConnection, new_total_throphies: NewTotalThrophies) -> QueryResult<TotalThrophies> {
@@ -273,6 +862,13 @@ impl TotalThrophiesRepository {  //CRUD operations for total throphies
             .await
     }
 
+    pub async fn patch(c: &mut AsyncPgConnection, id: i32, patch: TotalThrophiesPatch) -> QueryResult<TotalThrophies> {
+        match patch::applied(diesel::update(total_throphies::table.find(id)).set(&patch).get_result(c).await)? {
+            Some(row) => Ok(row),
+            None => Self::find(c, id).await,
+        }
+    }
+
     pub async fn delete(c: &mut AsyncPgConnection, id: i32) -> QueryResult<usize> {
         diesel::delete(total_throphies::table.find(id)).execute(c).await
     }
@@ -290,8 +886,19 @@ impl ThrophiesRepository {  //CRUD operations for throphies
         trophies::table.find(id).get_result(c).await
     }
 
-    pub async fn find_multiple(c: &mut AsyncPgConnection, limit: i64) -> QueryResult<Vec<Trophy>>{
-        trophies::table.limit(limit).get_results(c).await
+    pub async fn find_page(c: &mut AsyncPgConnection, filter: TrophyFilter, page: &Page) -> QueryResult<Vec<Trophy>> {
+        let mut query = trophies::table.into_boxed();
+        if let Some(user_id) = filter.user_id {
+            query = query.filter(trophies::user_id.eq(user_id));
+        }
+        if let Some(since) = filter.since {
+            query = query.filter(trophies::game_timestamp.ge(since));
+        }
+        let query = match page.sort {
+            "points" => page.apply(query, coalesce(trophies::points, 0), trophies::trophy_id),
+            _ => page.apply(query, trophies::trophy_id, trophies::trophy_id),
+        };
+        query.get_results(c).await
     }
 
     pub async fn create(c: &mut AsyncPgConnection, new_trophy: NewTrophy) -> QueryResult<Trophy> {
@@ -312,6 +919,13 @@ impl ThrophiesRepository {  //CRUD operations for throphies
             .await
     }
 
+    pub async fn patch(c: &mut AsyncPgConnection, id: i32, patch: TrophyPatch) -> QueryResult<Trophy> {
+        match patch::applied(diesel::update(trophies::table.find(id)).set(&patch).get_result(c).await)? {
+            Some(row) => Ok(row),
+            None => Self::find(c, id).await,
+        }
+    }
+
     pub async fn delete(c: &mut AsyncPgConnection, id: i32) -> QueryResult<usize> {
         diesel::delete(trophies::table.find(id)).execute(c).await
     }
@@ -329,8 +943,17 @@ impl UserLevelRepository {  //CRUD operations for user level
         user_levels::table.find(id).get_result(c).await
     }
 
-    pub async fn find_multiple(c: &mut AsyncPgConnection, limit: i64) -> QueryResult<Vec<UserLevel>>{
-        user_levels::table.limit(limit).get_results(c).await
+    pub async fn find_page(c: &mut AsyncPgConnection, filter: UserLevelFilter, page: &Page) -> QueryResult<Vec<UserLevel>> {
+        let mut query = user_levels::table.into_boxed();
+        if let Some(user_id) = filter.user_id {
+            query = query.filter(user_levels::user_id.eq(user_id));
+        }
+        let query = match page.sort {
+            "level" => page.apply(query, coalesce(user_levels::level, 0), user_levels::user_level_id),
+            "experience_points" => page.apply(query, coalesce(user_levels::experience_points, 0), user_levels::user_level_id),
+            _ => page.apply(query, user_levels::user_level_id, user_levels::user_level_id),
+        };
+        query.get_results(c).await
     }
 
     pub async fn create(c: &mut AsyncPgConnection, new_user_level: NewUserLevel) -> QueryResult<UserLevel> {
@@ -350,6 +973,13 @@ impl UserLevelRepository {  //CRUD operations for user level
             .await
     }
 
+    pub async fn patch(c: &mut AsyncPgConnection, id: i32, patch: UserLevelPatch) -> QueryResult<UserLevel> {
+        match patch::applied(diesel::update(user_levels::table.find(id)).set(&patch).get_result(c).await)? {
+            Some(row) => Ok(row),
+            None => Self::find(c, id).await,
+        }
+    }
+
     pub async fn delete(c: &mut AsyncPgConnection, id: i32) -> QueryResult<usize> {
         diesel::delete(user_levels::table.find(id)).execute(c).await
     }
@@ -367,8 +997,21 @@ impl ChatRepository {  //CRUD operations for chat
         chats::table.find(id).get_result(c).await
     }
 
-    pub async fn find_multiple(c: &mut AsyncPgConnection, limit: i64) -> QueryResult<Vec<Chat>>{
-        chats::table.limit(limit).get_results(c).await
+    pub async fn find_page(c: &mut AsyncPgConnection, filter: ChatFilter, page: &Page) -> QueryResult<Vec<Chat>> {
+        let mut query = chats::table.into_boxed();
+        query = match filter.participant_ids[..] {
+            [] => query,
+            [user_id] => query.filter(chats::sender_id.eq(user_id).or(chats::receiver_id.eq(user_id))),
+            [a, b, ..] => query.filter(
+                chats::sender_id.eq(a).and(chats::receiver_id.eq(b))
+                    .or(chats::sender_id.eq(b).and(chats::receiver_id.eq(a)))
+            ),
+        };
+        if let Some(since) = filter.since {
+            query = query.filter(chats::timestamp.ge(since));
+        }
+        let query = page.apply(query, chats::chat_id, chats::chat_id);
+        query.get_results(c).await
     }
 
     pub async fn create(c: &mut AsyncPgConnection, new_chat: NewChat) -> QueryResult<Chat> {
@@ -390,10 +1033,208 @@ impl ChatRepository {  //CRUD operations for chat
             .await
     }
 
+    pub async fn patch(c: &mut AsyncPgConnection, id: i32, patch: ChatPatch) -> QueryResult<Chat> {
+        match patch::applied(diesel::update(chats::table.find(id)).set(&patch).get_result(c).await)? {
+            Some(row) => Ok(row),
+            None => Self::find(c, id).await,
+        }
+    }
+
     pub async fn delete(c: &mut AsyncPgConnection, id: i32) -> QueryResult<usize> {
         diesel::delete(chats::table.find(id)).execute(c).await
     }
 
+    // the inbox of a user, one row per user they chatted with, ordered by the last message
+    pub async fn find_conversations(c: &mut AsyncPgConnection, user_id: i32, page: &Page) -> QueryResult<Vec<Conversation>> {
+        let (compare, order) = match page.direction {
+            Direction::Asc => (">", "ASC"),
+            Direction::Desc => ("<", "DESC"),
+        };
+        let query = format!("
+            SELECT chats.*, conversations.user_id, conversations.unread_count
+            FROM (
+                SELECT CASE WHEN sender_id = $1 THEN receiver_id ELSE sender_id END AS user_id,
+                    MAX(chat_id) AS last_chat_id,
+                    COUNT(*) FILTER (WHERE receiver_id = $1 AND is_read IS NOT TRUE) AS unread_count
+                FROM chats
+                WHERE sender_id = $1 OR receiver_id = $1
+                GROUP BY 1
+            ) conversations
+            JOIN chats ON chats.chat_id = conversations.last_chat_id
+            WHERE conversations.user_id IS NOT NULL
+                AND ($2::integer IS NULL OR conversations.last_chat_id {} $2)
+            ORDER BY conversations.last_chat_id {}
+            LIMIT $3", compare, order);
+        diesel::sql_query(query)
+            .bind::<diesel::sql_types::Integer, _>(user_id)
+            .bind::<diesel::sql_types::Nullable<diesel::sql_types::Integer>, _>(page.after().map(|(_, id)| id))
+            .bind::<diesel::sql_types::BigInt, _>(page.limit + 1)
+            .load(c)
+            .await
+    }
+
+    // marks everything other_user_id sent to user_id as read, returns how many chats changed
+    pub async fn mark_read(c: &mut AsyncPgConnection, user_id: i32, other_user_id: i32) -> QueryResult<usize> {
+        diesel::update(chats::table
+            .filter(chats::receiver_id.eq(user_id))
+            .filter(chats::sender_id.eq(other_user_id))
+            .filter(chats::is_read.is_distinct_from(true)))
+            .set(chats::is_read.eq(true))
+            .execute(c)
+            .await
+    }
+
+}
+
+// -----------------  Chat events  -----------------
+// new chats are published as json on the redis channel users/{receiver_id}/chats,
+// every server instance subscribes to the channels of the users streaming from it
+pub struct ChatEventRepository;
+
+impl ChatEventRepository {
+    fn channel(user_id: i32) -> String {
+        format!("users/{}/chats", user_id)
+    }
+
+    pub async fn publish(cache: &mut Connection<CacheConn>, chat: &Chat) -> Result<(), ApiError> {
+        let Some(receiver_id) = chat.receiver_id else {
+            return Ok(());
+        };
+        let payload = serde_json::to_string(chat).map_err(|e| ApiError::Internal(e.into()))?;
+        cache.publish::<String, String, ()>(Self::channel(receiver_id), payload).await
+            .map_err(ApiError::from)
+    }
+
+    // a subscribed connection can't run other commands, so it is taken out of the pool for good
+    pub async fn subscribe(cache: Connection<CacheConn>, user_id: i32) -> Result<redis::aio::PubSub, ApiError> {
+        let mut pubsub = deadpool_redis::Connection::take(cache.into_inner()).into_pubsub();
+        pubsub.subscribe(Self::channel(user_id)).await?;
+        Ok(pubsub)
+    }
+}
+
+
+
+
+// -----------------  Room  -----------------
+// rooms are group chats, room_members says who is in a room and as what (owner or member),
+// room_messages holds what was sent to a room
+pub struct RoomRepository;
+
+impl RoomRepository {
+    pub async fn find(c: &mut AsyncPgConnection, id: i32) -> QueryResult<Room> {
+        rooms::table.find(id).get_result(c).await
+    }
+
+    pub async fn find_page(c: &mut AsyncPgConnection, filter: RoomFilter, page: &Page) -> QueryResult<Vec<Room>> {
+        let mut query = rooms::table.into_boxed();
+        if let Some(member_id) = filter.member_id {
+            query = query.filter(rooms::room_id.eq_any(
+                room_members::table.select(room_members::room_id).filter(room_members::user_id.eq(member_id))
+            ));
+        }
+        let query = page.apply(query, rooms::room_id, rooms::room_id);
+        query.get_results(c).await
+    }
+
+    pub async fn find_owned_by(c: &mut AsyncPgConnection, user_id: i32) -> QueryResult<Vec<i32>> {
+        room_members::table
+            .select(room_members::room_id)
+            .filter(room_members::user_id.eq(user_id))
+            .filter(room_members::role.eq(ROOM_OWNER))
+            .get_results(c)
+            .await
+    }
+
+    // the room and its owner, a room is never without one
+    pub async fn create(c: &mut AsyncPgConnection, new_room: NewRoom, owner_id: i32) -> QueryResult<Room> {
+        c.transaction::<_, diesel::result::Error, _>(|c| async move {
+            let room: Room = diesel::insert_into(rooms::table)
+                .values(&new_room)
+                .get_result(c)
+                .await?;
+            diesel::insert_into(room_members::table)
+                .values(NewRoomMember { room_id: room.room_id, user_id: owner_id, role: ROOM_OWNER.to_owned() })
+                .execute(c)
+                .await?;
+            Ok(room)
+        }.scope_boxed()).await
+    }
+
+    pub async fn patch(c: &mut AsyncPgConnection, id: i32, patch: RoomPatch) -> QueryResult<Room> {
+        match patch::applied(diesel::update(rooms::table.find(id)).set(&patch).get_result(c).await)? {
+            Some(row) => Ok(row),
+            None => Self::find(c, id).await,
+        }
+    }
+
+    // messages, memberships and bans go with the room
+    pub async fn delete(c: &mut AsyncPgConnection, id: i32) -> QueryResult<usize> {
+        c.transaction::<_, diesel::result::Error, _>(|c| async move {
+            diesel::delete(room_messages::table.filter(room_messages::room_id.eq(id))).execute(c).await?;
+            diesel::delete(room_members::table.filter(room_members::room_id.eq(id))).execute(c).await?;
+            diesel::delete(room_bans::table.filter(room_bans::room_id.eq(id))).execute(c).await?;
+            diesel::delete(rooms::table.find(id)).execute(c).await
+        }.scope_boxed()).await
+    }
+
+    pub async fn find_member(c: &mut AsyncPgConnection, room_id: i32, user_id: i32) -> QueryResult<Option<RoomMember>> {
+        room_members::table.find((room_id, user_id)).get_result(c).await.optional()
+    }
+
+    pub async fn find_members(c: &mut AsyncPgConnection, room_id: i32, page: &Page) -> QueryResult<Vec<RoomMember>> {
+        let query = room_members::table.filter(room_members::room_id.eq(room_id)).into_boxed();
+        let query = page.apply(query, room_members::user_id, room_members::user_id);
+        query.get_results(c).await
+    }
+
+    // joining twice is a unique violation on room_members_pkey
+    pub async fn add_member(c: &mut AsyncPgConnection, room_id: i32, user_id: i32) -> QueryResult<RoomMember> {
+        diesel::insert_into(room_members::table)
+            .values(NewRoomMember { room_id, user_id, role: ROOM_MEMBER.to_owned() })
+            .get_result(c)
+            .await
+    }
+
+    pub async fn remove_member(c: &mut AsyncPgConnection, room_id: i32, user_id: i32) -> QueryResult<usize> {
+        diesel::delete(room_members::table.find((room_id, user_id))).execute(c).await
+    }
+
+    // a kicked member is banned in the same transaction, so they can't simply join again
+    pub async fn kick_member(c: &mut AsyncPgConnection, room_id: i32, user_id: i32) -> QueryResult<usize> {
+        c.transaction::<_, diesel::result::Error, _>(|c| async move {
+            diesel::insert_into(room_bans::table)
+                .values((room_bans::room_id.eq(room_id), room_bans::user_id.eq(user_id)))
+                .on_conflict_do_nothing()
+                .execute(c)
+                .await?;
+            Self::remove_member(c, room_id, user_id).await
+        }.scope_boxed()).await
+    }
+
+    pub async fn is_banned(c: &mut AsyncPgConnection, room_id: i32, user_id: i32) -> QueryResult<bool> {
+        diesel::select(diesel::dsl::exists(room_bans::table.find((room_id, user_id)))).get_result(c).await
+    }
+
+    pub async fn lift_ban(c: &mut AsyncPgConnection, room_id: i32, user_id: i32) -> QueryResult<usize> {
+        diesel::delete(room_bans::table.find((room_id, user_id))).execute(c).await
+    }
+
+    pub async fn find_messages(c: &mut AsyncPgConnection, room_id: i32, since: Option<NaiveDateTime>, page: &Page) -> QueryResult<Vec<RoomMessage>> {
+        let mut query = room_messages::table.filter(room_messages::room_id.eq(room_id)).into_boxed();
+        if let Some(since) = since {
+            query = query.filter(room_messages::timestamp.ge(since));
+        }
+        let query = page.apply(query, room_messages::message_id, room_messages::message_id);
+        query.get_results(c).await
+    }
+
+    pub async fn create_message(c: &mut AsyncPgConnection, new_message: NewRoomMessage) -> QueryResult<RoomMessage> {
+        diesel::insert_into(room_messages::table)
+            .values(&new_message)
+            .get_result(c)
+            .await
+    }
 }
 
 
@@ -407,31 +1248,332 @@ impl CurrencyRepository {  //CRUD operations for currency
         currency::table.find(id).get_result(c).await
     }
 
-    pub async fn find_multiple(c: &mut AsyncPgConnection, limit: i64) -> QueryResult<Vec<Currency>>{
-        currency::table.limit(limit).get_results(c).await
+    pub async fn find_page(c: &mut AsyncPgConnection, filter: CurrencyFilter, page: &Page) -> QueryResult<Vec<Currency>> {
+        let mut query = currency::table.into_boxed();
+        if let Some(user_id) = filter.user_id {
+            query = query.filter(currency::user_id.eq(user_id));
+        }
+        if let Some(currency_type) = filter.currency_type {
+            query = query.filter(currency::currency_type.eq(normalize_currency_type(&currency_type)));
+        }
+        let query = match page.sort {
+            "amount" => page.apply(query, coalesce(currency::amount, 0), currency::currency_id),
+            _ => page.apply(query, currency::currency_id, currency::currency_id),
+        };
+        query.get_results(c).await
+    }
+
+    // every change of an amount is booked in currency_transactions within the same transaction,
+    // see book, the amounts always add up to the ledger
+    // a user has one wallet per type, a second one is a 409
+    pub async fn create(c: &mut AsyncPgConnection, mut new_currency: NewCurrency) -> Result<Currency, ApiError> {
+        new_currency.currency_type = new_currency.currency_type.as_deref().map(normalize_currency_type);
+        c.transaction::<_, ApiError, _>(|c| async move {
+            let currency: Currency = diesel::insert_into(currency::table)
+                .values(&new_currency)
+                .get_result(c)
+                .await?;
+            Self::ensure_within_limit(c, None, &currency).await?;
+            Self::book(c, None, Some(&currency), LEDGER_ADJUSTMENT).await?;
+            Ok(currency)
+        }.scope_boxed()).await
+    }
+
+    pub async fn update(c: &mut AsyncPgConnection, id: i32, currency: Currency) -> Result<Currency, ApiError> {
+        c.transaction::<_, ApiError, _>(|c| async move {
+            let before: Currency = currency::table.find(id).for_update().get_result(c).await?;
+            let after = diesel::update(currency::table.find(id))
+                .set((
+                    currency::amount.eq(currency.amount),
+                    currency::currency_type.eq(currency.currency_type.as_deref().map(normalize_currency_type)),
+                    currency::last_updated.eq(currency.last_updated),
+                    currency::user_id.eq(currency.user_id),
+                ))
+                .get_result(c)
+                .await?;
+            Self::ensure_within_limit(c, Some(&before), &after).await?;
+            Self::book(c, Some(&before), Some(&after), LEDGER_ADJUSTMENT).await?;
+            Ok(after)
+        }.scope_boxed()).await
+    }
+
+    pub async fn patch(c: &mut AsyncPgConnection, id: i32, mut patch: CurrencyPatch) -> Result<Currency, ApiError> {
+        patch.currency_type = patch.currency_type.map(|currency_type| currency_type.as_deref().map(normalize_currency_type));
+        c.transaction::<_, ApiError, _>(|c| async move {
+            let before: Currency = currency::table.find(id).for_update().get_result(c).await?;
+            let Some(after) = patch::applied(diesel::update(currency::table.find(id)).set(&patch).get_result(c).await)? else {
+                return Ok(before);
+            };
+            Self::ensure_within_limit(c, Some(&before), &after).await?;
+            Self::book(c, Some(&before), Some(&after), LEDGER_ADJUSTMENT).await?;
+            Ok(after)
+        }.scope_boxed()).await
+    }
+
+    pub async fn delete(c: &mut AsyncPgConnection, id: i32) -> QueryResult<usize> {
+        c.transaction::<_, diesel::result::Error, _>(|c| async move {
+            let Some(before) = currency::table.find(id).for_update().get_result::<Currency>(c).await.optional()? else {
+                return Ok(0);
+            };
+            let deleted = diesel::delete(currency::table.find(id)).execute(c).await?;
+            Self::book(c, Some(&before), None, LEDGER_ADJUSTMENT).await?;
+            Ok(deleted)
+        }.scope_boxed()).await
     }
 
-    pub async fn create(c: &mut AsyncPgConnection, new_currency: NewCurrency) -> QueryResult<Currency> {
+    // books what it takes to get from the row before to the row after, None for a row that doesn't exist,
+    // a row moved to another user or currency type is booked out of the old wallet and into the new one
+    async fn book(c: &mut AsyncPgConnection, before: Option<&Currency>, after: Option<&Currency>, reason: &str) -> QueryResult<()> {
+        let mut bookings: Vec<NewCurrencyTransaction> = Vec::new();
+        for (row, sign) in [(before, -1), (after, 1)] {
+            // rows without an owner or a type are no wallet
+            let Some(Currency { currency_id, user_id: Some(user_id), currency_type: Some(currency_type), amount, .. }) = row else {
+                continue;
+            };
+            let delta = sign * amount.unwrap_or(0);
+            match bookings.iter_mut().find(|booking| booking.user_id == *user_id && booking.currency_type == *currency_type) {
+                Some(booking) => booking.delta += delta,
+                None => bookings.push(NewCurrencyTransaction {
+                    user_id: *user_id,
+                    currency_type: currency_type.clone(),
+                    delta,
+                    reason: reason.to_owned(),
+                    reference: Some(format!("currency/{}", currency_id)),
+                }),
+            }
+        }
+        bookings.retain(|booking| booking.delta != 0);
+        if !bookings.is_empty() {
+            diesel::insert_into(currency_transactions::table).values(&bookings).execute(c).await?;
+        }
+        Ok(())
+    }
+
+    // -- balance changes
+    // a wallet is the currency row of a user and a type, it is locked for the whole transaction
+    // so concurrent changes wait for each other instead of overwriting each other
+    async fn lock_wallet(c: &mut AsyncPgConnection, user_id: i32, currency_type: &str) -> QueryResult<Option<Currency>> {
+        currency::table
+            .filter(currency::user_id.eq(user_id))
+            .filter(currency::currency_type.eq(currency_type))
+            .order(currency::currency_id)
+            .for_update()
+            .first(c)
+            .await
+            .optional()
+    }
+
+    async fn lock_or_create_wallet(c: &mut AsyncPgConnection, user_id: i32, currency_type: &str) -> QueryResult<Currency> {
+        if let Some(wallet) = Self::lock_wallet(c, user_id, currency_type).await? {
+            return Ok(wallet);
+        }
+        // a concurrent request may create the wallet first, this one then waits for its lock
         diesel::insert_into(currency::table)
-            .values(&new_currency)
-            .get_result(c)
+            .values(NewCurrency { user_id: Some(user_id), currency_type: Some(currency_type.to_owned()), amount: Some(0) })
+            .on_conflict((currency::user_id, currency::currency_type))
+            .do_nothing()
+            .execute(c)
+            .await?;
+        Self::lock_wallet(c, user_id, currency_type).await?
+            .ok_or(diesel::result::Error::NotFound)
+    }
+
+    // the type of a balance change, unknown types are the client's fault
+    async fn find_type(c: &mut AsyncPgConnection, currency_type: &str) -> Result<CurrencyType, ApiError> {
+        match currency_types::table.find(currency_type).get_result(c).await {
+            Err(diesel::result::Error::NotFound) =>
+                Err(ApiError::UnprocessableEntity("Unknown currency type".to_owned(), json!({ "currency_type": currency_type }))),
+            result => result.map_err(ApiError::from),
+        }
+    }
+
+    async fn max_balance(c: &mut AsyncPgConnection, currency_type: &str) -> QueryResult<Option<i32>> {
+        currency_types::table.find(currency_type)
+            .select(currency_types::max_balance)
+            .first::<Option<i32>>(c)
             .await
+            .optional()
+            .map(Option::flatten)
+    }
+
+    fn balance_limit_exceeded(max_balance: i32, balance: i32) -> ApiError {
+        ApiError::UnprocessableEntity("Balance limit exceeded".to_owned(), json!({ "max_balance": max_balance, "balance": balance }))
+    }
+
+    // a wallet may not grow past the max_balance of its type, one that already is over it can still shrink
+    async fn ensure_within_limit(c: &mut AsyncPgConnection, before: Option<&Currency>, after: &Currency) -> Result<(), ApiError> {
+        let (Some(currency_type), Some(balance)) = (&after.currency_type, after.amount) else {
+            return Ok(());
+        };
+        let previous = before
+            .filter(|before| before.user_id == after.user_id && before.currency_type == after.currency_type)
+            .and_then(|before| before.amount)
+            .unwrap_or(0);
+        if balance <= previous {
+            return Ok(());
+        }
+        match Self::max_balance(c, currency_type).await? {
+            Some(max_balance) if balance > max_balance => Err(Self::balance_limit_exceeded(max_balance, balance)),
+            _ => Ok(()),
+        }
+    }
+
+    fn insufficient_funds(balance: i32, amount: i32) -> ApiError {
+        ApiError::UnprocessableEntity("Insufficient funds".to_owned(), json!({ "balance": balance, "amount": amount }))
     }
 
-    pub async fn update(c: &mut AsyncPgConnection, id: i32, currency: Currency) -> QueryResult<Currency> {
-        diesel::update(currency::table.find(id))
+    // adds delta to a locked wallet and books it, the balance never goes below 0
+    async fn change_balance(c: &mut AsyncPgConnection, wallet: Currency, booking: NewCurrencyTransaction) -> Result<Currency, ApiError> {
+        let balance = wallet.amount.unwrap_or(0);
+        let new_balance = balance.checked_add(booking.delta)
+            .ok_or_else(|| ApiError::unprocessable("The balance can't hold that much"))?;
+        if new_balance < 0 {
+            return Err(Self::insufficient_funds(balance, -booking.delta));
+        }
+        if booking.delta > 0 {
+            if let Some(max_balance) = Self::max_balance(c, &booking.currency_type).await? {
+                if new_balance > max_balance {
+                    return Err(Self::balance_limit_exceeded(max_balance, new_balance));
+                }
+            }
+        }
+        let wallet = diesel::update(currency::table.find(wallet.currency_id))
             .set((
-                currency::amount.eq(currency.amount),
-                currency::currency_type.eq(currency.currency_type),
-                currency::last_updated.eq(currency.last_updated),
-                currency::user_id.eq(currency.user_id),
+                currency::amount.eq(new_balance),
+                currency::last_updated.eq(diesel::dsl::now),
             ))
             .get_result(c)
+            .await?;
+        diesel::insert_into(currency_transactions::table).values(&booking).execute(c).await?;
+        Ok(wallet)
+    }
+
+    pub async fn credit(c: &mut AsyncPgConnection, user_id: i32, change: BalanceChange) -> Result<Currency, ApiError> {
+        c.transaction::<_, ApiError, _>(|c| async move {
+            let currency_type = Self::find_type(c, &normalize_currency_type(&change.currency_type)).await?.code;
+            let wallet = Self::lock_or_create_wallet(c, user_id, &currency_type).await?;
+            Self::change_balance(c, wallet, NewCurrencyTransaction {
+                user_id,
+                currency_type,
+                delta: change.amount,
+                reason: change.reason.unwrap_or_else(|| LEDGER_CREDIT.to_owned()),
+                reference: change.reference,
+            }).await
+        }.scope_boxed()).await
+    }
+
+    pub async fn debit(c: &mut AsyncPgConnection, user_id: i32, change: BalanceChange) -> Result<Currency, ApiError> {
+        c.transaction::<_, ApiError, _>(|c| async move {
+            let currency_type = normalize_currency_type(&change.currency_type);
+            let wallet = Self::lock_wallet(c, user_id, &currency_type).await?
+                .ok_or_else(|| Self::insufficient_funds(0, change.amount))?;
+            Self::change_balance(c, wallet, NewCurrencyTransaction {
+                user_id,
+                currency_type,
+                delta: -change.amount,
+                reason: change.reason.unwrap_or_else(|| LEDGER_DEBIT.to_owned()),
+                reference: change.reference,
+            }).await
+        }.scope_boxed()).await
+    }
+
+    // moves amount from one user's wallet to another's, both or neither, only tradeable types can be moved
+    pub async fn transfer(c: &mut AsyncPgConnection, from_user_id: i32, transfer: Transfer) -> Result<TransferResult, ApiError> {
+        // both sides would lock the same wallet and the credit would overwrite the debit
+        if from_user_id == transfer.to_user_id {
+            return Err(ApiError::unprocessable("You can't transfer to yourself"));
+        }
+        c.transaction::<_, ApiError, _>(|c| async move {
+            let to_user_id = transfer.to_user_id;
+            let currency_type = Self::find_type(c, &normalize_currency_type(&transfer.currency_type)).await?;
+            if !currency_type.tradeable {
+                return Err(ApiError::UnprocessableEntity("This currency type can't be traded".to_owned(), json!({ "currency_type": currency_type.code })));
+            }
+            let currency_type = currency_type.code;
+            // wallets are always locked in user_id order, two opposite transfers can't deadlock
+            let (from_wallet, to_wallet) = if from_user_id < to_user_id {
+                let from_wallet = Self::lock_wallet(c, from_user_id, &currency_type).await?;
+                (from_wallet, Self::lock_or_create_wallet(c, to_user_id, &currency_type).await?)
+            } else {
+                let to_wallet = Self::lock_or_create_wallet(c, to_user_id, &currency_type).await?;
+                (Self::lock_wallet(c, from_user_id, &currency_type).await?, to_wallet)
+            };
+            let from_wallet = from_wallet.ok_or_else(|| Self::insufficient_funds(0, transfer.amount))?;
+            let from = Self::change_balance(c, from_wallet, NewCurrencyTransaction {
+                user_id: from_user_id,
+                currency_type: currency_type.clone(),
+                delta: -transfer.amount,
+                reason: LEDGER_TRANSFER_OUT.to_owned(),
+                reference: Some(format!("users/{}", to_user_id)),
+            }).await?;
+            let to = Self::change_balance(c, to_wallet, NewCurrencyTransaction {
+                user_id: to_user_id,
+                currency_type,
+                delta: transfer.amount,
+                reason: LEDGER_TRANSFER_IN.to_owned(),
+                reference: Some(format!("users/{}", from_user_id)),
+            }).await?;
+            Ok(TransferResult { from, to })
+        }.scope_boxed()).await
+    }
+
+    pub async fn find_transactions(c: &mut AsyncPgConnection, filter: CurrencyTransactionFilter, page: &Page) -> QueryResult<Vec<CurrencyTransaction>> {
+        let mut query = currency_transactions::table.into_boxed();
+        if let Some(user_id) = filter.user_id {
+            query = query.filter(currency_transactions::user_id.eq(user_id));
+        }
+        if let Some(currency_type) = filter.currency_type {
+            query = query.filter(currency_transactions::currency_type.eq(normalize_currency_type(&currency_type)));
+        }
+        if let Some(since) = filter.since {
+            query = query.filter(currency_transactions::created_at.ge(since));
+        }
+        let query = page.apply(query, currency_transactions::transaction_id, currency_transactions::transaction_id);
+        query.get_results(c).await
+    }
+
+    // every currency type with the user's balance of it, None for the types the user has no wallet of
+    pub async fn find_wallet(c: &mut AsyncPgConnection, user_id: i32) -> QueryResult<Vec<(CurrencyType, Option<i32>)>> {
+        currency_types::table
+            .left_join(currency::table.on(
+                currency::currency_type.eq(currency_types::code.nullable()).and(currency::user_id.eq(user_id))
+            ))
+            .select((currency_types::all_columns, currency::amount.nullable()))
+            .order(currency_types::code)
+            .load(c)
             .await
     }
+}
 
-    pub async fn delete(c: &mut AsyncPgConnection, id: i32) -> QueryResult<usize> {
-        diesel::delete(currency::table.find(id)).execute(c).await
+// -----------------  CurrencyType  -----------------
+pub struct CurrencyTypeRepository;
+
+impl CurrencyTypeRepository {
+    pub async fn find_all(c: &mut AsyncPgConnection) -> QueryResult<Vec<CurrencyType>> {
+        currency_types::table.order(currency_types::code).load(c).await
+    }
+
+    pub async fn find(c: &mut AsyncPgConnection, code: &str) -> QueryResult<CurrencyType> {
+        currency_types::table.find(code).get_result(c).await
+    }
+
+    pub async fn create(c: &mut AsyncPgConnection, new_currency_type: NewCurrencyType) -> QueryResult<CurrencyType> {
+        diesel::insert_into(currency_types::table)
+            .values(new_currency_type)
+            .get_result(c)
+            .await
+    }
+
+    pub async fn patch(c: &mut AsyncPgConnection, code: &str, patch: CurrencyTypePatch) -> QueryResult<CurrencyType> {
+        match patch::applied(diesel::update(currency_types::table.find(code)).set(&patch).get_result(c).await)? {
+            Some(row) => Ok(row),
+            None => Self::find(c, code).await,
+        }
+    }
+
+    // types still held in a wallet can't be deleted, the foreign key refuses
+    pub async fn delete(c: &mut AsyncPgConnection, code: &str) -> QueryResult<usize> {
+        diesel::delete(currency_types::table.find(code)).execute(c).await
     }
 }
 
@@ -444,8 +1586,33 @@ impl FriendshipRepository {  //CRUD operations for friendship
     pub async fn find(c: &mut AsyncPgConnection, id: i32) -> QueryResult<Friendship> {
         friendships::table.find(id).get_result(c).await
     }
-    pub async fn find_multiple(c: &mut AsyncPgConnection, limit: i64) -> QueryResult<Vec<Friendship>>{
-        friendships::table.limit(limit).get_results(c).await
+    pub async fn find_page(c: &mut AsyncPgConnection, filter: FriendshipFilter, page: &Page) -> QueryResult<Vec<Friendship>> {
+        let mut query = friendships::table.into_boxed();
+        query = match filter.participant_ids[..] {
+            [] => query,
+            [user_id] => query.filter(friendships::user_id.eq(user_id).or(friendships::friend_id.eq(user_id))),
+            [a, b, ..] => query.filter(
+                friendships::user_id.eq(a).and(friendships::friend_id.eq(b))
+                    .or(friendships::user_id.eq(b).and(friendships::friend_id.eq(a)))
+            ),
+        };
+        if let Some(sender_id) = filter.sender_id {
+            query = query.filter(friendships::user_id.eq(sender_id));
+        }
+        if let Some(recipient_id) = filter.recipient_id {
+            query = query.filter(friendships::friend_id.eq(recipient_id));
+        }
+        if let Some(status) = filter.status {
+            query = query.filter(friendships::status.eq(status));
+        }
+        if let Some(since) = filter.since {
+            query = query.filter(friendships::friendship_date.ge(since));
+        }
+        if let Some(viewer_id) = filter.viewer_id {
+            query = query.filter(friendships::status.ne(FRIENDSHIP_BLOCKED).or(friendships::user_id.eq(viewer_id)));
+        }
+        let query = page.apply(query, friendships::friendship_id, friendships::friendship_id);
+        query.get_results(c).await
     }
 
     pub async fn create(c: &mut AsyncPgConnection, new_friendship: NewFriendship) -> QueryResult<Friendship> {
@@ -467,7 +1634,178 @@ impl FriendshipRepository {  //CRUD operations for friendship
             .await
     }
 
+    pub async fn patch(c: &mut AsyncPgConnection, id: i32, patch: FriendshipPatch) -> QueryResult<Friendship> {
+        match patch::applied(diesel::update(friendships::table.find(id)).set(&patch).get_result(c).await)? {
+            Some(row) => Ok(row),
+            None => Self::find(c, id).await,
+        }
+    }
+
     pub async fn delete(c: &mut AsyncPgConnection, id: i32) -> QueryResult<usize> {
         diesel::delete(friendships::table.find(id)).execute(c).await
     }
+
+    // -- friend requests, see FRIENDSHIP_PENDING
+    pub async fn find_request(c: &mut AsyncPgConnection, id: i32) -> QueryResult<Friendship> {
+        friendships::table.find(id)
+            .filter(friendships::status.eq(FRIENDSHIP_PENDING))
+            .get_result(c)
+            .await
+    }
+
+    // the two are friends from now on
+    pub async fn accept(c: &mut AsyncPgConnection, id: i32) -> QueryResult<Friendship> {
+        diesel::update(friendships::table.find(id))
+            .set((
+                friendships::status.eq(FRIENDSHIP_ACCEPTED),
+                friendships::friendship_date.eq(diesel::dsl::now),
+            ))
+            .get_result(c)
+            .await
+    }
+
+    // keeps the users with an accepted friendship with user_id, no matter who sent the request
+    fn friends_of(query: users::BoxedQuery<'static, diesel::pg::Pg>, user_id: i32) -> users::BoxedQuery<'static, diesel::pg::Pg> {
+        let sent = friendships::table
+            .select(friendships::friend_id)
+            .filter(friendships::user_id.eq(user_id))
+            .filter(friendships::status.eq(FRIENDSHIP_ACCEPTED));
+        let received = friendships::table
+            .select(friendships::user_id)
+            .filter(friendships::friend_id.eq(user_id))
+            .filter(friendships::status.eq(FRIENDSHIP_ACCEPTED));
+        query.filter(users::user_id.nullable().eq_any(sent).or(users::user_id.nullable().eq_any(received)))
+    }
+
+    pub async fn find_friends(c: &mut AsyncPgConnection, user_id: i32, page: &Page) -> QueryResult<Vec<User>> {
+        let query = Self::friends_of(users::table.into_boxed(), user_id);
+        let query = page.apply(query, users::user_id, users::user_id);
+        query.get_results(c).await
+    }
+
+    // the friends the two users have in common
+    pub async fn find_mutual_friends(c: &mut AsyncPgConnection, user_id: i32, other_id: i32, page: &Page) -> QueryResult<Vec<User>> {
+        let query = Self::friends_of(Self::friends_of(users::table.into_boxed(), user_id), other_id);
+        let query = page.apply(query, users::user_id, users::user_id);
+        query.get_results(c).await
+    }
+
+    // "people you may know", active users the user has no friendship, request or block with, best first
+    // score: 3 per mutual friend, 2 for the same country, 1 for the same language and 1 for a
+    // total_throphies within a quarter of the user's, candidates without any of it are left out
+    // ties go to the closer trophy count
+    pub async fn find_suggestions(c: &mut AsyncPgConnection, user_id: i32, limit: i64) -> QueryResult<Vec<FriendSuggestion>> {
+        diesel::sql_query("
+            WITH me AS (
+                SELECT users.country, users.language,
+                    (SELECT COALESCE(SUM(total), 0) FROM total_throphies WHERE total_throphies.user_id = users.user_id) AS trophies
+                FROM users
+                WHERE users.user_id = $1
+            ),
+            friends AS (
+                SELECT CASE WHEN user_id = $1 THEN friend_id ELSE user_id END AS friend_id
+                FROM friendships
+                WHERE status = 'accepted' AND (user_id = $1 OR friend_id = $1)
+            ),
+            candidates AS (
+                SELECT users.*,
+                    (SELECT COUNT(*) FROM friendships
+                        WHERE status = 'accepted'
+                            AND ((friendships.user_id = users.user_id AND friendships.friend_id IN (SELECT friend_id FROM friends))
+                                OR (friendships.friend_id = users.user_id AND friendships.user_id IN (SELECT friend_id FROM friends)))
+                    ) AS mutual_friends,
+                    (SELECT COALESCE(SUM(total), 0) FROM total_throphies WHERE total_throphies.user_id = users.user_id) AS trophies,
+                    COALESCE(users.country = me.country, FALSE) AS same_country,
+                    COALESCE(users.language = me.language, FALSE) AS same_language
+                FROM users, me
+                WHERE users.user_id <> $1
+                    AND users.is_active IS TRUE
+                    AND users.email_verified_at IS NOT NULL
+                    AND NOT EXISTS (
+                        SELECT 1 FROM friendships
+                        WHERE (friendships.user_id = $1 AND friendships.friend_id = users.user_id)
+                            OR (friendships.user_id = users.user_id AND friendships.friend_id = $1)
+                    )
+            ),
+            scored AS (
+                SELECT candidates.*,
+                    (3 * mutual_friends
+                        + CASE WHEN same_country THEN 2 ELSE 0 END
+                        + CASE WHEN same_language THEN 1 ELSE 0 END
+                        + CASE WHEN ABS(candidates.trophies - me.trophies) <= GREATEST(candidates.trophies, me.trophies) / 4 THEN 1 ELSE 0 END
+                    )::integer AS score,
+                    ABS(candidates.trophies - me.trophies) AS trophy_difference
+                FROM candidates, me
+            )
+            SELECT * FROM scored
+            WHERE score > 0
+            ORDER BY score DESC, trophy_difference ASC, user_id ASC
+            LIMIT $2")
+            .bind::<diesel::sql_types::Integer, _>(user_id)
+            .bind::<diesel::sql_types::BigInt, _>(limit)
+            .load(c)
+            .await
+    }
+
+    pub async fn unfriend(c: &mut AsyncPgConnection, user_id: i32, friend_id: i32) -> QueryResult<usize> {
+        diesel::delete(friendships::table
+            .filter(friendships::status.eq(FRIENDSHIP_ACCEPTED))
+            .filter(
+                friendships::user_id.eq(user_id).and(friendships::friend_id.eq(friend_id))
+                    .or(friendships::user_id.eq(friend_id).and(friendships::friend_id.eq(user_id)))
+            )
+        ).execute(c).await
+    }
+
+    // -- blocks, see FRIENDSHIP_BLOCKED
+    // a block between the two users, whoever blocked whom
+    pub async fn find_block(c: &mut AsyncPgConnection, a: i32, b: i32) -> QueryResult<Option<Friendship>> {
+        friendships::table
+            .filter(friendships::status.eq(FRIENDSHIP_BLOCKED))
+            .filter(
+                friendships::user_id.eq(a).and(friendships::friend_id.eq(b))
+                    .or(friendships::user_id.eq(b).and(friendships::friend_id.eq(a)))
+            )
+            .first(c)
+            .await
+            .optional()
+    }
+
+    // the users blocked by or blocking this one
+    pub async fn find_blocked_ids(c: &mut AsyncPgConnection, user_id: i32) -> QueryResult<Vec<i32>> {
+        let pairs: Vec<(Option<i32>, Option<i32>)> = friendships::table
+            .select((friendships::user_id, friendships::friend_id))
+            .filter(friendships::status.eq(FRIENDSHIP_BLOCKED))
+            .filter(friendships::user_id.eq(user_id).or(friendships::friend_id.eq(user_id)))
+            .get_results(c)
+            .await?;
+        Ok(pairs.into_iter()
+            .flat_map(|(a, b)| a.into_iter().chain(b))
+            .filter(|id| *id != user_id)
+            .collect())
+    }
+
+    // replaces whatever friendship the two had, blocking twice keeps a single row
+    pub async fn block(c: &mut AsyncPgConnection, blocker_id: i32, blocked_id: i32) -> QueryResult<Friendship> {
+        c.transaction::<_, diesel::result::Error, _>(|c| async move {
+            diesel::delete(friendships::table.filter(
+                friendships::user_id.eq(blocker_id).and(friendships::friend_id.eq(blocked_id))
+                    .or(friendships::user_id.eq(blocked_id).and(friendships::friend_id.eq(blocker_id)))
+                    // the other side's block stays
+                    .and(friendships::status.ne(FRIENDSHIP_BLOCKED).or(friendships::user_id.eq(blocker_id)))
+            )).execute(c).await?;
+            diesel::insert_into(friendships::table)
+                .values(NewFriendship { user_id: Some(blocker_id), friend_id: Some(blocked_id), status: FRIENDSHIP_BLOCKED.to_owned() })
+                .get_result(c)
+                .await
+        }.scope_boxed()).await
+    }
+
+    pub async fn unblock(c: &mut AsyncPgConnection, blocker_id: i32, blocked_id: i32) -> QueryResult<usize> {
+        diesel::delete(friendships::table
+            .filter(friendships::user_id.eq(blocker_id))
+            .filter(friendships::friend_id.eq(blocked_id))
+            .filter(friendships::status.eq(FRIENDSHIP_BLOCKED))
+        ).execute(c).await
+    }
 }
\ No newline at end of file
@@ -1,35 +1,159 @@
-use crate::auth::{authorize_user, Credentials};
-use crate::repositories::{UserRepository, SessionRepository};
-use crate::rocket_routes::{server_error, DbConn, CacheConn};
-use rocket::response::status::Custom;
-use rocket::http::Status;
+use crate::auth::{authorize_user, generate_token, verify_dummy_password, Credentials, RefreshRequest, TwoFactorLogin};
+use crate::config::{LoginThrottleConfig, SessionConfig};
+use crate::repositories::{LoginChallengeRepository, LoginThrottleRepository, UserRepository, SessionRepository};
+use crate::rocket_routes::{ClientIp, DbConn, CacheConn, Session};
+use crate::rocket_routes::error::ApiError;
+use crate::rocket_routes::two_factor::verify_second_factor;
+use rocket::response::status::NoContent;
 use rocket::serde::json::{json, Json, Value};
+use rocket::State;
 use rocket_db_pools::Connection;
 
 
 
 #[rocket::post("/login", format="json", data="<credentials>")]
-pub async fn login(mut db: Connection<DbConn>, mut cache: Connection<CacheConn>, credentials: Json<Credentials>) -> Result<Value, Custom<Value>> {
-    // debug username and non username 
-    println!("username: {}", &credentials.username);
-    println!("password: {}", &credentials.password);
-    // query the database for the user
-    let user = UserRepository::find_by_username(&mut db, &credentials.username).await
-        .map_err(|e| server_error(e.into()))?;
-    dbg!(&user);
-
-    let session_id = authorize_user(&user, credentials.into_inner())
-        .map_err(|_| Custom(Status::Unauthorized, json!("Invalid credentials")))?;
-    dbg!(&session_id);
+pub async fn login(mut db: Connection<DbConn>, mut cache: Connection<CacheConn>, config: &State<SessionConfig>, throttle: &State<LoginThrottleConfig>, client_ip: ClientIp, credentials: Json<Credentials>) -> Result<Value, ApiError> {
+    let client_ip = client_ip.0;
+    if let Some(retry_after) = LoginThrottleRepository::locked_for(&mut cache, &credentials.username, client_ip).await? {
+        return Err(ApiError::TooManyRequests("Too many failed logins, try again later".to_owned(), retry_after));
+    }
+
+    // unknown usernames fail the same way as wrong passwords
+    let username = credentials.username.clone();
+    let user = match UserRepository::find_by_username(&mut db, &username).await {
+        Ok(user) => Some(user),
+        Err(diesel::result::Error::NotFound) => None,
+        Err(e) => return Err(e.into()),
+    };
+    let authorized = match user {
+        Some(user) => authorize_user(&user, credentials.into_inner()).ok()
+            .map(|session_id| (user, session_id)),
+        // argon2 runs either way, the response time doesn't tell whether the username exists
+        None => {
+            verify_dummy_password(&credentials.password);
+            None
+        }
+    };
+    let Some((user, session_id)) = authorized else {
+        LoginThrottleRepository::record_failure(&mut cache, throttle, &username, client_ip).await?;
+        return Err(ApiError::Unauthorized("Invalid credentials".to_owned()));
+    };
+    LoginThrottleRepository::reset(&mut cache, &username).await?;
+
+    if user.is_active != Some(true) {
+        return Err(ApiError::Forbidden("Account is deactivated".to_owned()));
+    }
+    if user.email_verified_at.is_none() {
+        return Err(ApiError::Forbidden("Email address not verified".to_owned()));
+    }
+
+    // two factor users first get a short lived challenge, see POST /login/2fa
+    if user.two_factor_auth_enabled == Some(true) {
+        let challenge = generate_token();
+        LoginChallengeRepository::create(&mut cache, &challenge, user.user_id).await?;
+        return Ok(json!({
+            "two_factor_required": true,
+            "challenge": challenge,
+        }));
+    }
+
     // create a session in the cache
-    SessionRepository::create_session(&mut cache, session_id.clone(), user.user_id).await?;
+    let refresh_token = generate_token();
+    SessionRepository::create_session(&mut cache, config, session_id.clone(), refresh_token.clone(), user.user_id).await?;
 
     Ok(json!({
         "token": session_id,
+        "refresh_token": refresh_token,
     }))
 }
 /* Tested with , works
     docker-compose exec app curl 127.0.0.1:8000/login 
     -d '{"username":"admin2", "password":"admin"}' 
     -H 'Content-type: application/json'
-*/
\ No newline at end of file
+*/
+
+//------------- two factor login endpoint -------------
+#[rocket::post("/login/2fa", format="json", data="<two_factor_login>")]
+pub async fn login_two_factor(mut db: Connection<DbConn>, mut cache: Connection<CacheConn>, config: &State<SessionConfig>, two_factor_login: Json<TwoFactorLogin>) -> Result<Value, ApiError> {
+    let invalid = || ApiError::Unauthorized("Invalid credentials".to_owned());
+    let (user_id, ttl) = LoginChallengeRepository::take(&mut cache, &two_factor_login.challenge).await?
+        .ok_or_else(invalid)?;
+    // wrong codes are counted across challenges, the password alone can't buy more guesses
+    if let Some(retry_after) = LoginChallengeRepository::locked_for(&mut cache, user_id).await? {
+        return Err(ApiError::TooManyRequests("Too many wrong codes, try again later".to_owned(), retry_after));
+    }
+    let user = UserRepository::find(&mut db, user_id).await?;
+
+    if !verify_second_factor(&mut db, &mut cache, &user, &two_factor_login.code).await? {
+        LoginChallengeRepository::record_failure(&mut cache, &two_factor_login.challenge, user_id, ttl).await?;
+        return Err(invalid());
+    }
+    LoginChallengeRepository::reset(&mut cache, &two_factor_login.challenge, user_id).await?;
+
+    let session_id = generate_token();
+    let refresh_token = generate_token();
+    SessionRepository::create_session(&mut cache, config, session_id.clone(), refresh_token.clone(), user.user_id).await?;
+
+    Ok(json!({
+        "token": session_id,
+        "refresh_token": refresh_token,
+    }))
+}
+/* Test Endpoint with:
+    docker-compose exec app curl 127.0.0.1:8000/login/2fa
+    -d '{"challenge":"<challenge>", "code":"123456"}'
+    -H 'Content-type: application/json'
+*/
+
+//------------- refresh endpoint -------------
+// trades a refresh token for a new session, the token is single use and
+// the session it was issued with ends
+#[rocket::post("/token/refresh", format="json", data="<request>")]
+pub async fn refresh_token(mut db: Connection<DbConn>, mut cache: Connection<CacheConn>, config: &State<SessionConfig>, request: Json<RefreshRequest>) -> Result<Value, ApiError> {
+    let invalid = || ApiError::Unauthorized("Invalid refresh token".to_owned());
+    let (user_id, old_session_id) = SessionRepository::take_refresh_token(&mut cache, &request.refresh_token).await?
+        .ok_or_else(invalid)?;
+    SessionRepository::delete_session(&mut cache, &old_session_id).await?;
+    // accounts deactivated since the login get no new session
+    let user = UserRepository::find(&mut db, user_id).await?;
+    if !user.can_sign_in() {
+        return Err(invalid());
+    }
+
+    let session_id = generate_token();
+    let refresh_token = generate_token();
+    SessionRepository::create_session(&mut cache, config, session_id.clone(), refresh_token.clone(), user_id).await?;
+
+    Ok(json!({
+        "token": session_id,
+        "refresh_token": refresh_token,
+    }))
+}
+/* Test Endpoint with:
+    docker-compose exec app curl 127.0.0.1:8000/token/refresh
+    -d '{"refresh_token":"<refresh_token>"}'
+    -H 'Content-type: application/json'
+*/
+
+//------------- logout endpoints -------------
+// ends the session the request was made with
+#[rocket::post("/logout")]
+pub async fn logout(mut cache: Connection<CacheConn>, session: Session) -> Result<NoContent, ApiError> {
+    SessionRepository::delete_session(&mut cache, &session.id).await
+        .map(|_| NoContent)
+}
+/* Test Endpoint with:
+    docker-compose exec app curl 127.0.0.1:8000/logout -X POST
+    -H 'Authorization: Bearer <token>'
+*/
+
+// ends every session of the current user, on every device
+#[rocket::post("/logout/all")]
+pub async fn logout_all(mut cache: Connection<CacheConn>, session: Session) -> Result<NoContent, ApiError> {
+    SessionRepository::revoke_all(&mut cache, session.user.user_id).await
+        .map(|_| NoContent)
+}
+/* Test Endpoint with:
+    docker-compose exec app curl 127.0.0.1:8000/logout/all -X POST
+    -H 'Authorization: Bearer <token>'
+*/
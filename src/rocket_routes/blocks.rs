@@ -0,0 +1,50 @@
+use crate::repositories::{FriendshipRepository, UserRepository};
+use crate::rocket_routes::{AuthorizedUser, DbConn};
+use crate::rocket_routes::error::ApiError;
+use rocket::{response::status::Custom, response::status::NoContent};
+use rocket::http::Status;
+use rocket_db_pools::Connection;
+use rocket::serde::json::{json, Value};
+
+/*
+    Blocks
+    - a block is a friendship row from the blocker to the blocked user with status blocked,
+      blocking replaces whatever friendship the two had
+    - a block works both ways: no chats, no friend requests and no profile, answered with a 403
+    - only the blocker sees the block and only the blocker lifts it
+*/
+
+// 403 when one of the two users blocked the other
+pub async fn ensure_not_blocked(db: &mut Connection<DbConn>, user_id: i32, other_id: i32) -> Result<(), ApiError> {
+    match FriendshipRepository::find_block(db, user_id, other_id).await? {
+        Some(_) => Err(ApiError::Forbidden("This user is blocked or has blocked you".to_owned())),
+        None => Ok(()),
+    }
+}
+
+//------------- block endpoint -------------
+#[rocket::post("/users/<id>/block")]
+pub async fn block_user(mut db: Connection<DbConn>, id: i32, caller: AuthorizedUser) -> Result<Custom<Value>, ApiError> {
+    if id == caller.user.user_id {
+        return Err(ApiError::unprocessable("You can't block yourself"));
+    }
+    UserRepository::find(&mut db, id).await?;
+    FriendshipRepository::block(&mut db, caller.user.user_id, id).await
+        .map(|block| Custom(Status::Created, json!(block)))
+        .map_err(ApiError::from)
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/users/2/block -X POST
+*/
+
+//------------- unblock endpoint -------------
+#[rocket::delete("/users/<id>/block")]
+pub async fn unblock_user(mut db: Connection<DbConn>, id: i32, caller: AuthorizedUser) -> Result<NoContent, ApiError> {
+    match FriendshipRepository::unblock(&mut db, caller.user.user_id, id).await? {
+        0 => Err(ApiError::NotFound("You haven't blocked this user".to_owned())),
+        _ => Ok(NoContent),
+    }
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/users/2/block -X DELETE
+*/
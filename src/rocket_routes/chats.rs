@@ -1,9 +1,18 @@
-use crate::models::{Chat, NewChat, User};
-use crate::repositories::ChatRepository;
-use crate::rocket_routes::{DbConn, server_error};
+use crate::models::{Chat, ChatFilter, ChatPatch, NewChat};
+use crate::pagination::{PageParams, Timestamp};
+use crate::repositories::{ChatEventRepository, ChatRepository};
+use crate::rocket_routes::{AuthorizedUser, CacheConn, DbConn};
+use crate::rocket_routes::blocks::ensure_not_blocked;
+use crate::rocket_routes::error::ApiError;
+use crate::patch::MergePatch;
+use crate::rocket_routes::ownership::{ensure_owner, ensure_reader};
 use rocket::{response::status::Custom, serde::json::Json, response::status::NoContent};
 use rocket::http::Status;
+use rocket::futures::StreamExt;
+use rocket::response::stream::{Event, EventStream};
+use rocket::Shutdown;
 use rocket_db_pools::Connection;
+use validator::Validate;
 use rocket::serde::json::{json, Value};
 
 
@@ -15,23 +24,32 @@ use rocket::serde::json::{json, Value};
 
 //------------- get endpoint -------------
 //multi
-#[rocket::get("/chats")]
-pub async fn get_chats(mut db: Connection<DbConn>, _user: User) -> Result<Value, Custom<Value>> {
-    ChatRepository::find_multiple(&mut db, 100).await
-        .map(|chats| json!(chats))
-        .map_err(|e| server_error(e.into())) // hiding error from client for now
+#[rocket::get("/chats?<user_id>&<since>&<page..>")]
+pub async fn get_chats(mut db: Connection<DbConn>, user_id: Option<i32>, since: Option<Timestamp>, page: PageParams, caller: AuthorizedUser) -> Result<Value, ApiError> {
+    let page = page.page::<Chat>()?;
+    // admins see every chat, everyone else only their own, ?user_id= narrows it down to the chats with that user
+    let mut participant_ids = Vec::new();
+    if !caller.is_admin() {
+        participant_ids.push(caller.user.user_id);
+    }
+    if let Some(user_id) = user_id.filter(|user_id| !participant_ids.contains(user_id)) {
+        participant_ids.push(user_id);
+    }
+    let filter = ChatFilter { participant_ids, since: since.map(|since| since.0) };
+    let chats = ChatRepository::find_page(&mut db, filter, &page).await?;
+    Ok(json!(page.finish(chats)))
 }   
 /*
     Test Endpoint with: 
-    docker-compose exec app curl 127.0.0.1:8000/chats
+    docker-compose exec app curl '127.0.0.1:8000/chats?user_id=2&since=2024-01-01&limit=20'
 */
 
 //single chat
 #[rocket::get("/chats/<id>")]
-pub async fn view_chat(mut db: Connection<DbConn>, id: i32, _user: User) -> Result<Value, Custom<Value>> {
-    ChatRepository::find(&mut db, id).await
-        .map(|chat| json!(chat))
-        .map_err(|e| server_error(e.into()))
+pub async fn view_chat(mut db: Connection<DbConn>, id: i32, caller: AuthorizedUser) -> Result<Value, ApiError> {
+    let chat = ChatRepository::find(&mut db, id).await?;
+    ensure_reader(&caller, &chat)?;
+    Ok(json!(chat))
 }
 /*
     Test Endpoint with: 
@@ -39,12 +57,48 @@ pub async fn view_chat(mut db: Connection<DbConn>, id: i32, _user: User) -> Resu
 
 */
 
+//------------- stream endpoint -------------
+// server sent events, one "chat" event with the chat as json for every new chat sent to the caller,
+// fanned out through redis so it doesn't matter which server instance the chat was created on
+#[rocket::get("/chats/stream")]
+pub async fn stream_chats(cache: Connection<CacheConn>, caller: AuthorizedUser, mut shutdown: Shutdown) -> Result<EventStream![], ApiError> {
+    let mut messages = ChatEventRepository::subscribe(cache, caller.user.user_id).await?.into_on_message();
+    Ok(EventStream! {
+        loop {
+            let message = rocket::tokio::select! {
+                message = messages.next() => match message {
+                    Some(message) => message,
+                    None => break,
+                },
+                _ = &mut shutdown => break,
+            };
+            match message.get_payload::<String>() {
+                Ok(chat) => yield Event::data(chat).event("chat"),
+                Err(e) => rocket::warn!("Unreadable chat event: {}", e),
+            }
+        }
+    })
+}
+/*
+    Test Endpoint with:
+    docker-compose exec app curl -N 127.0.0.1:8000/chats/stream
+*/
+
 //------------- create endpoint -------------
 #[rocket::post("/chats", format="json", data="<new_chat>")]
-pub async fn create_chat(mut db: Connection<DbConn>, new_chat: Json<NewChat>, _user: User) -> Result<Custom<Value>, Custom<Value>> {
-    ChatRepository::create(&mut db, new_chat.into_inner()).await
-        .map(|chat| Custom(Status::Created, json!(chat)))
-        .map_err(|e| server_error(e.into()))
+pub async fn create_chat(mut db: Connection<DbConn>, mut cache: Connection<CacheConn>, new_chat: Json<NewChat>, caller: AuthorizedUser) -> Result<Custom<Value>, ApiError> {
+    new_chat.validate()?;
+    // no sending in someone else's name
+    ensure_owner(&caller, &*new_chat)?;
+    if let (Some(sender_id), Some(receiver_id)) = (new_chat.sender_id, new_chat.receiver_id) {
+        ensure_not_blocked(&mut db, sender_id, receiver_id).await?;
+    }
+    let chat = ChatRepository::create(&mut db, new_chat.into_inner()).await?;
+    // the chat is stored either way, receivers that miss the event still find it in GET /chats
+    if let Err(e) = ChatEventRepository::publish(&mut cache, &chat).await {
+        rocket::warn!("Could not publish chat {}: {:?}", chat.chat_id, e);
+    }
+    Ok(Custom(Status::Created, json!(chat)))
 }
 /* Test Endpoint with:  
   docker-compose exec app curl 127.0.0.1:8000/chats -H 'Content-type: application/json' 
@@ -53,22 +107,46 @@ pub async fn create_chat(mut db: Connection<DbConn>, new_chat: Json<NewChat>, _u
 
 //------------- update endpoint -------------
 #[rocket::put("/chats/<id>", format="json", data="<chat>")]
-pub async fn update_chat(mut db: Connection<DbConn>, id: i32, chat: Json<Chat>, _user: User) -> Result<Value, Custom<Value>> {
+pub async fn update_chat(mut db: Connection<DbConn>, id: i32, chat: Json<Chat>, caller: AuthorizedUser) -> Result<Value, ApiError> {
+    chat.validate()?;
+    let existing_chat = ChatRepository::find(&mut db, id).await?;
+    ensure_owner(&caller, &existing_chat)?;
+    ensure_owner(&caller, &*chat)?;
+    if let (Some(sender_id), Some(receiver_id)) = (chat.sender_id, chat.receiver_id) {
+        ensure_not_blocked(&mut db, sender_id, receiver_id).await?;
+    }
     ChatRepository::update(&mut db, id, chat.into_inner()).await
         .map(|chat| json!(chat))
-        .map_err(|e| server_error(e.into()))
+        .map_err(ApiError::from)
 }
 /* Test Endpoint with:  
   docker-compose exec app curl 127.0.0.1:8000/chats/1 -X PUT -H 'Content-type: application/json' 
   -d '{"user_id":1, "friend_id":2, "status":"accepted", "friendship_date":"2021-01-01"}'
 */
 
+//------------- patch endpoint -------------
+#[rocket::patch("/chats/<id>", data="<patch>")]
+pub async fn patch_chat(mut db: Connection<DbConn>, id: i32, patch: MergePatch<ChatPatch>, caller: AuthorizedUser) -> Result<Value, ApiError> {
+    patch.validate()?;
+    let chat = ChatRepository::find(&mut db, id).await?;
+    ensure_owner(&caller, &chat)?;
+    ChatRepository::patch(&mut db, id, patch.into_inner()).await
+        .map(|chat| json!(chat))
+        .map_err(ApiError::from)
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/chats/1 -X PATCH -H 'Content-type: application/merge-patch+json'
+  -d '{"message":"hello again"}'
+*/
+
 //------------- delete endpoint -------------
 #[rocket::delete("/chats/<id>")]
-pub async fn delete_chat(mut db: Connection<DbConn>, id: i32, _user: User) -> Result<NoContent, Custom<Value>> {
+pub async fn delete_chat(mut db: Connection<DbConn>, id: i32, caller: AuthorizedUser) -> Result<NoContent, ApiError> {
+    let chat = ChatRepository::find(&mut db, id).await?;
+    ensure_owner(&caller, &chat)?;
     ChatRepository::delete(&mut db, id).await
         .map(|_|  NoContent)
-        .map_err(|e| server_error(e.into()))
+        .map_err(ApiError::from)
 }
 /* Test Endpoint with: 
   docker-compose exec app curl 127.0.0.1:8000/chats/1 -X DELETE 
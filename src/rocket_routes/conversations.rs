@@ -0,0 +1,51 @@
+use crate::models::{Chat, ChatFilter, Conversation, User};
+use crate::pagination::{PageParams, Timestamp};
+use crate::repositories::{ChatRepository, UserRepository};
+use crate::rocket_routes::DbConn;
+use crate::rocket_routes::error::ApiError;
+use rocket::response::status::NoContent;
+use rocket::serde::json::{json, Value};
+use rocket_db_pools::Connection;
+
+/*
+    The inbox of the logged in user, chats grouped by the other participant
+    a conversation is addressed by the user_id of the other participant
+*/
+
+//------------- get endpoint -------------
+//inbox, newest conversation first
+#[rocket::get("/conversations?<page..>")]
+pub async fn get_conversations(mut db: Connection<DbConn>, page: PageParams, user: User) -> Result<Value, ApiError> {
+    let page = page.page::<Conversation>()?;
+    let conversations = ChatRepository::find_conversations(&mut db, user.user_id, &page).await?;
+    Ok(json!(page.finish(conversations)))
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/conversations -H 'Authorization: Bearer <token>'
+*/
+
+//messages of one conversation, both directions
+#[rocket::get("/conversations/<user_id>/messages?<since>&<page..>")]
+pub async fn get_conversation_messages(mut db: Connection<DbConn>, user_id: i32, since: Option<Timestamp>, page: PageParams, user: User) -> Result<Value, ApiError> {
+    let page = page.page::<Chat>()?;
+    UserRepository::find(&mut db, user_id).await?;
+    let filter = ChatFilter { participant_ids: vec![user.user_id, user_id], since: since.map(|since| since.0) };
+    let chats = ChatRepository::find_page(&mut db, filter, &page).await?;
+    Ok(json!(page.finish(chats)))
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/conversations/2/messages?limit=20 -H 'Authorization: Bearer <token>'
+*/
+
+//------------- read endpoint -------------
+// marks every chat the other participant sent as read
+#[rocket::post("/conversations/<user_id>/read")]
+pub async fn mark_conversation_read(mut db: Connection<DbConn>, user_id: i32, user: User) -> Result<NoContent, ApiError> {
+    UserRepository::find(&mut db, user_id).await?;
+    ChatRepository::mark_read(&mut db, user.user_id, user_id).await
+        .map(|_| NoContent)
+        .map_err(ApiError::from)
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/conversations/2/read -X POST -H 'Authorization: Bearer <token>'
+*/
@@ -1,47 +1,126 @@
-use crate::models::{NewCurrency, Currency, User};
-use crate::repositories::CurrencyRepository;
-use crate::rocket_routes::{DbConn, server_error};
+use crate::models::{BalanceChange, NewCurrency, Currency, CurrencyFilter, CurrencyPatch, CurrencyTransaction, CurrencyTransactionFilter, Transfer, User};
+use crate::pagination::{PageParams, Timestamp};
+use crate::repositories::{CurrencyRepository, UserRepository};
+use crate::rocket_routes::{AdminUser, AuthorizedUser, CacheConn, DbConn};
+use crate::rocket_routes::blocks::ensure_not_blocked;
+use crate::rocket_routes::error::ApiError;
+use crate::rocket_routes::idempotency::Idempotency;
+use crate::patch::MergePatch;
+use crate::rocket_routes::ownership::ensure_owner;
 use rocket::{response::status::Custom, serde::json::Json, response::status::NoContent};
 use rocket::http::Status;
 use rocket_db_pools::Connection;
+use validator::Validate;
 use rocket::serde::json::{json, Value};
 
 /*  TESTED  , 
-
-
+    every change of an amount is booked in the currency_transactions ledger,
+    users read their own history, admins everyone's
+    balances change server side through credit, debit and transfer, they lock the wallets and never overdraw,
+    credit and debit are admin only, users transfer from their own wallet
+    the POST routes take an Idempotency-Key, see idempotency.rs
+    currency_type is one of the currency_types catalog, a user has one wallet per type, see currency_types.rs
 */
 
 //------------- get endpoint -------------
 //multi
-#[rocket::get("/currencies")]
-pub async fn get_currencies(mut db: Connection<DbConn>, _user: User) -> Result<Value, Custom<Value>> {
-    CurrencyRepository::find_multiple(&mut db, 100).await
-        .map(|currencies| json!(currencies))
-        .map_err(|e| server_error(e.into())) // hiding error from client for now
+#[rocket::get("/currencies?<user_id>&<currency_type>&<page..>")]
+pub async fn get_currencies(mut db: Connection<DbConn>, user_id: Option<i32>, currency_type: Option<String>, page: PageParams, _user: User) -> Result<Value, ApiError> {
+    let page = page.page::<Currency>()?;
+    let filter = CurrencyFilter { user_id, currency_type };
+    let currencies = CurrencyRepository::find_page(&mut db, filter, &page).await?;
+    Ok(json!(page.finish(currencies)))
 }   
 /*
     Test Endpoint with: 
-    docker-compose exec app curl 127.0.0.1:8000/currencies
+    docker-compose exec app curl '127.0.0.1:8000/currencies?currency_type=gold&sort=-amount'
 */
 
 //single currency
 #[rocket::get("/currencies/<id>")]
-pub async fn view_currency(mut db: Connection<DbConn>, id: i32, _user: User) -> Result<Value, Custom<Value>> {
+pub async fn view_currency(mut db: Connection<DbConn>, id: i32, _user: User) -> Result<Value, ApiError> {
     CurrencyRepository::find(&mut db, id).await
         .map(|currency| json!(currency))
-        .map_err(|e| server_error(e.into()))
+        .map_err(ApiError::from)
 }
 /*
     Test Endpoint with: 
     docker-compose exec app curl 127.0.0.1:8000/currencies/1
 */
 
+//------------- transaction history -------------
+#[rocket::get("/users/<id>/currency-transactions?<currency_type>&<since>&<page..>")]
+pub async fn get_currency_transactions(mut db: Connection<DbConn>, id: i32, currency_type: Option<String>, since: Option<Timestamp>, page: PageParams, caller: AuthorizedUser) -> Result<Value, ApiError> {
+    let page = page.page::<CurrencyTransaction>()?;
+    let user = UserRepository::find(&mut db, id).await?;
+    ensure_owner(&caller, &user)?;
+    let filter = CurrencyTransactionFilter { user_id: Some(id), currency_type, since: since.map(|since| since.0) };
+    let transactions = CurrencyRepository::find_transactions(&mut db, filter, &page).await?;
+    Ok(json!(page.finish(transactions)))
+}
+/*
+    Test Endpoint with:
+    docker-compose exec app curl '127.0.0.1:8000/users/1/currency-transactions?currency_type=gold'
+*/
+
+//------------- balance endpoints -------------
+#[rocket::post("/users/<id>/currency/credit", format="json", data="<change>")]
+pub async fn credit_currency(mut db: Connection<DbConn>, mut cache: Connection<CacheConn>, id: i32, change: Json<BalanceChange>, idempotency: Idempotency, admin: AdminUser) -> Result<Custom<Value>, ApiError> {
+    change.validate()?;
+    UserRepository::find(&mut db, id).await?;
+    idempotency.run(&mut cache, admin.0.user_id, json!(&*change), async move {
+        CurrencyRepository::credit(&mut db, id, change.into_inner()).await
+            .map(|wallet| Custom(Status::Ok, json!(wallet)))
+    }).await
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/users/1/currency/credit -H 'Content-type: application/json'
+  -d '{"currency_type":"gold","amount":100,"reason":"match_reward","reference":"matches/42"}'
+*/
+
+#[rocket::post("/users/<id>/currency/debit", format="json", data="<change>")]
+pub async fn debit_currency(mut db: Connection<DbConn>, mut cache: Connection<CacheConn>, id: i32, change: Json<BalanceChange>, idempotency: Idempotency, admin: AdminUser) -> Result<Custom<Value>, ApiError> {
+    change.validate()?;
+    UserRepository::find(&mut db, id).await?;
+    idempotency.run(&mut cache, admin.0.user_id, json!(&*change), async move {
+        CurrencyRepository::debit(&mut db, id, change.into_inner()).await
+            .map(|wallet| Custom(Status::Ok, json!(wallet)))
+    }).await
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/users/1/currency/debit -H 'Content-type: application/json'
+  -d '{"currency_type":"gold","amount":100,"reason":"shop_purchase"}'
+*/
+
+#[rocket::post("/users/<id>/currency/transfer", format="json", data="<transfer>")]
+pub async fn transfer_currency(mut db: Connection<DbConn>, mut cache: Connection<CacheConn>, id: i32, transfer: Json<Transfer>, idempotency: Idempotency, caller: AuthorizedUser) -> Result<Custom<Value>, ApiError> {
+    transfer.validate()?;
+    let user = UserRepository::find(&mut db, id).await?;
+    ensure_owner(&caller, &user)?;
+    match UserRepository::find(&mut db, transfer.to_user_id).await {
+        Ok(_) => {},
+        Err(diesel::result::Error::NotFound) => return Err(ApiError::unprocessable("to_user_id does not exist")),
+        Err(e) => return Err(e.into()),
+    }
+    ensure_not_blocked(&mut db, id, transfer.to_user_id).await?;
+    idempotency.run(&mut cache, caller.user.user_id, json!(&*transfer), async move {
+        CurrencyRepository::transfer(&mut db, id, transfer.into_inner()).await
+            .map(|result| Custom(Status::Ok, json!(result)))
+    }).await
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/users/1/currency/transfer -H 'Content-type: application/json'
+  -d '{"to_user_id":2,"currency_type":"gold","amount":50}'
+*/
+
 //------------- create endpoint -------------
 #[rocket::post("/currencies", format="json", data="<new_currency>")]
-pub async fn create_currency(mut db: Connection<DbConn>, new_currency: Json<NewCurrency>, _user: User) -> Result<Custom<Value>, Custom<Value>> {
-    CurrencyRepository::create(&mut db, new_currency.into_inner()).await
-        .map(|currency| Custom(Status::Created, json!(currency)))
-        .map_err(|e| server_error(e.into()))
+pub async fn create_currency(mut db: Connection<DbConn>, mut cache: Connection<CacheConn>, new_currency: Json<NewCurrency>, idempotency: Idempotency, admin: AdminUser) -> Result<Custom<Value>, ApiError> {
+    new_currency.validate()?;
+    idempotency.run(&mut cache, admin.0.user_id, json!(&*new_currency), async move {
+        CurrencyRepository::create(&mut db, new_currency.into_inner()).await
+            .map(|currency| Custom(Status::Created, json!(currency)))
+    }).await
 }
 /* Test Endpoint with:  
   docker-compose exec app curl 127.0.0.1:8000/currencies -H 'Content-type: application/json' 
@@ -50,22 +129,34 @@ pub async fn create_currency(mut db: Connection<DbConn>, new_currency: Json<NewC
 
 //------------- update endpoint -------------
 #[rocket::put("/currencies/<id>", format="json", data="<currency>")]
-pub async fn update_currency(mut db: Connection<DbConn>, id: i32, currency: Json<Currency>, _user: User) -> Result<Value, Custom<Value>> {
+pub async fn update_currency(mut db: Connection<DbConn>, id: i32, currency: Json<Currency>, _admin: AdminUser) -> Result<Value, ApiError> {
+    currency.validate()?;
     CurrencyRepository::update(&mut db, id, currency.into_inner()).await
         .map(|currency| json!(currency))
-        .map_err(|e| server_error(e.into()))
 }
 /* Test Endpoint with:  
   docker-compose exec app curl 127.0.0.1:8000/currencies/1 -X PUT -H 'Content-type: application/json' 
   -d '{"user_id":1,"currency_type":"gold","amount":1000}'
 */
 
+//------------- patch endpoint -------------
+#[rocket::patch("/currencies/<id>", data="<patch>")]
+pub async fn patch_currency(mut db: Connection<DbConn>, id: i32, patch: MergePatch<CurrencyPatch>, _admin: AdminUser) -> Result<Value, ApiError> {
+    patch.validate()?;
+    CurrencyRepository::patch(&mut db, id, patch.into_inner()).await
+        .map(|currency| json!(currency))
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/currencies/1 -X PATCH -H 'Content-type: application/merge-patch+json'
+  -d '{"amount":2000}'
+*/
+
 //------------- delete endpoint -------------
 #[rocket::delete("/currencies/<id>")]
-pub async fn delete_currency(mut db: Connection<DbConn>, id: i32, _user: User) -> Result<NoContent, Custom<Value>> {
+pub async fn delete_currency(mut db: Connection<DbConn>, id: i32, _admin: AdminUser) -> Result<NoContent, ApiError> {
     CurrencyRepository::delete(&mut db, id).await
         .map(|_|  NoContent)
-        .map_err(|e| server_error(e.into()))
+        .map_err(ApiError::from)
 }
 /* Test Endpoint with: 
   docker-compose exec app curl 127.0.0.1:8000/currencies/1 -X DELETE 
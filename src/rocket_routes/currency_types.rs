@@ -0,0 +1,81 @@
+use crate::models::{NewCurrencyType, CurrencyTypePatch, User};
+use crate::repositories::CurrencyTypeRepository;
+use crate::rocket_routes::{AdminUser, DbConn};
+use crate::rocket_routes::error::ApiError;
+use crate::patch::MergePatch;
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use rocket::{response::status::Custom, serde::json::Json, response::status::NoContent};
+use rocket::http::Status;
+use rocket_db_pools::Connection;
+use validator::Validate;
+use rocket::serde::json::{json, Value};
+
+/*
+    Currency types
+    - the catalog of currencies a wallet can hold, looked up by their code ("gold")
+    - codes are lower case, currency_type in requests is trimmed and lower cased before it is looked up
+    - max_balance caps every wallet of the type, null for no cap
+    - only tradeable types can be transferred between users
+    - everyone reads the catalog, admins change it, a type still held in a wallet can't be deleted
+*/
+
+//------------- get endpoint -------------
+// the catalog is small, it is not paginated
+#[rocket::get("/currency-types")]
+pub async fn get_currency_types(mut db: Connection<DbConn>, _user: User) -> Result<Value, ApiError> {
+    let currency_types = CurrencyTypeRepository::find_all(&mut db).await?;
+    Ok(json!({ "items": currency_types }))
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/currency-types
+*/
+
+#[rocket::get("/currency-types/<code>")]
+pub async fn view_currency_type(mut db: Connection<DbConn>, code: &str, _user: User) -> Result<Value, ApiError> {
+    CurrencyTypeRepository::find(&mut db, code).await
+        .map(|currency_type| json!(currency_type))
+        .map_err(ApiError::from)
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/currency-types/gold
+*/
+
+//------------- create endpoint -------------
+#[rocket::post("/currency-types", format="json", data="<new_currency_type>")]
+pub async fn create_currency_type(mut db: Connection<DbConn>, new_currency_type: Json<NewCurrencyType>, _admin: AdminUser) -> Result<Custom<Value>, ApiError> {
+    new_currency_type.validate()?;
+    CurrencyTypeRepository::create(&mut db, new_currency_type.into_inner()).await
+        .map(|currency_type| Custom(Status::Created, json!(currency_type)))
+        .map_err(ApiError::from)
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/currency-types -H 'Content-type: application/json'
+  -d '{"code":"event_tokens","display_name":"Event tokens","max_balance":500,"tradeable":false}'
+*/
+
+//------------- patch endpoint -------------
+#[rocket::patch("/currency-types/<code>", data="<patch>")]
+pub async fn patch_currency_type(mut db: Connection<DbConn>, code: &str, patch: MergePatch<CurrencyTypePatch>, _admin: AdminUser) -> Result<Value, ApiError> {
+    patch.validate()?;
+    CurrencyTypeRepository::patch(&mut db, code, patch.into_inner()).await
+        .map(|currency_type| json!(currency_type))
+        .map_err(ApiError::from)
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/currency-types/gold -X PATCH -H 'Content-type: application/merge-patch+json'
+  -d '{"max_balance":null}'
+*/
+
+//------------- delete endpoint -------------
+#[rocket::delete("/currency-types/<code>")]
+pub async fn delete_currency_type(mut db: Connection<DbConn>, code: &str, _admin: AdminUser) -> Result<NoContent, ApiError> {
+    match CurrencyTypeRepository::delete(&mut db, code).await {
+        Ok(_) => Ok(NoContent),
+        Err(DieselError::DatabaseError(DatabaseErrorKind::ForeignKeyViolation, _)) =>
+            Err(ApiError::Conflict("Currency type is still held in wallets".to_owned(), Value::Null)),
+        Err(e) => Err(e.into()),
+    }
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/currency-types/event_tokens -X DELETE
+*/
@@ -0,0 +1,217 @@
+use std::error::Error;
+
+use diesel::result::{DatabaseErrorKind, Error as DieselError};
+use rocket::fairing::AdHoc;
+use rocket::http::{Header, Status};
+use rocket::response::{self, status::Custom, Responder};
+use rocket::serde::json::{json, Value};
+use rocket::{Catcher, Request};
+use rocket_db_pools::deadpool_redis::redis::RedisError;
+use validator::{ValidationError, ValidationErrors};
+
+/*
+    Every error the api returns has the same body
+    {"code":"not_found", "message":"Resource not found", "details":null, "request_id":"..."}
+    - code is stable and meant for clients, message is for humans
+    - details is null or an object with more about what went wrong, like the violated constraint
+    - request_id is also sent as the X-Request-Id header of every response and is part of the
+      server log line of internal errors, clients can set it themselves with X-Request-Id
+    routes return ApiError, the catchers give the errors rocket raises itself (failed guards,
+    unknown routes, bodies that don't parse) the same shape
+*/
+
+#[derive(Debug)]
+pub enum ApiError {
+    BadRequest(String),
+    Unauthorized(String),
+    Forbidden(String),
+    NotFound(String),
+    // message, details
+    Conflict(String, Value),
+    UnprocessableEntity(String, Value),
+    // message, seconds until the client may try again, sent as Retry-After
+    TooManyRequests(String, u64),
+    // logged, the client only learns that something went wrong
+    Internal(Box<dyn Error + Send + Sync>),
+}
+
+impl ApiError {
+    pub fn unprocessable(message: impl Into<String>) -> Self {
+        ApiError::UnprocessableEntity(message.into(), Value::Null)
+    }
+
+    pub fn status(&self) -> Status {
+        match self {
+            ApiError::BadRequest(_) => Status::BadRequest,
+            ApiError::Unauthorized(_) => Status::Unauthorized,
+            ApiError::Forbidden(_) => Status::Forbidden,
+            ApiError::NotFound(_) => Status::NotFound,
+            ApiError::Conflict(..) => Status::Conflict,
+            ApiError::UnprocessableEntity(..) => Status::UnprocessableEntity,
+            ApiError::TooManyRequests(..) => Status::TooManyRequests,
+            ApiError::Internal(_) => Status::InternalServerError,
+        }
+    }
+}
+
+fn code(status: Status) -> &'static str {
+    match status.code {
+        400 => "bad_request",
+        401 => "unauthorized",
+        403 => "forbidden",
+        404 => "not_found",
+        409 => "conflict",
+        415 => "unsupported_media_type",
+        422 => "unprocessable_entity",
+        429 => "too_many_requests",
+        500..=599 => "internal_error",
+        _ => "error",
+    }
+}
+
+fn body(request: &Request<'_>, status: Status, message: &str, details: Value) -> Custom<Value> {
+    Custom(status, json!({
+        "code": code(status),
+        "message": message,
+        "details": details,
+        "request_id": request_id(request),
+    }))
+}
+
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let status = self.status();
+        let (message, details, retry_after) = match self {
+            ApiError::BadRequest(message)
+            | ApiError::Unauthorized(message)
+            | ApiError::Forbidden(message)
+            | ApiError::NotFound(message) => (message, Value::Null, None),
+            ApiError::Conflict(message, details)
+            | ApiError::UnprocessableEntity(message, details) => (message, details, None),
+            ApiError::TooManyRequests(message, retry_after) => (message, Value::Null, Some(retry_after)),
+            ApiError::Internal(e) => {
+                rocket::error!("[{}] {}", request_id(request), e);
+                ("Internal server error".to_owned(), Value::Null, None)
+            }
+        };
+
+        let mut response = body(request, status, &message, details).respond_to(request)?;
+        if let Some(retry_after) = retry_after {
+            response.set_header(Header::new("Retry-After", retry_after.to_string()));
+        }
+        Ok(response)
+    }
+}
+
+// missing rows are 404, broken constraints 409 (unique) or 422 (foreign key, not null, check)
+impl From<DieselError> for ApiError {
+    fn from(e: DieselError) -> Self {
+        match e {
+            DieselError::NotFound => ApiError::NotFound("Resource not found".to_owned()),
+            DieselError::DatabaseError(kind, info) => {
+                let details = json!({
+                    "constraint": info.constraint_name(),
+                    "column": info.column_name(),
+                });
+                match kind {
+                    DatabaseErrorKind::UniqueViolation =>
+                        ApiError::Conflict("Resource already exists".to_owned(), details),
+                    DatabaseErrorKind::ForeignKeyViolation =>
+                        ApiError::UnprocessableEntity("Referenced resource does not exist".to_owned(), details),
+                    DatabaseErrorKind::NotNullViolation | DatabaseErrorKind::CheckViolation =>
+                        ApiError::UnprocessableEntity("Invalid value".to_owned(), details),
+                    _ => ApiError::Internal(info.message().to_owned().into()),
+                }
+            }
+            e => ApiError::Internal(e.into()),
+        }
+    }
+}
+
+// {"username":[{"code":"length","message":"must be between 1 and 255 characters"}], ...}
+impl From<ValidationErrors> for ApiError {
+    fn from(errors: ValidationErrors) -> Self {
+        let details: serde_json::Map<String, Value> = errors.field_errors().into_iter()
+            .map(|(field, errors)| {
+                let errors: Vec<Value> = errors.iter()
+                    .map(|error| json!({ "code": error.code, "message": describe(error) }))
+                    .collect();
+                (field.to_owned(), json!(errors))
+            })
+            .collect();
+        ApiError::UnprocessableEntity("Validation failed".to_owned(), Value::Object(details))
+    }
+}
+
+// the message of the rule if it has one, otherwise one made from the built in rule and its limits
+fn describe(error: &ValidationError) -> String {
+    if let Some(message) = &error.message {
+        return message.to_string();
+    }
+    let param = |name: &str| error.params.get(name).map(|value| value.to_string());
+    match (error.code.as_ref(), param("min"), param("max")) {
+        ("length", Some(min), Some(max)) => format!("must be between {} and {} characters", min, max),
+        ("length", Some(min), None) => format!("must be at least {} characters", min),
+        ("length", None, Some(max)) => format!("must be at most {} characters", max),
+        ("range", Some(min), Some(max)) => format!("must be between {} and {}", min, max),
+        ("range", Some(min), None) => format!("must be at least {}", min),
+        ("range", None, Some(max)) => format!("must be at most {}", max),
+        ("email", ..) => "must be a valid email address".to_owned(),
+        ("url", ..) => "must be a valid url".to_owned(),
+        (code, ..) => format!("failed the {} rule", code),
+    }
+}
+
+impl From<RedisError> for ApiError {
+    fn from(e: RedisError) -> Self {
+        ApiError::Internal(e.into())
+    }
+}
+
+// a password that doesn't match is the client's fault, anything else about hashing is ours
+impl From<argon2::password_hash::Error> for ApiError {
+    fn from(e: argon2::password_hash::Error) -> Self {
+        match e {
+            argon2::password_hash::Error::Password => ApiError::Unauthorized("Invalid credentials".to_owned()),
+            e => ApiError::Internal(e.to_string().into()),
+        }
+    }
+}
+
+impl From<Box<dyn Error + Send + Sync>> for ApiError {
+    fn from(e: Box<dyn Error + Send + Sync>) -> Self {
+        ApiError::Internal(e)
+    }
+}
+
+//------------- request id -------------
+struct RequestId(String);
+
+// the X-Request-Id the client sent if it looks sane, otherwise a new one
+pub fn request_id<'r>(request: &'r Request<'_>) -> &'r str {
+    &request.local_cache(|| {
+        let id = request.headers().get_one("X-Request-Id")
+            .filter(|id| !id.is_empty() && id.len() <= 64)
+            .filter(|id| id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+            .map(str::to_owned)
+            .unwrap_or_else(|| crate::auth::generate_token()[..32].to_owned());
+        RequestId(id)
+    }).0
+}
+
+// sends the request id back with every response, attach it in the server with .attach(error::fairing())
+pub fn fairing() -> AdHoc {
+    AdHoc::on_response("Request id", |request, response| Box::pin(async move {
+        response.set_header(Header::new("X-Request-Id", request_id(request).to_owned()));
+    }))
+}
+
+//------------- catchers -------------
+#[rocket::catch(default)]
+fn default_catcher(status: Status, request: &Request) -> Custom<Value> {
+    body(request, status, status.reason_lossy(), Value::Null)
+}
+
+pub fn catchers() -> Vec<Catcher> {
+    rocket::catchers![default_catcher]
+}
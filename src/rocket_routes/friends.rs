@@ -0,0 +1,147 @@
+use crate::models::{FriendSuggestionView, Friendship, FriendshipFilter, NewFriendship, PublicUserView, User, FRIENDSHIP_PENDING};
+use crate::pagination::PageParams;
+use crate::repositories::{FriendshipRepository, UserRepository};
+use crate::rocket_routes::{AuthorizedUser, DbConn};
+use crate::rocket_routes::blocks::ensure_not_blocked;
+use crate::rocket_routes::error::ApiError;
+use crate::rocket_routes::ownership::ensure_recipient;
+use rocket::{response::status::Custom, response::status::NoContent};
+use rocket::http::Status;
+use rocket_db_pools::Connection;
+use validator::Validate;
+use rocket::serde::json::{json, Value};
+
+/*
+    Friends and friend requests
+    - a request is a pending friendship from the sender (user_id) to the recipient (friend_id)
+    - only the recipient accepts or declines it, declining deletes it
+    - two users have at most one friendship or request between them, a second one is a 409
+    - either friend ends the friendship with DELETE /friends/<user_id>
+    - suggestions rank everyone the caller has no friendship, request or block with,
+      see FriendshipRepository::find_suggestions for the score
+*/
+
+pub const DEFAULT_SUGGESTIONS: i64 = 10;
+pub const MAX_SUGGESTIONS: i64 = 50;
+
+// ?limit= of the suggestions, they are ranked and not paginated
+#[derive(rocket::FromForm, Validate)]
+pub struct SuggestionParams {
+    #[validate(range(min = 1, max = MAX_SUGGESTIONS))]
+    pub limit: Option<i64>,
+}
+
+//------------- friends -------------
+#[rocket::get("/friends?<page..>")]
+pub async fn get_friends(mut db: Connection<DbConn>, page: PageParams, user: User) -> Result<Value, ApiError> {
+    let page = page.page::<User>()?;
+    let friends = FriendshipRepository::find_friends(&mut db, user.user_id, &page).await?;
+    Ok(json!(page.finish(friends).map(PublicUserView::from)))
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/friends
+*/
+
+#[rocket::delete("/friends/<user_id>")]
+pub async fn unfriend(mut db: Connection<DbConn>, user_id: i32, user: User) -> Result<NoContent, ApiError> {
+    match FriendshipRepository::unfriend(&mut db, user.user_id, user_id).await? {
+        0 => Err(ApiError::NotFound("You are not friends with this user".to_owned())),
+        _ => Ok(NoContent),
+    }
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/friends/2 -X DELETE
+*/
+
+// friends the caller and the user have in common
+#[rocket::get("/users/<id>/mutual-friends?<page..>")]
+pub async fn get_mutual_friends(mut db: Connection<DbConn>, id: i32, page: PageParams, caller: AuthorizedUser) -> Result<Value, ApiError> {
+    let page = page.page::<User>()?;
+    UserRepository::find(&mut db, id).await?;
+    if !caller.is_admin() {
+        ensure_not_blocked(&mut db, caller.user.user_id, id).await?;
+    }
+    let friends = FriendshipRepository::find_mutual_friends(&mut db, caller.user.user_id, id, &page).await?;
+    Ok(json!(page.finish(friends).map(PublicUserView::from)))
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/users/2/mutual-friends
+*/
+
+// "people you may know", best first
+#[rocket::get("/friends/suggestions?<params..>")]
+pub async fn get_friend_suggestions(mut db: Connection<DbConn>, params: SuggestionParams, user: User) -> Result<Value, ApiError> {
+    params.validate()?;
+    let limit = params.limit.unwrap_or(DEFAULT_SUGGESTIONS);
+    let suggestions = FriendshipRepository::find_suggestions(&mut db, user.user_id, limit).await?;
+    let suggestions: Vec<FriendSuggestionView> = suggestions.into_iter().map(FriendSuggestionView::from).collect();
+    Ok(json!({ "items": suggestions }))
+}
+/* Test Endpoint with:
+  docker-compose exec app curl '127.0.0.1:8000/friends/suggestions?limit=5'
+*/
+
+//------------- requests -------------
+#[rocket::post("/users/<id>/friend-request")]
+pub async fn send_friend_request(mut db: Connection<DbConn>, id: i32, user: User) -> Result<Custom<Value>, ApiError> {
+    if id == user.user_id {
+        return Err(ApiError::unprocessable("You can't send a friend request to yourself"));
+    }
+    UserRepository::find(&mut db, id).await?;
+    ensure_not_blocked(&mut db, user.user_id, id).await?;
+    let request = NewFriendship { user_id: Some(user.user_id), friend_id: Some(id), status: FRIENDSHIP_PENDING.to_owned() };
+    FriendshipRepository::create(&mut db, request).await
+        .map(|request| Custom(Status::Created, json!(request)))
+        .map_err(ApiError::from)
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/users/2/friend-request -X POST
+*/
+
+// requests sent to the caller
+#[rocket::get("/friend-requests/incoming?<page..>")]
+pub async fn get_incoming_friend_requests(mut db: Connection<DbConn>, page: PageParams, user: User) -> Result<Value, ApiError> {
+    let page = page.page::<Friendship>()?;
+    let filter = FriendshipFilter { recipient_id: Some(user.user_id), status: Some(FRIENDSHIP_PENDING.to_owned()), ..Default::default() };
+    let requests = FriendshipRepository::find_page(&mut db, filter, &page).await?;
+    Ok(json!(page.finish(requests)))
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/friend-requests/incoming
+*/
+
+// requests the caller sent
+#[rocket::get("/friend-requests/outgoing?<page..>")]
+pub async fn get_outgoing_friend_requests(mut db: Connection<DbConn>, page: PageParams, user: User) -> Result<Value, ApiError> {
+    let page = page.page::<Friendship>()?;
+    let filter = FriendshipFilter { sender_id: Some(user.user_id), status: Some(FRIENDSHIP_PENDING.to_owned()), ..Default::default() };
+    let requests = FriendshipRepository::find_page(&mut db, filter, &page).await?;
+    Ok(json!(page.finish(requests)))
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/friend-requests/outgoing
+*/
+
+#[rocket::post("/friend-requests/<id>/accept")]
+pub async fn accept_friend_request(mut db: Connection<DbConn>, id: i32, user: User) -> Result<Value, ApiError> {
+    let request = FriendshipRepository::find_request(&mut db, id).await?;
+    ensure_recipient(&user, &request)?;
+    FriendshipRepository::accept(&mut db, id).await
+        .map(|friendship| json!(friendship))
+        .map_err(ApiError::from)
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/friend-requests/1/accept -X POST
+*/
+
+#[rocket::post("/friend-requests/<id>/decline")]
+pub async fn decline_friend_request(mut db: Connection<DbConn>, id: i32, user: User) -> Result<NoContent, ApiError> {
+    let request = FriendshipRepository::find_request(&mut db, id).await?;
+    ensure_recipient(&user, &request)?;
+    FriendshipRepository::delete(&mut db, id).await
+        .map(|_| NoContent)
+        .map_err(ApiError::from)
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/friend-requests/1/decline -X POST
+*/
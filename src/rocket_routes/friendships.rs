@@ -1,34 +1,51 @@
-use crate::models::{NewFriendship, Friendship, User};
+use crate::models::{NewFriendship, Friendship, FriendshipFilter, FriendshipPatch};
+use crate::pagination::{PageParams, Timestamp};
 use crate::repositories::FriendshipRepository;
-use crate::rocket_routes::{DbConn, server_error};
+use crate::rocket_routes::{AdminUser, AuthorizedUser, DbConn};
+use crate::rocket_routes::error::ApiError;
+use crate::patch::MergePatch;
+use crate::rocket_routes::ownership::{ensure_owner, ensure_reader};
 use rocket::{response::status::Custom, serde::json::Json, response::status::NoContent};
 use rocket::http::Status;
 use rocket_db_pools::Connection;
+use validator::Validate;
 use rocket::serde::json::{json, Value};
 
 
 /*  TESTED  , 
+    users read and end their friendships here, creating and changing rows is admin only,
+    users go through the friend requests in friends.rs and the blocks in blocks.rs
 */
 
 //------------- get endpoint -------------
 //multi
-#[rocket::get("/friendships")]
-pub async fn get_friendships(mut db: Connection<DbConn>, _user: User) -> Result<Value, Custom<Value>> {
-    FriendshipRepository::find_multiple(&mut db, 100).await
-        .map(|friendships| json!(friendships))
-        .map_err(|e| server_error(e.into())) // hiding error from client for now
+#[rocket::get("/friendships?<user_id>&<status>&<since>&<page..>")]
+pub async fn get_friendships(mut db: Connection<DbConn>, user_id: Option<i32>, status: Option<String>, since: Option<Timestamp>, page: PageParams, caller: AuthorizedUser) -> Result<Value, ApiError> {
+    let page = page.page::<Friendship>()?;
+    // admins see every friendship, everyone else only their own, ?user_id= narrows it down to the one with that user
+    let mut participant_ids = Vec::new();
+    if !caller.is_admin() {
+        participant_ids.push(caller.user.user_id);
+    }
+    if let Some(user_id) = user_id.filter(|user_id| !participant_ids.contains(user_id)) {
+        participant_ids.push(user_id);
+    }
+    let viewer_id = (!caller.is_admin()).then_some(caller.user.user_id);
+    let filter = FriendshipFilter { participant_ids, status, since: since.map(|since| since.0), viewer_id, ..Default::default() };
+    let friendships = FriendshipRepository::find_page(&mut db, filter, &page).await?;
+    Ok(json!(page.finish(friendships)))
 }   
 /*
     Test Endpoint with: 
-    docker-compose exec app curl 127.0.0.1:8000/friendships
+    docker-compose exec app curl '127.0.0.1:8000/friendships?status=pending&sort=id'
 */
 
 //single friendship
 #[rocket::get("/friendships/<id>")]
-pub async fn view_friendship(mut db: Connection<DbConn>, id: i32, _user: User) -> Result<Value, Custom<Value>> {
-    FriendshipRepository::find(&mut db, id).await
-        .map(|friendship| json!(friendship))
-        .map_err(|e| server_error(e.into()))
+pub async fn view_friendship(mut db: Connection<DbConn>, id: i32, caller: AuthorizedUser) -> Result<Value, ApiError> {
+    let friendship = FriendshipRepository::find(&mut db, id).await?;
+    ensure_reader(&caller, &friendship)?;
+    Ok(json!(friendship))
 }
 /*
     Test Endpoint with: 
@@ -37,10 +54,11 @@ pub async fn view_friendship(mut db: Connection<DbConn>, id: i32, _user: User) -
 
 //------------- create endpoint -------------
 #[rocket::post("/friendships", format="json", data="<new_friendship>")]
-pub async fn create_friendship(mut db: Connection<DbConn>, new_friendship: Json<NewFriendship>, _user: User) -> Result<Custom<Value>, Custom<Value>> {
+pub async fn create_friendship(mut db: Connection<DbConn>, new_friendship: Json<NewFriendship>, _admin: AdminUser) -> Result<Custom<Value>, ApiError> {
+    new_friendship.validate()?;
     FriendshipRepository::create(&mut db, new_friendship.into_inner()).await
         .map(|friendship| Custom(Status::Created, json!(friendship)))
-        .map_err(|e| server_error(e.into()))
+        .map_err(ApiError::from)
 }
 /* Test Endpoint with:  
   docker-compose exec app curl 127.0.0.1:8000/friendships -H 'Content-type: application/json' 
@@ -49,22 +67,38 @@ pub async fn create_friendship(mut db: Connection<DbConn>, new_friendship: Json<
 
 //------------- update endpoint -------------
 #[rocket::put("/friendships/<id>", format="json", data="<friendship>")]
-pub async fn update_friendship(mut db: Connection<DbConn>, id: i32, friendship: Json<Friendship>, _user: User) -> Result<Value, Custom<Value>> {
+pub async fn update_friendship(mut db: Connection<DbConn>, id: i32, friendship: Json<Friendship>, _admin: AdminUser) -> Result<Value, ApiError> {
+    friendship.validate()?;
     FriendshipRepository::update(&mut db, id, friendship.into_inner()).await
         .map(|friendship| json!(friendship))
-        .map_err(|e| server_error(e.into()))
+        .map_err(ApiError::from)
 }
 /* Test Endpoint with:  
   docker-compose exec app curl 127.0.0.1:8000/friendships/1 -X PUT -H 'Content-type: application/json' 
   -d '{"user_id":1, "friend_id":2, "status":"blocked"}'
 */
 
+//------------- patch endpoint -------------
+#[rocket::patch("/friendships/<id>", data="<patch>")]
+pub async fn patch_friendship(mut db: Connection<DbConn>, id: i32, patch: MergePatch<FriendshipPatch>, _admin: AdminUser) -> Result<Value, ApiError> {
+    patch.validate()?;
+    FriendshipRepository::patch(&mut db, id, patch.into_inner()).await
+        .map(|friendship| json!(friendship))
+        .map_err(ApiError::from)
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/friendships/1 -X PATCH -H 'Content-type: application/merge-patch+json'
+  -d '{"status":"accepted"}'
+*/
+
 //------------- delete endpoint -------------
 #[rocket::delete("/friendships/<id>")]
-pub async fn delete_friendship(mut db: Connection<DbConn>, id: i32, _user: User) -> Result<NoContent, Custom<Value>> {
+pub async fn delete_friendship(mut db: Connection<DbConn>, id: i32, caller: AuthorizedUser) -> Result<NoContent, ApiError> {
+    let friendship = FriendshipRepository::find(&mut db, id).await?;
+    ensure_owner(&caller, &friendship)?;
     FriendshipRepository::delete(&mut db, id).await
         .map(|_|  NoContent)
-        .map_err(|e| server_error(e.into()))
+        .map_err(ApiError::from)
 }
 /* Test Endpoint with: 
   docker-compose exec app curl 127.0.0.1:8000/friendships/1 -X DELETE 
@@ -0,0 +1,134 @@
+use crate::models::IdempotencyRecord;
+use crate::repositories::{IdempotencyRepository, IDEMPOTENCY_CLAIM_TTL};
+use crate::rocket_routes::CacheConn;
+use crate::rocket_routes::error::ApiError;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use rocket::http::Status;
+use rocket::request::{FromRequest, Outcome};
+use rocket::response::status::Custom;
+use rocket::serde::json::Value;
+use rocket::tokio::time;
+use rocket_db_pools::Connection;
+use sha2::{Digest, Sha256};
+use std::future::Future;
+use std::pin::pin;
+use std::time::Duration;
+
+/*
+    Idempotency keys
+    - the POST routes that hand out currency, trophies or experience take an optional Idempotency-Key
+      header, a client retrying after a timeout sends the same key again
+    - the first successful response is kept for a day and replayed to retries with the same body
+    - the change is applied once per key as long as redis keeps the claim or the response:
+      the claim is renewed while the request runs and the response is stored with a few retries,
+      if redis can't take it the key is free again once the claim expires and a retry applies it again
+    - keys belong to the user sending them, reusing one for another body or route is a 422,
+      a retry arriving while the first request still runs is a 409
+    - failed requests don't keep their key, they can be retried with it
+*/
+
+pub const MAX_IDEMPOTENCY_KEY_LENGTH: usize = 255;
+// pauses between the attempts to store a response, the last attempt follows the last pause
+const STORE_RETRY_DELAYS_MS: [u64; 4] = [250, 500, 1000, 2000];
+
+// the Idempotency-Key the request was sent with, if any, a malformed one is a 400
+pub struct Idempotency {
+    key: Option<String>,
+    route: String,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for Idempotency {
+    type Error = ();
+
+    async fn from_request(request: &'r rocket::Request<'_>) -> Outcome<Self, Self::Error> {
+        let key = request.headers().get_one("Idempotency-Key");
+        let is_valid = |key: &str| !key.is_empty() && key.len() <= MAX_IDEMPOTENCY_KEY_LENGTH && key.chars().all(|c| c.is_ascii_graphic());
+        match key {
+            Some(key) if !is_valid(key) => Outcome::Error((Status::BadRequest, ())),
+            key => Outcome::Success(Idempotency {
+                key: key.map(str::to_owned),
+                route: format!("{} {}", request.method(), request.uri().path()),
+            }),
+        }
+    }
+}
+
+impl Idempotency {
+    // runs the request once per key, a retry gets the stored response instead,
+    // body is the parsed request body, retries have to send the same one
+    pub async fn run<F>(self, cache: &mut Connection<CacheConn>, user_id: i32, body: Value, request: F) -> Result<Custom<Value>, ApiError>
+    where
+        F: Future<Output = Result<Custom<Value>, ApiError>>,
+    {
+        let Some(key) = self.key else {
+            return request.await;
+        };
+        let fingerprint = fingerprint(&self.route, &body);
+        if let Some(record) = IdempotencyRepository::claim(cache, user_id, &key, &fingerprint).await? {
+            return replay(record, &fingerprint);
+        }
+
+        // a request outliving the claim would let a retry apply the change a second time
+        let mut request = pin!(request);
+        let mut renewal = time::interval(Duration::from_secs(IDEMPOTENCY_CLAIM_TTL as u64 / 3));
+        renewal.tick().await;
+        let result = loop {
+            rocket::tokio::select! {
+                result = &mut request => break result,
+                _ = renewal.tick() => {
+                    if let Err(e) = IdempotencyRepository::renew_claim(cache, user_id, &key).await {
+                        rocket::warn!("Could not renew the claim of an idempotency key of user {}: {:?}", user_id, e);
+                    }
+                }
+            }
+        };
+
+        match result {
+            Ok(Custom(status, body)) => {
+                let record = IdempotencyRecord { fingerprint, status: Some(status.code), body: Some(body) };
+                store(cache, user_id, &key, &record).await;
+                // the change is done, a response that couldn't be kept is still the right answer
+                Ok(Custom(status, record.body.unwrap_or_default()))
+            }
+            Err(e) => {
+                IdempotencyRepository::release(cache, user_id, &key).await?;
+                Err(e)
+            }
+        }
+    }
+}
+
+// the claim was renewed less than a third of its ttl ago, the retries end well before it runs out
+async fn store(cache: &mut Connection<CacheConn>, user_id: i32, key: &str, record: &IdempotencyRecord) {
+    for (attempt, delay) in STORE_RETRY_DELAYS_MS.iter().enumerate() {
+        match IdempotencyRepository::store(cache, user_id, key, record).await {
+            Ok(()) => return,
+            Err(e) => rocket::warn!("Could not keep the response for an idempotency key of user {} (attempt {}): {:?}", user_id, attempt + 1, e),
+        }
+        time::sleep(Duration::from_millis(*delay)).await;
+    }
+    if let Err(e) = IdempotencyRepository::store(cache, user_id, key, record).await {
+        rocket::error!("Gave up keeping the response for an idempotency key of user {}, the key is free once its claim expires: {:?}", user_id, e);
+    }
+}
+
+fn fingerprint(route: &str, body: &Value) -> String {
+    let digest = Sha256::new()
+        .chain_update(route.as_bytes())
+        .chain_update(b"\n")
+        .chain_update(body.to_string().as_bytes())
+        .finalize();
+    URL_SAFE_NO_PAD.encode(digest)
+}
+
+fn replay(record: IdempotencyRecord, fingerprint: &str) -> Result<Custom<Value>, ApiError> {
+    if record.fingerprint != fingerprint {
+        return Err(ApiError::unprocessable("Idempotency-Key was already used for a different request"));
+    }
+    match (record.status, record.body) {
+        (Some(status), Some(body)) => Ok(Custom(Status::new(status), body)),
+        _ => Err(ApiError::Conflict("A request with this Idempotency-Key is still in progress".to_owned(), Value::Null)),
+    }
+}
@@ -1,9 +1,13 @@
-use crate::models::{NewImage, Image, User};
+use crate::models::{NewImage, Image, ImageFilter, ImagePatch, User};
+use crate::pagination::{PageParams, Timestamp};
 use crate::repositories::ImageRepository;
-use crate::rocket_routes::{DbConn, server_error};
+use crate::rocket_routes::{AdminUser, DbConn};
+use crate::rocket_routes::error::ApiError;
+use crate::patch::MergePatch;
 use rocket::{response::status::Custom, serde::json::Json, response::status::NoContent};
 use rocket::http::Status;
 use rocket_db_pools::Connection;
+use validator::Validate;
 use rocket::serde::json::{json, Value};
 
 
@@ -30,23 +34,24 @@ pub struct NewImage {
 
 //------------- get endpoint -------------
 //multi
-#[rocket::get("/images")]
-pub async fn get_images(mut db: Connection<DbConn>, _user: User) -> Result<Value, Custom<Value>> {
-    ImageRepository::find_multiple(&mut db, 100).await
-        .map(|images| json!(images))
-        .map_err(|e| server_error(e.into())) // hiding error from client for now
+#[rocket::get("/images?<since>&<page..>")]
+pub async fn get_images(mut db: Connection<DbConn>, since: Option<Timestamp>, page: PageParams, _user: User) -> Result<Value, ApiError> {
+    let page = page.page::<Image>()?;
+    let filter = ImageFilter { since: since.map(|since| since.0) };
+    let images = ImageRepository::find_page(&mut db, filter, &page).await?;
+    Ok(json!(page.finish(images)))
 }   
 /*
     Test Endpoint with: 
-    docker-compose exec app curl 127.0.0.1:8000/images
+    docker-compose exec app curl '127.0.0.1:8000/images?since=2024-01-01'
 */
 
 //single image
 #[rocket::get("/images/<id>")]
-pub async fn view_image(mut db: Connection<DbConn>, id: i32, _user: User) -> Result<Value, Custom<Value>> {
+pub async fn view_image(mut db: Connection<DbConn>, id: i32, _user: User) -> Result<Value, ApiError> {
     ImageRepository::find(&mut db, id).await
         .map(|image| json!(image))
-        .map_err(|e| server_error(e.into()))
+        .map_err(ApiError::from)
 }
 /*
     Test Endpoint with: 
@@ -55,10 +60,11 @@ pub async fn view_image(mut db: Connection<DbConn>, id: i32, _user: User) -> Res
 
 //------------- create endpoint -------------
 #[rocket::post("/images", format="json", data="<new_image>")]
-pub async fn create_image(mut db: Connection<DbConn>, new_image: Json<NewImage>, _user: User) -> Result<Custom<Value>, Custom<Value>> {
+pub async fn create_image(mut db: Connection<DbConn>, new_image: Json<NewImage>, _user: User) -> Result<Custom<Value>, ApiError> {
+    new_image.validate()?;
     ImageRepository::create(&mut db, new_image.into_inner()).await
         .map(|image| Custom(Status::Created, json!(image)))
-        .map_err(|e| server_error(e.into()))
+        .map_err(ApiError::from)
 }
 /* Test Endpoint with:  
   docker-compose exec app curl 127.0.0.1:8000/images -H 'Content-type: application/json' 
@@ -67,22 +73,36 @@ pub async fn create_image(mut db: Connection<DbConn>, new_image: Json<NewImage>,
 
 //------------- update endpoint -------------
 #[rocket::put("/images/<id>", format="json", data="<image>")]
-pub async fn update_image(mut db: Connection<DbConn>, id: i32, image: Json<Image>, _user: User) -> Result<Value, Custom<Value>> {
+pub async fn update_image(mut db: Connection<DbConn>, id: i32, image: Json<Image>, _admin: AdminUser) -> Result<Value, ApiError> {
+    image.validate()?;
     ImageRepository::update(&mut db, id, image.into_inner()).await
         .map(|image| json!(image))
-        .map_err(|e| server_error(e.into()))
+        .map_err(ApiError::from)
 }
 /* Test Endpoint with:  
   docker-compose exec app curl 127.0.0.1:8000/images/1 -X PUT -H 'Content-type: application/json' 
   -d '{"image_url":"https://www.google.com","description":"hello"}'
 */
 
+//------------- patch endpoint -------------
+#[rocket::patch("/images/<id>", data="<patch>")]
+pub async fn patch_image(mut db: Connection<DbConn>, id: i32, patch: MergePatch<ImagePatch>, _admin: AdminUser) -> Result<Value, ApiError> {
+    patch.validate()?;
+    ImageRepository::patch(&mut db, id, patch.into_inner()).await
+        .map(|image| json!(image))
+        .map_err(ApiError::from)
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/images/1 -X PATCH -H 'Content-type: application/merge-patch+json'
+  -d '{"description":null}'
+*/
+
 //------------- delete endpoint -------------
 #[rocket::delete("/images/<id>")]
-pub async fn delete_image(mut db: Connection<DbConn>, id: i32, _user: User) -> Result<NoContent, Custom<Value>> {
+pub async fn delete_image(mut db: Connection<DbConn>, id: i32, _admin: AdminUser) -> Result<NoContent, ApiError> {
     ImageRepository::delete(&mut db, id).await
         .map(|_|  NoContent)
-        .map_err(|e| server_error(e.into()))
+        .map_err(ApiError::from)
 }
 /* Test Endpoint with: 
   docker-compose exec app curl 127.0.0.1:8000/images/1 -X DELETE 
@@ -0,0 +1,55 @@
+use crate::models::{SelfUserView, UpdateProfile, User, WalletBalance};
+use crate::repositories::{CurrencyRepository, ImageRepository, UserRepository};
+use crate::rocket_routes::DbConn;
+use crate::rocket_routes::error::ApiError;
+use crate::patch::MergePatch;
+use rocket::serde::json::{json, Value};
+use rocket_db_pools::Connection;
+use validator::Validate;
+
+/*
+    Self service endpoints for the logged in user, no user_id needed
+*/
+
+//------------- get endpoint -------------
+#[rocket::get("/me")]
+pub async fn get_me(user: User) -> Value {
+    json!(SelfUserView::from(user))
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/me -H 'Authorization: Bearer <token>'
+*/
+
+//------------- update endpoint -------------
+#[rocket::patch("/me", data="<profile>")]
+pub async fn update_me(mut db: Connection<DbConn>, profile: MergePatch<UpdateProfile>, user: User) -> Result<Value, ApiError> {
+    let profile = profile.into_inner();
+    profile.validate()?;
+    if let Some(Some(avatar_id)) = profile.avatar_id {
+        match ImageRepository::find(&mut db, avatar_id).await {
+            Ok(_) => {},
+            Err(diesel::result::Error::NotFound) => return Err(ApiError::unprocessable("avatar_id does not exist")),
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    UserRepository::update_profile(&mut db, user.user_id, profile).await
+        .map(|user| json!(SelfUserView::from(user)))
+        .map_err(ApiError::from)
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/me -X PATCH -H 'Authorization: Bearer <token>'
+  -H 'Content-type: application/merge-patch+json' -d '{"full_name":"New Name","timezone":null}'
+*/
+
+//------------- wallet endpoint -------------
+// every currency type with the caller's balance, 0 for the types they don't hold
+#[rocket::get("/me/wallet")]
+pub async fn get_my_wallet(mut db: Connection<DbConn>, user: User) -> Result<Value, ApiError> {
+    let wallet = CurrencyRepository::find_wallet(&mut db, user.user_id).await?;
+    let wallet: Vec<WalletBalance> = wallet.into_iter().map(WalletBalance::from).collect();
+    Ok(json!({ "items": wallet }))
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/me/wallet -H 'Authorization: Bearer <token>'
+*/
@@ -0,0 +1,95 @@
+use crate::models::{Chat, Friendship, NewChat, RoomMember, User, FRIENDSHIP_BLOCKED, ROOM_OWNER};
+use crate::rocket_routes::AuthorizedUser;
+use crate::rocket_routes::error::ApiError;
+
+/*
+    Ownership policy shared by the route modules
+    - owners may change a row, readers may see it (by default the owners)
+    - admins pass every check, except answering friend requests
+    - everyone else gets a 403
+    currency, throphies and levels are not listed here, changing them is admin only
+*/
+
+pub trait Owned {
+    // ids of the users the row belongs to
+    fn owner_ids(&self) -> Vec<i32>;
+
+    // ids of the users that may read the row
+    fn reader_ids(&self) -> Vec<i32> {
+        self.owner_ids()
+    }
+}
+
+pub fn ensure_owner<T: Owned>(caller: &AuthorizedUser, row: &T) -> Result<(), ApiError> {
+    ensure(caller, row.owner_ids())
+}
+
+pub fn ensure_reader<T: Owned>(caller: &AuthorizedUser, row: &T) -> Result<(), ApiError> {
+    ensure(caller, row.reader_ids())
+}
+
+fn ensure(caller: &AuthorizedUser, allowed_ids: Vec<i32>) -> Result<(), ApiError> {
+    if caller.is_admin() || allowed_ids.contains(&caller.user.user_id) {
+        Ok(())
+    } else {
+        Err(ApiError::Forbidden("Not allowed to access this resource".to_owned()))
+    }
+}
+
+// for routes that only have the id of the user they act on
+pub fn ensure_self(caller: &AuthorizedUser, user_id: i32) -> Result<(), ApiError> {
+    ensure(caller, vec![user_id])
+}
+
+impl Owned for User {
+    fn owner_ids(&self) -> Vec<i32> {
+        vec![self.user_id]
+    }
+}
+
+// only the sender may edit or delete a message, both ends may read it
+impl Owned for Chat {
+    fn owner_ids(&self) -> Vec<i32> {
+        self.sender_id.into_iter().collect()
+    }
+
+    fn reader_ids(&self) -> Vec<i32> {
+        self.sender_id.into_iter().chain(self.receiver_id).collect()
+    }
+}
+
+impl Owned for NewChat {
+    fn owner_ids(&self) -> Vec<i32> {
+        self.sender_id.into_iter().collect()
+    }
+}
+
+// both sides own a friendship, a block only belongs to the blocker
+impl Owned for Friendship {
+    fn owner_ids(&self) -> Vec<i32> {
+        if self.status == FRIENDSHIP_BLOCKED {
+            return self.user_id.into_iter().collect();
+        }
+        self.user_id.into_iter().chain(self.friend_id).collect()
+    }
+}
+
+// friend requests are answered by the user they were sent to and nobody else,
+// not even an admin can agree to a friendship on someone's behalf
+pub fn ensure_recipient(caller: &User, request: &Friendship) -> Result<(), ApiError> {
+    if request.friend_id == Some(caller.user_id) {
+        Ok(())
+    } else {
+        Err(ApiError::Forbidden("Only the recipient can answer a friend request".to_owned()))
+    }
+}
+
+// rooms are checked against the caller's membership, members may read and write messages,
+// the owner manages the room and its members
+pub fn ensure_member(caller: &AuthorizedUser, membership: Option<&RoomMember>) -> Result<(), ApiError> {
+    ensure(caller, membership.map(|member| member.user_id).into_iter().collect())
+}
+
+pub fn ensure_room_owner(caller: &AuthorizedUser, membership: Option<&RoomMember>) -> Result<(), ApiError> {
+    ensure(caller, membership.filter(|member| member.role == ROOM_OWNER).map(|member| member.user_id).into_iter().collect())
+}
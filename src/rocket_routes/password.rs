@@ -0,0 +1,113 @@
+use crate::auth::{generate_token, hash_password, validate_password, verify_password_hash, PasswordChange, PasswordReset, PasswordResetRequest};
+use crate::config::{LoginThrottleConfig, SessionConfig};
+use crate::mail::{Mail, MailSender};
+use crate::models::User;
+use crate::repositories::{LoginThrottleRepository, PasswordResetRepository, SessionRepository, UserRepository};
+use crate::rocket_routes::{CacheConn, DbConn};
+use crate::rocket_routes::error::ApiError;
+use rocket::http::Status;
+use rocket::response::status::{Custom, NoContent};
+use rocket::serde::json::{json, Json, Value};
+use rocket::State;
+use rocket_db_pools::Connection;
+
+/*
+    Passwords only change through these routes, both ways end every session of the user
+    - POST /password/change for logged in users that still know their password
+    - POST /password/forgot mails a single use reset token, POST /password/reset redeems it,
+      only the newest token of a user works and an address gets a few mails an hour at most
+*/
+
+fn check_password(password: &str) -> Result<(), ApiError> {
+    validate_password(password)
+        .map_err(ApiError::unprocessable)
+}
+
+async fn set_password(db: &mut Connection<DbConn>, cache: &mut Connection<CacheConn>, user_id: i32, password: String) -> Result<(), ApiError> {
+    let password_hash = hash_password(password)?;
+    UserRepository::update_password(db, user_id, password_hash).await?;
+    SessionRepository::revoke_all(cache, user_id).await
+}
+
+//------------- change endpoint -------------
+#[rocket::post("/password/change", format="json", data="<password_change>")]
+pub async fn change_password(mut db: Connection<DbConn>, mut cache: Connection<CacheConn>, config: &State<SessionConfig>, throttle: &State<LoginThrottleConfig>, password_change: Json<PasswordChange>, user: User) -> Result<Value, ApiError> {
+    // wrong old passwords count like failed logins of the user, a stolen session
+    // can't guess the password here without running into the /login lockout
+    if let Some(retry_after) = LoginThrottleRepository::locked_for(&mut cache, &user.username, None).await? {
+        return Err(ApiError::TooManyRequests("Too many wrong passwords, try again later".to_owned(), retry_after));
+    }
+    let password_change = password_change.into_inner();
+    if !verify_password_hash(&password_change.old_password, &user.password_hash) {
+        LoginThrottleRepository::record_failure(&mut cache, throttle, &user.username, None).await?;
+        return Err(ApiError::Forbidden("Wrong password".to_owned()));
+    }
+    LoginThrottleRepository::reset(&mut cache, &user.username).await?;
+    check_password(&password_change.new_password)?;
+    set_password(&mut db, &mut cache, user.user_id, password_change.new_password).await?;
+
+    // every other device has to log in again, this one gets a fresh session
+    let session_id = generate_token();
+    let refresh_token = generate_token();
+    SessionRepository::create_session(&mut cache, config, session_id.clone(), refresh_token.clone(), user.user_id).await?;
+
+    Ok(json!({
+        "token": session_id,
+        "refresh_token": refresh_token,
+    }))
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/password/change -H 'Authorization: Bearer <token>'
+  -H 'Content-type: application/json' -d '{"old_password":"testpassword","new_password":"newpassword"}'
+*/
+
+//------------- forgot endpoint -------------
+#[rocket::post("/password/forgot", format="json", data="<reset_request>")]
+pub async fn forgot_password(mut db: Connection<DbConn>, mut cache: Connection<CacheConn>, mail_sender: &State<Box<dyn MailSender>>, reset_request: Json<PasswordResetRequest>) -> Result<Custom<Value>, ApiError> {
+    // counted per address whether it is known or not, so the limit gives nothing away
+    if let Some(retry_after) = PasswordResetRepository::record_request(&mut cache, &reset_request.email).await? {
+        return Err(ApiError::TooManyRequests("Too many reset requests, try again later".to_owned(), retry_after));
+    }
+
+    // same answer whether the email is known or not
+    let accepted = Custom(Status::Accepted, json!("If the email is registered a reset token is on its way"));
+    let user = match UserRepository::find_by_email(&mut db, &reset_request.email).await {
+        Ok(user) => user,
+        Err(diesel::result::Error::NotFound) => return Ok(accepted),
+        Err(e) => return Err(e.into()),
+    };
+
+    let token = generate_token();
+    PasswordResetRepository::create(&mut cache, &token, user.user_id).await?;
+    mail_sender.send(Mail {
+        to: user.email,
+        subject: "Reset your BattleGear password".to_owned(),
+        body: format!(
+            "Hi {},\n\nsomeone asked to reset your password. If it was you, send this token to POST /password/reset within the next hour:\n\ntoken: {}\n\nOtherwise just ignore this mail.",
+            user.username, token
+        ),
+    }).await?;
+
+    Ok(accepted)
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/password/forgot
+  -H 'Content-type: application/json' -d '{"email":"testuser@gmail.com"}'
+*/
+
+//------------- reset endpoint -------------
+#[rocket::post("/password/reset", format="json", data="<password_reset>")]
+pub async fn reset_password(mut db: Connection<DbConn>, mut cache: Connection<CacheConn>, password_reset: Json<PasswordReset>) -> Result<NoContent, ApiError> {
+    let password_reset = password_reset.into_inner();
+    // checked first so a rejected password doesn't use up the token
+    check_password(&password_reset.new_password)?;
+    let user_id = PasswordResetRepository::take(&mut cache, &password_reset.token).await?
+        .ok_or_else(|| ApiError::BadRequest("Invalid or expired reset token".to_owned()))?;
+
+    set_password(&mut db, &mut cache, user_id, password_reset.new_password).await
+        .map(|_| NoContent)
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/password/reset
+  -H 'Content-type: application/json' -d '{"token":"<token>","new_password":"newpassword"}'
+*/
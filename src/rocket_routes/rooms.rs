@@ -0,0 +1,197 @@
+use crate::models::{NewRoom, NewRoomMessage, Room, RoomFilter, RoomMember, RoomMessage, RoomPatch, ROOM_OWNER};
+use crate::pagination::{PageParams, Timestamp};
+use crate::repositories::RoomRepository;
+use crate::rocket_routes::{AuthorizedUser, DbConn};
+use crate::rocket_routes::error::ApiError;
+use crate::patch::MergePatch;
+use crate::rocket_routes::ownership::{ensure_member, ensure_room_owner};
+use rocket::{response::status::Custom, serde::json::Json, response::status::NoContent};
+use rocket::http::Status;
+use rocket_db_pools::Connection;
+use validator::Validate;
+use rocket::serde::json::{json, Value};
+
+/*
+    Group chats
+    - anyone can create a room and becomes its owner, anyone can join a room they are not banned from
+    - members read and send messages and see who else is in the room
+    - the owner renames and deletes the room and kicks members, the owner can't leave
+    - a kicked member is banned from the room until the owner lifts the ban
+*/
+
+// the room and the caller's membership in it, 404 for rooms that don't exist
+async fn find_room(db: &mut Connection<DbConn>, id: i32, caller: &AuthorizedUser) -> Result<(Room, Option<RoomMember>), ApiError> {
+    let room = RoomRepository::find(db, id).await?;
+    let membership = RoomRepository::find_member(db, id, caller.user.user_id).await?;
+    Ok((room, membership))
+}
+
+//------------- get endpoint -------------
+//multi
+#[rocket::get("/rooms?<page..>")]
+pub async fn get_rooms(mut db: Connection<DbConn>, page: PageParams, caller: AuthorizedUser) -> Result<Value, ApiError> {
+    let page = page.page::<Room>()?;
+    // admins see every room, everyone else the rooms they are in
+    let filter = RoomFilter { member_id: (!caller.is_admin()).then_some(caller.user.user_id) };
+    let rooms = RoomRepository::find_page(&mut db, filter, &page).await?;
+    Ok(json!(page.finish(rooms)))
+}
+/*
+    Test Endpoint with:
+    docker-compose exec app curl 127.0.0.1:8000/rooms
+*/
+
+//single room
+#[rocket::get("/rooms/<id>")]
+pub async fn view_room(mut db: Connection<DbConn>, id: i32, caller: AuthorizedUser) -> Result<Value, ApiError> {
+    let (room, membership) = find_room(&mut db, id, &caller).await?;
+    ensure_member(&caller, membership.as_ref())?;
+    Ok(json!(room))
+}
+/*
+    Test Endpoint with:
+    docker-compose exec app curl 127.0.0.1:8000/rooms/1
+*/
+
+//------------- create endpoint -------------
+#[rocket::post("/rooms", format="json", data="<new_room>")]
+pub async fn create_room(mut db: Connection<DbConn>, new_room: Json<NewRoom>, caller: AuthorizedUser) -> Result<Custom<Value>, ApiError> {
+    new_room.validate()?;
+    RoomRepository::create(&mut db, new_room.into_inner(), caller.user.user_id).await
+        .map(|room| Custom(Status::Created, json!(room)))
+        .map_err(ApiError::from)
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/rooms -H 'Content-type: application/json'
+  -d '{"name":"squad"}'
+*/
+
+//------------- patch endpoint -------------
+#[rocket::patch("/rooms/<id>", data="<patch>")]
+pub async fn patch_room(mut db: Connection<DbConn>, id: i32, patch: MergePatch<RoomPatch>, caller: AuthorizedUser) -> Result<Value, ApiError> {
+    patch.validate()?;
+    let (_, membership) = find_room(&mut db, id, &caller).await?;
+    ensure_room_owner(&caller, membership.as_ref())?;
+    RoomRepository::patch(&mut db, id, patch.into_inner()).await
+        .map(|room| json!(room))
+        .map_err(ApiError::from)
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/rooms/1 -X PATCH -H 'Content-type: application/merge-patch+json'
+  -d '{"name":"clan"}'
+*/
+
+//------------- delete endpoint -------------
+#[rocket::delete("/rooms/<id>")]
+pub async fn delete_room(mut db: Connection<DbConn>, id: i32, caller: AuthorizedUser) -> Result<NoContent, ApiError> {
+    let (_, membership) = find_room(&mut db, id, &caller).await?;
+    ensure_room_owner(&caller, membership.as_ref())?;
+    RoomRepository::delete(&mut db, id).await
+        .map(|_| NoContent)
+        .map_err(ApiError::from)
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/rooms/1 -X DELETE
+*/
+
+//------------- membership endpoints -------------
+#[rocket::get("/rooms/<id>/members?<page..>")]
+pub async fn get_room_members(mut db: Connection<DbConn>, id: i32, page: PageParams, caller: AuthorizedUser) -> Result<Value, ApiError> {
+    let page = page.page::<RoomMember>()?;
+    let (_, membership) = find_room(&mut db, id, &caller).await?;
+    ensure_member(&caller, membership.as_ref())?;
+    let members = RoomRepository::find_members(&mut db, id, &page).await?;
+    Ok(json!(page.finish(members)))
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/rooms/1/members
+*/
+
+// joining a room twice is a 409, joining a room one was kicked from a 403
+#[rocket::post("/rooms/<id>/join")]
+pub async fn join_room(mut db: Connection<DbConn>, id: i32, caller: AuthorizedUser) -> Result<Custom<Value>, ApiError> {
+    RoomRepository::find(&mut db, id).await?;
+    if RoomRepository::is_banned(&mut db, id, caller.user.user_id).await? {
+        return Err(ApiError::Forbidden("You were removed from this room".to_owned()));
+    }
+    RoomRepository::add_member(&mut db, id, caller.user.user_id).await
+        .map(|member| Custom(Status::Created, json!(member)))
+        .map_err(ApiError::from)
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/rooms/1/join -X POST
+*/
+
+#[rocket::post("/rooms/<id>/leave")]
+pub async fn leave_room(mut db: Connection<DbConn>, id: i32, caller: AuthorizedUser) -> Result<NoContent, ApiError> {
+    let (_, membership) = find_room(&mut db, id, &caller).await?;
+    match membership {
+        None => Err(ApiError::NotFound("Not a member of this room".to_owned())),
+        Some(member) if member.role == ROOM_OWNER => Err(ApiError::unprocessable("The owner can't leave the room, delete it instead")),
+        Some(_) => RoomRepository::remove_member(&mut db, id, caller.user.user_id).await
+            .map(|_| NoContent)
+            .map_err(ApiError::from),
+    }
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/rooms/1/leave -X POST
+*/
+
+// kicks and bans a member, owner only
+#[rocket::delete("/rooms/<id>/members/<user_id>")]
+pub async fn kick_room_member(mut db: Connection<DbConn>, id: i32, user_id: i32, caller: AuthorizedUser) -> Result<NoContent, ApiError> {
+    let (_, membership) = find_room(&mut db, id, &caller).await?;
+    ensure_room_owner(&caller, membership.as_ref())?;
+    match RoomRepository::find_member(&mut db, id, user_id).await? {
+        None => Err(ApiError::NotFound("Not a member of this room".to_owned())),
+        Some(member) if member.role == ROOM_OWNER => Err(ApiError::unprocessable("The owner can't be removed from the room")),
+        Some(_) => RoomRepository::kick_member(&mut db, id, user_id).await
+            .map(|_| NoContent)
+            .map_err(ApiError::from),
+    }
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/rooms/1/members/2 -X DELETE
+*/
+
+// lets a kicked user join again, owner only
+#[rocket::delete("/rooms/<id>/bans/<user_id>")]
+pub async fn lift_room_ban(mut db: Connection<DbConn>, id: i32, user_id: i32, caller: AuthorizedUser) -> Result<NoContent, ApiError> {
+    let (_, membership) = find_room(&mut db, id, &caller).await?;
+    ensure_room_owner(&caller, membership.as_ref())?;
+    match RoomRepository::lift_ban(&mut db, id, user_id).await? {
+        0 => Err(ApiError::NotFound("Not banned from this room".to_owned())),
+        _ => Ok(NoContent),
+    }
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/rooms/1/bans/2 -X DELETE
+*/
+
+//------------- message endpoints -------------
+#[rocket::get("/rooms/<id>/messages?<since>&<page..>")]
+pub async fn get_room_messages(mut db: Connection<DbConn>, id: i32, since: Option<Timestamp>, page: PageParams, caller: AuthorizedUser) -> Result<Value, ApiError> {
+    let page = page.page::<RoomMessage>()?;
+    let (_, membership) = find_room(&mut db, id, &caller).await?;
+    ensure_member(&caller, membership.as_ref())?;
+    let messages = RoomRepository::find_messages(&mut db, id, since.map(|since| since.0), &page).await?;
+    Ok(json!(page.finish(messages)))
+}
+/* Test Endpoint with:
+  docker-compose exec app curl '127.0.0.1:8000/rooms/1/messages?since=2024-01-01'
+*/
+
+#[rocket::post("/rooms/<id>/messages", format="json", data="<new_message>")]
+pub async fn create_room_message(mut db: Connection<DbConn>, id: i32, new_message: Json<NewRoomMessage>, caller: AuthorizedUser) -> Result<Custom<Value>, ApiError> {
+    new_message.validate()?;
+    let (_, membership) = find_room(&mut db, id, &caller).await?;
+    ensure_member(&caller, membership.as_ref())?;
+    let new_message = NewRoomMessage { room_id: id, sender_id: caller.user.user_id, ..new_message.into_inner() };
+    RoomRepository::create_message(&mut db, new_message).await
+        .map(|message| Custom(Status::Created, json!(message)))
+        .map_err(ApiError::from)
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/rooms/1/messages -H 'Content-type: application/json'
+  -d '{"message":"gg"}'
+*/
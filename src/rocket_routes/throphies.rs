@@ -1,9 +1,14 @@
-use crate::models::{NewTrophy, Trophy, User};
+use crate::models::{NewTrophy, Trophy, TrophyFilter, TrophyPatch, User};
+use crate::pagination::{PageParams, Timestamp};
 use crate::repositories::ThrophiesRepository;
-use crate::rocket_routes::{DbConn, server_error};
+use crate::rocket_routes::{AdminUser, CacheConn, DbConn};
+use crate::rocket_routes::error::ApiError;
+use crate::rocket_routes::idempotency::Idempotency;
+use crate::patch::MergePatch;
 use rocket::{response::status::Custom, serde::json::Json, response::status::NoContent};
 use rocket::http::Status;
 use rocket_db_pools::Connection;
+use validator::Validate;
 use rocket::serde::json::{json, Value};
 
 
@@ -14,23 +19,24 @@ use rocket::serde::json::{json, Value};
 
 //------------- get endpoint -------------
 //multi
-#[rocket::get("/throphies")]
-pub async fn get_throphies(mut db: Connection<DbConn>, _user: User) -> Result<Value, Custom<Value>> {
-    ThrophiesRepository::find_multiple(&mut db, 100).await
-        .map(|throphies: Vec<Trophy>| json!(throphies))
-        .map_err(|e| server_error(e.into())) // hiding error from client for now
+#[rocket::get("/throphies?<user_id>&<since>&<page..>")]
+pub async fn get_throphies(mut db: Connection<DbConn>, user_id: Option<i32>, since: Option<Timestamp>, page: PageParams, _user: User) -> Result<Value, ApiError> {
+    let page = page.page::<Trophy>()?;
+    let filter = TrophyFilter { user_id, since: since.map(|since| since.0) };
+    let throphies = ThrophiesRepository::find_page(&mut db, filter, &page).await?;
+    Ok(json!(page.finish(throphies)))
 }   
 /*
     Test Endpoint with: 
-    docker-compose exec app curl 127.0.0.1:8000/throphies
+    docker-compose exec app curl '127.0.0.1:8000/throphies?user_id=1&sort=-points'
 */
 
 //single throphy
 #[rocket::get("/throphies/<id>")]
-pub async fn view_throphy(mut db: Connection<DbConn>, id: i32, _user: User) -> Result<Value, Custom<Value>> {
+pub async fn view_throphy(mut db: Connection<DbConn>, id: i32, _user: User) -> Result<Value, ApiError> {
     ThrophiesRepository::find(&mut db, id).await
         .map(|throphy| json!(throphy))
-        .map_err(|e| server_error(e.into()))
+        .map_err(ApiError::from)
 }
 /*
     Test Endpoint with: 
@@ -39,10 +45,13 @@ pub async fn view_throphy(mut db: Connection<DbConn>, id: i32, _user: User) -> R
 
 //------------- create endpoint -------------
 #[rocket::post("/throphies", format="json", data="<new_throphy>")]
-pub async fn create_throphy(mut db: Connection<DbConn>, new_throphy: Json<NewTrophy>, _user: User) -> Result<Custom<Value>, Custom<Value>> {
-    ThrophiesRepository::create(&mut db, new_throphy.into_inner()).await
-        .map(|throphy| Custom(Status::Created, json!(throphy)))
-        .map_err(|e| server_error(e.into()))
+pub async fn create_throphy(mut db: Connection<DbConn>, mut cache: Connection<CacheConn>, new_throphy: Json<NewTrophy>, idempotency: Idempotency, admin: AdminUser) -> Result<Custom<Value>, ApiError> {
+    new_throphy.validate()?;
+    idempotency.run(&mut cache, admin.0.user_id, json!(&*new_throphy), async move {
+        ThrophiesRepository::create(&mut db, new_throphy.into_inner()).await
+            .map(|throphy| Custom(Status::Created, json!(throphy)))
+            .map_err(ApiError::from)
+    }).await
 }
 /* Test Endpoint with:  
   docker-compose exec app curl 127.0.0.1:8000/throphies -H 'Content-type: application/json' 
@@ -51,22 +60,36 @@ pub async fn create_throphy(mut db: Connection<DbConn>, new_throphy: Json<NewTro
 
 //------------- update endpoint -------------
 #[rocket::put("/throphies/<id>", format="json", data="<throphy>")]
-pub async fn update_throphy(mut db: Connection<DbConn>, id: i32, throphy: Json<Trophy>, _user: User) -> Result<Value, Custom<Value>> {
+pub async fn update_throphy(mut db: Connection<DbConn>, id: i32, throphy: Json<Trophy>, _admin: AdminUser) -> Result<Value, ApiError> {
+    throphy.validate()?;
     ThrophiesRepository::update(&mut db, id, throphy.into_inner()).await
         .map(|throphy| json!(throphy))
-        .map_err(|e| server_error(e.into()))
+        .map_err(ApiError::from)
 }
 /* Test Endpoint with:  
   docker-compose exec app curl 127.0.0.1:8000/throphies/1 -X PUT -H 'Content-type: application/json' 
   -d '{"user_id":1,"points":1000}'
 */
 
+//------------- patch endpoint -------------
+#[rocket::patch("/throphies/<id>", data="<patch>")]
+pub async fn patch_throphy(mut db: Connection<DbConn>, id: i32, patch: MergePatch<TrophyPatch>, _admin: AdminUser) -> Result<Value, ApiError> {
+    patch.validate()?;
+    ThrophiesRepository::patch(&mut db, id, patch.into_inner()).await
+        .map(|throphy| json!(throphy))
+        .map_err(ApiError::from)
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/throphies/1 -X PATCH -H 'Content-type: application/merge-patch+json'
+  -d '{"points":2000}'
+*/
+
 //------------- delete endpoint -------------
 #[rocket::delete("/throphies/<id>")]
-pub async fn delete_throphy(mut db: Connection<DbConn>, id: i32, _user: User) -> Result<NoContent, Custom<Value>> {
+pub async fn delete_throphy(mut db: Connection<DbConn>, id: i32, _admin: AdminUser) -> Result<NoContent, ApiError> {
     ThrophiesRepository::delete(&mut db, id).await
         .map(|_|  NoContent)
-        .map_err(|e| server_error(e.into()))
+        .map_err(ApiError::from)
 }
 /* Test Endpoint with: 
   docker-compose exec app curl 127.0.0.1:8000/throphies/1 -X DELETE 
@@ -1,9 +1,13 @@
-use crate::models::{NewTotalThrophies, TotalThrophies, User};
+use crate::models::{NewTotalThrophies, TotalThrophies, TotalThrophiesFilter, TotalThrophiesPatch, User};
+use crate::pagination::PageParams;
 use crate::repositories::TotalThrophiesRepository;
-use crate::rocket_routes::{DbConn, server_error};
+use crate::rocket_routes::{AdminUser, DbConn};
+use crate::rocket_routes::error::ApiError;
+use crate::patch::MergePatch;
 use rocket::{response::status::Custom, serde::json::Json, response::status::NoContent};
 use rocket::http::Status;
 use rocket_db_pools::Connection;
+use validator::Validate;
 use rocket::serde::json::{json, Value};
 
 /*  TESTED  , 
@@ -12,23 +16,24 @@ use rocket::serde::json::{json, Value};
 
 //------------- get endpoint -------------
 //multi
-#[rocket::get("/total_throphies")]
-pub async fn get_total_throphies(mut db: Connection<DbConn>, _user: User) -> Result<Value, Custom<Value>> {
-    TotalThrophiesRepository::find_multiple(&mut db, 100).await
-        .map(|total_throphies| json!(total_throphies))
-        .map_err(|e| server_error(e.into())) // hiding error from client for now
+#[rocket::get("/total_throphies?<user_id>&<page..>")]
+pub async fn get_total_throphies(mut db: Connection<DbConn>, user_id: Option<i32>, page: PageParams, _user: User) -> Result<Value, ApiError> {
+    let page = page.page::<TotalThrophies>()?;
+    let filter = TotalThrophiesFilter { user_id };
+    let total_throphies = TotalThrophiesRepository::find_page(&mut db, filter, &page).await?;
+    Ok(json!(page.finish(total_throphies)))
 }   
 /*
     Test Endpoint with: 
-    docker-compose exec app curl 127.0.0.1:8000/total_throphies
+    docker-compose exec app curl '127.0.0.1:8000/total_throphies?sort=-total&limit=10'
 */
 
 //single TotalThrophies
 #[rocket::get("/total_throphies/<id>")]
-pub async fn view_total_throphies(mut db: Connection<DbConn>, id: i32, _user: User) -> Result<Value, Custom<Value>> {
+pub async fn view_total_throphies(mut db: Connection<DbConn>, id: i32, _user: User) -> Result<Value, ApiError> {
     TotalThrophiesRepository::find(&mut db, id).await
         .map(|total_throphies| json!(total_throphies))
-        .map_err(|e| server_error(e.into()))
+        .map_err(ApiError::from)
 }
 /*
     Test Endpoint with: 
@@ -37,10 +42,11 @@ pub async fn view_total_throphies(mut db: Connection<DbConn>, id: i32, _user: Us
 
 //------------- create endpoint -------------
 #[rocket::post("/total_throphies", format="json", data="<new_total_throphies>")]
-pub async fn create_total_throphies(mut db: Connection<DbConn>, new_total_throphies: Json<NewTotalThrophies>, _user: User) -> Result<Custom<Value>, Custom<Value>> {
+pub async fn create_total_throphies(mut db: Connection<DbConn>, new_total_throphies: Json<NewTotalThrophies>, _admin: AdminUser) -> Result<Custom<Value>, ApiError> {
+    new_total_throphies.validate()?;
     TotalThrophiesRepository::create(&mut db, new_total_throphies.into_inner()).await
         .map(|total_throphies| Custom(Status::Created, json!(total_throphies)))
-        .map_err(|e| server_error(e.into()))
+        .map_err(ApiError::from)
 }
 /* Test Endpoint with:  
   docker-compose exec app curl 127.0.0.1:8000/total_throphies -H 'Content-type: application/json' 
@@ -49,22 +55,36 @@ pub async fn create_total_throphies(mut db: Connection<DbConn>, new_total_throph
 
 //------------- update endpoint -------------
 #[rocket::put("/total_throphies/<id>", format="json", data="<total_throphies>")]
-pub async fn update_total_throphies(mut db: Connection<DbConn>, id: i32, total_throphies: Json<TotalThrophies>, _user: User) -> Result<Value, Custom<Value>> {
+pub async fn update_total_throphies(mut db: Connection<DbConn>, id: i32, total_throphies: Json<TotalThrophies>, _admin: AdminUser) -> Result<Value, ApiError> {
+    total_throphies.validate()?;
     TotalThrophiesRepository::update(&mut db, id, total_throphies.into_inner()).await
         .map(|total_throphies| json!(total_throphies))
-        .map_err(|e| server_error(e.into()))
+        .map_err(ApiError::from)
 }
 /* Test Endpoint with:  
   docker-compose exec app curl 127.0.0.1:8000/total_throphies/1 -X PUT -H 'Content-type: application/json' 
   -d '{"user_id":1,"total_throphies":1000}'
 */
 
+//------------- patch endpoint -------------
+#[rocket::patch("/total_throphies/<id>", data="<patch>")]
+pub async fn patch_total_throphies(mut db: Connection<DbConn>, id: i32, patch: MergePatch<TotalThrophiesPatch>, _admin: AdminUser) -> Result<Value, ApiError> {
+    patch.validate()?;
+    TotalThrophiesRepository::patch(&mut db, id, patch.into_inner()).await
+        .map(|total_throphies| json!(total_throphies))
+        .map_err(ApiError::from)
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/total_throphies/1 -X PATCH -H 'Content-type: application/merge-patch+json'
+  -d '{"total":2000}'
+*/
+
 //------------- delete endpoint -------------
 #[rocket::delete("/total_throphies/<id>")]
-pub async fn delete_total_throphies(mut db: Connection<DbConn>, id: i32, _user: User) -> Result<NoContent, Custom<Value>> {
+pub async fn delete_total_throphies(mut db: Connection<DbConn>, id: i32, _admin: AdminUser) -> Result<NoContent, ApiError> {
     TotalThrophiesRepository::delete(&mut db, id).await
         .map(|_|  NoContent)
-        .map_err(|e| server_error(e.into()))
+        .map_err(ApiError::from)
 }
 /* Test Endpoint with: 
   docker-compose exec app curl 127.0.0.1:8000/total_throphies/1 -X DELETE 
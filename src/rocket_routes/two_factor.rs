@@ -0,0 +1,134 @@
+use crate::auth::{generate_recovery_codes, generate_totp_secret, hash_password, totp, verify_password_hash, verify_totp, TwoFactorCode};
+use crate::models::{NewTwoFactorSecret, User};
+use crate::repositories::{LoginChallengeRepository, TwoFactorRepository, UserRepository};
+use crate::rocket_routes::{CacheConn, DbConn};
+use crate::rocket_routes::error::ApiError;
+use rocket::response::status::NoContent;
+use rocket::serde::json::{json, Json, Value};
+use rocket_db_pools::Connection;
+
+/*
+    Two factor auth with TOTP (RFC 6238)
+    1. POST /2fa/enroll returns a secret and an otpauth:// uri for the authenticator app
+    2. POST /2fa/confirm with a code from the app turns two factor auth on
+       and returns the recovery codes, they are only shown this once
+    3. from then on POST /login answers with a challenge that has to be
+       traded for a session at POST /login/2fa
+*/
+
+// checks a TOTP code or, failing that, one of the unused recovery codes
+pub(crate) async fn verify_second_factor(db: &mut Connection<DbConn>, cache: &mut Connection<CacheConn>, user: &User, code: &str) -> Result<bool, ApiError> {
+    let secret = match TwoFactorRepository::find(db, user).await {
+        Ok(secret) if secret.confirmed_at.is_some() => secret,
+        Ok(_) | Err(diesel::result::Error::NotFound) => return Ok(false),
+        Err(e) => return Err(e.into()),
+    };
+
+    if verify_totp(&secret.secret, &user.username, code) {
+        return LoginChallengeRepository::claim_totp_code(cache, user.user_id, code).await;
+    }
+
+    let recovery_codes = TwoFactorRepository::find_recovery_codes(db, user).await?;
+    let code = code.trim().to_lowercase();
+    for recovery_code in recovery_codes.into_iter().filter(|code| code.used_at.is_none()) {
+        if verify_password_hash(&code, &recovery_code.code_hash) {
+            return TwoFactorRepository::use_recovery_code(db, recovery_code.id).await
+                .map_err(ApiError::from);
+        }
+    }
+    Ok(false)
+}
+
+//------------- status endpoint -------------
+#[rocket::get("/2fa")]
+pub async fn status(mut db: Connection<DbConn>, user: User) -> Result<Value, ApiError> {
+    let secret = match TwoFactorRepository::find(&mut db, &user).await {
+        Ok(secret) => Some(secret),
+        Err(diesel::result::Error::NotFound) => None,
+        Err(e) => return Err(e.into()),
+    };
+    let recovery_codes = TwoFactorRepository::find_recovery_codes(&mut db, &user).await?;
+
+    Ok(json!({
+        "enabled": user.two_factor_auth_enabled == Some(true),
+        "enrolled_at": secret.as_ref().map(|secret| secret.created_at),
+        "confirmed_at": secret.and_then(|secret| secret.confirmed_at),
+        "recovery_codes_remaining": recovery_codes.iter().filter(|code| code.used_at.is_none()).count(),
+    }))
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/2fa -H 'Authorization: Bearer <token>'
+*/
+
+//------------- enroll endpoint -------------
+#[rocket::post("/2fa/enroll")]
+pub async fn enroll(mut db: Connection<DbConn>, user: User) -> Result<Value, ApiError> {
+    if user.two_factor_auth_enabled == Some(true) {
+        return Err(ApiError::Conflict("Two factor auth is already enabled".to_owned(), Value::Null));
+    }
+
+    let secret = generate_totp_secret();
+    let provisioning_uri = totp(&secret, &user.username)
+        .map(|totp| totp.get_url())
+        .map_err(|e| ApiError::Internal(e.into()))?;
+    TwoFactorRepository::create(&mut db, NewTwoFactorSecret { user_id: user.user_id, secret: secret.clone() }).await?;
+
+    Ok(json!({
+        "secret": secret,
+        "provisioning_uri": provisioning_uri,
+    }))
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/2fa/enroll -X POST -H 'Authorization: Bearer <token>'
+*/
+
+//------------- confirm endpoint -------------
+#[rocket::post("/2fa/confirm", format="json", data="<code>")]
+pub async fn confirm(mut db: Connection<DbConn>, code: Json<TwoFactorCode>, user: User) -> Result<Value, ApiError> {
+    let secret = match TwoFactorRepository::find(&mut db, &user).await {
+        Ok(secret) if secret.confirmed_at.is_none() => secret,
+        Ok(_) => return Err(ApiError::Conflict("Two factor auth is already enabled".to_owned(), Value::Null)),
+        Err(diesel::result::Error::NotFound) => return Err(ApiError::NotFound("No pending two factor enrollment".to_owned())),
+        Err(e) => return Err(e.into()),
+    };
+    if !verify_totp(&secret.secret, &user.username, &code.code) {
+        return Err(ApiError::unprocessable("Invalid code"));
+    }
+
+    let recovery_codes = generate_recovery_codes();
+    let code_hashes = recovery_codes.iter()
+        .map(|code| hash_password(code.clone()))
+        .collect::<Result<Vec<String>, _>>()?;
+
+    TwoFactorRepository::confirm(&mut db, user.user_id).await?;
+    TwoFactorRepository::replace_recovery_codes(&mut db, user.user_id, code_hashes).await?;
+    UserRepository::set_two_factor_enabled(&mut db, user.user_id, true).await?;
+
+    Ok(json!({
+        "recovery_codes": recovery_codes,
+    }))
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/2fa/confirm -H 'Authorization: Bearer <token>'
+  -H 'Content-type: application/json' -d '{"code":"123456"}'
+*/
+
+//------------- disable endpoint -------------
+#[rocket::post("/2fa/disable", format="json", data="<code>")]
+pub async fn disable(mut db: Connection<DbConn>, mut cache: Connection<CacheConn>, code: Json<TwoFactorCode>, user: User) -> Result<NoContent, ApiError> {
+    if user.two_factor_auth_enabled != Some(true) {
+        return Err(ApiError::Conflict("Two factor auth is not enabled".to_owned(), Value::Null));
+    }
+    if !verify_second_factor(&mut db, &mut cache, &user, &code.code).await? {
+        return Err(ApiError::unprocessable("Invalid code"));
+    }
+
+    TwoFactorRepository::delete(&mut db, user.user_id).await?;
+    UserRepository::set_two_factor_enabled(&mut db, user.user_id, false).await
+        .map(|_| NoContent)
+        .map_err(ApiError::from)
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/2fa/disable -H 'Authorization: Bearer <token>'
+  -H 'Content-type: application/json' -d '{"code":"123456"}'
+*/
@@ -1,9 +1,14 @@
-use crate::models::{NewUserLevel, UserLevel, User};
+use crate::models::{NewUserLevel, UserLevel, UserLevelFilter, UserLevelPatch, User};
+use crate::pagination::PageParams;
 use crate::repositories::UserLevelRepository;
-use crate::rocket_routes::{DbConn, server_error};
+use crate::rocket_routes::{AdminUser, CacheConn, DbConn};
+use crate::rocket_routes::error::ApiError;
+use crate::rocket_routes::idempotency::Idempotency;
+use crate::patch::MergePatch;
 use rocket::{response::status::Custom, serde::json::Json, response::status::NoContent};
 use rocket::http::Status;
 use rocket_db_pools::Connection;
+use validator::Validate;
 use rocket::serde::json::{json, Value};
 
 
@@ -14,23 +19,24 @@ use rocket::serde::json::{json, Value};
 
 //------------- get endpoint -------------
 //multi
-#[rocket::get("/user_levels")]
-pub async fn get_user_levels(mut db: Connection<DbConn>, _user: User) -> Result<Value, Custom<Value>> {
-    UserLevelRepository::find_multiple(&mut db, 100).await
-        .map(|user_levels| json!(user_levels))
-        .map_err(|e| server_error(e.into())) // hiding error from client for now
+#[rocket::get("/user_levels?<user_id>&<page..>")]
+pub async fn get_user_levels(mut db: Connection<DbConn>, user_id: Option<i32>, page: PageParams, _user: User) -> Result<Value, ApiError> {
+    let page = page.page::<UserLevel>()?;
+    let filter = UserLevelFilter { user_id };
+    let user_levels = UserLevelRepository::find_page(&mut db, filter, &page).await?;
+    Ok(json!(page.finish(user_levels)))
 }   
 /*
     Test Endpoint with: 
-    docker-compose exec app curl 127.0.0.1:8000/user_levels
+    docker-compose exec app curl '127.0.0.1:8000/user_levels?sort=-experience_points'
 */
 
 //single user_level
 #[rocket::get("/user_levels/<id>")]
-pub async fn view_user_levels(mut db: Connection<DbConn>, id: i32, _user: User) -> Result<Value, Custom<Value>> {
+pub async fn view_user_levels(mut db: Connection<DbConn>, id: i32, _user: User) -> Result<Value, ApiError> {
     UserLevelRepository::find(&mut db, id).await
         .map(|user_level| json!(user_level))
-        .map_err(|e| server_error(e.into()))
+        .map_err(ApiError::from)
 }
 /*
     Test Endpoint with: 
@@ -39,10 +45,13 @@ pub async fn view_user_levels(mut db: Connection<DbConn>, id: i32, _user: User)
 
 //------------- create endpoint -------------
 #[rocket::post("/user_levels", format="json", data="<new_user_level>")]
-pub async fn create_user_levels(mut db: Connection<DbConn>, new_user_level: Json<NewUserLevel>, _user: User) -> Result<Custom<Value>, Custom<Value>> {
-    UserLevelRepository::create(&mut db, new_user_level.into_inner()).await
-        .map(|user_level| Custom(Status::Created, json!(user_level)))
-        .map_err(|e| server_error(e.into()))
+pub async fn create_user_levels(mut db: Connection<DbConn>, mut cache: Connection<CacheConn>, new_user_level: Json<NewUserLevel>, idempotency: Idempotency, admin: AdminUser) -> Result<Custom<Value>, ApiError> {
+    new_user_level.validate()?;
+    idempotency.run(&mut cache, admin.0.user_id, json!(&*new_user_level), async move {
+        UserLevelRepository::create(&mut db, new_user_level.into_inner()).await
+            .map(|user_level| Custom(Status::Created, json!(user_level)))
+            .map_err(ApiError::from)
+    }).await
 }
 /* Test Endpoint with:  
   docker-compose exec app curl 127.0.0.1:8000/user_levels -H 'Content-type: application/json' 
@@ -51,22 +60,36 @@ pub async fn create_user_levels(mut db: Connection<DbConn>, new_user_level: Json
 
 //------------- update endpoint -------------
 #[rocket::put("/user_levels/<id>", format="json", data="<user_level>")]
-pub async fn update_user_levels(mut db: Connection<DbConn>, id: i32, user_level: Json<UserLevel>, _user: User) -> Result<Value, Custom<Value>> {
+pub async fn update_user_levels(mut db: Connection<DbConn>, id: i32, user_level: Json<UserLevel>, _admin: AdminUser) -> Result<Value, ApiError> {
+    user_level.validate()?;
     UserLevelRepository::update(&mut db, id, user_level.into_inner()).await
         .map(|user_level| json!(user_level))
-        .map_err(|e| server_error(e.into()))
+        .map_err(ApiError::from)
 }
 /* Test Endpoint with:  
   docker-compose exec app curl 127.0.0.1:8000/user_levels/1 -X PUT -H 'Content-type: application/json' 
   -d '{"user_id":1,"level":1,"experience_points":1000}'
 */
 
+//------------- patch endpoint -------------
+#[rocket::patch("/user_levels/<id>", data="<patch>")]
+pub async fn patch_user_levels(mut db: Connection<DbConn>, id: i32, patch: MergePatch<UserLevelPatch>, _admin: AdminUser) -> Result<Value, ApiError> {
+    patch.validate()?;
+    UserLevelRepository::patch(&mut db, id, patch.into_inner()).await
+        .map(|user_level| json!(user_level))
+        .map_err(ApiError::from)
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/user_levels/1 -X PATCH -H 'Content-type: application/merge-patch+json'
+  -d '{"level":2}'
+*/
+
 //------------- delete endpoint -------------
 #[rocket::delete("/user_levels/<id>")]
-pub async fn delete_user_levels(mut db: Connection<DbConn>, id: i32, _user: User) -> Result<NoContent, Custom<Value>> {
+pub async fn delete_user_levels(mut db: Connection<DbConn>, id: i32, _admin: AdminUser) -> Result<NoContent, ApiError> {
     UserLevelRepository::delete(&mut db, id).await
         .map(|_|  NoContent)
-        .map_err(|e| server_error(e.into()))
+        .map_err(ApiError::from)
 }
 /* Test Endpoint with: 
   docker-compose exec app curl 127.0.0.1:8000/user_levels/1 -X DELETE 
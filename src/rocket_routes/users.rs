@@ -1,10 +1,21 @@
-use crate::models::{NewUser, User};
-use crate::repositories::UserRepository;
-use crate::rocket_routes::{DbConn, server_error};
+use crate::auth::hash_password;
+use crate::config::{LoginThrottleConfig, VerificationConfig};
+use crate::mail::MailSender;
+use crate::models::{AdminUserView, NewUser, PublicUserView, RegisterUser, SelfUserView, User, UserFilter, UserPatch};
+use crate::pagination::{PageParams, Timestamp};
+use crate::repositories::{FriendshipRepository, LoginThrottleRepository, SessionRepository, UserRepository};
+use crate::rocket_routes::{AdminUser, AuthorizedUser, CacheConn, ClientIp, DbConn};
+use crate::rocket_routes::blocks::ensure_not_blocked;
+use crate::rocket_routes::error::ApiError;
+use crate::patch::MergePatch;
+use crate::rocket_routes::ownership::{ensure_owner, ensure_self};
+use crate::rocket_routes::verification::send_verification_mail;
 use rocket::response::status::NoContent;
 use rocket::{response::status::Custom, serde::json::Json};
 use rocket::http::Status;
+use rocket::State;
 use rocket_db_pools::Connection;
+use validator::Validate;
 use rocket::serde::json::{json, Value};
 
 
@@ -21,26 +32,45 @@ use rocket::serde::json::{json, Value};
 
 */
 
+// picks the representation of `user` that `caller` is allowed to see
+fn user_view(user: User, caller: &User, is_admin: bool) -> Value {
+    if is_admin {
+        json!(AdminUserView::from(user))
+    } else if user.user_id == caller.user_id {
+        json!(SelfUserView::from(user))
+    } else {
+        json!(PublicUserView::from(user))
+    }
+}
 
 //------------- get endpoint -------------
 //multi
-#[rocket::get("/users")]
-pub async fn get_users(mut db: Connection<DbConn>, _user: User) -> Result<Value, Custom<Value>> {
-    UserRepository::find_multiple(&mut db, 100).await
-        .map(|users| json!(users))
-        .map_err(|e| server_error(e.into()))
+#[rocket::get("/users?<country>&<since>&<page..>")]
+pub async fn get_users(mut db: Connection<DbConn>, country: Option<String>, since: Option<Timestamp>, page: PageParams, caller: AuthorizedUser) -> Result<Value, ApiError> {
+    let page = page.page::<User>()?;
+    // blocked users don't show up for each other
+    let hidden_ids = match caller.is_admin() {
+        true => vec![],
+        false => FriendshipRepository::find_blocked_ids(&mut db, caller.user.user_id).await?,
+    };
+    let filter = UserFilter { country, since: since.map(|since| since.0), hidden_ids };
+    let users = UserRepository::find_page(&mut db, filter, &page).await?;
+    Ok(json!(page.finish(users).map(|user| user_view(user, &caller.user, caller.is_admin()))))
 }   
 /*
     Test Endpoint with:  Working✅
-    docker-compose exec app curl 127.0.0.1:8000/users
+    docker-compose exec app curl '127.0.0.1:8000/users?country=USA&limit=20'
 */
 
 //single user
 #[rocket::get("/users/<id>")]
-pub async fn view_user(mut db: Connection<DbConn>, id: i32, _user: User) -> Result<Value, Custom<Value>> {
+pub async fn view_user(mut db: Connection<DbConn>, id: i32, caller: AuthorizedUser) -> Result<Value, ApiError> {
+    if !caller.is_admin() {
+        ensure_not_blocked(&mut db, caller.user.user_id, id).await?;
+    }
     UserRepository::find(&mut db, id).await
-        .map(|user| json!(user))
-        .map_err(|e| server_error(e.into()))
+        .map(|user| user_view(user, &caller.user, caller.is_admin()))
+        .map_err(ApiError::from)
 }
 /*
     Test Endpoint with:  Working✅
@@ -49,53 +79,127 @@ pub async fn view_user(mut db: Connection<DbConn>, id: i32, _user: User) -> Resu
 
 //------------- create endpoint -------------
 #[rocket::post("/users", format="json", data="<new_user>")]
-pub async fn create_user(mut db: Connection<DbConn>, new_user: Json<NewUser>) -> Result<Custom<Value>, Custom<Value>> {
-    UserRepository::create(&mut db, new_user.into_inner(), vec![]).await
-        .map(|user| Custom(Status::Created, json!(user)))
-        .map_err(|e| server_error(e.into()))
+pub async fn create_user(mut db: Connection<DbConn>, mail_sender: &State<Box<dyn MailSender>>, config: &State<VerificationConfig>, new_user: Json<RegisterUser>) -> Result<Custom<Value>, ApiError> {
+    new_user.validate()?;
+    let new_user = new_user.into_inner();
+    let password_hash = hash_password(new_user.password)?;
+
+    let new_user = NewUser {
+        username: new_user.username,
+        email: new_user.email,
+        password_hash,
+        full_name: new_user.full_name,
+        country: new_user.country,
+        date_of_birth: new_user.date_of_birth,
+    };
+    let user = UserRepository::create(&mut db, new_user, vec![]).await?;
+
+    // the account can't log in until the mailed link is opened, a lost mail can be resent
+    if let Err(e) = send_verification_mail(mail_sender.inner().as_ref(), config, &user).await {
+        rocket::warn!("Could not send the verification mail to user {}: {}", user.user_id, e);
+    }
+    Ok(Custom(Status::Created, json!(SelfUserView::from(user))))
 }
 /* Test Endpoint with:  Working✅
   docker-compose exec app curl 127.0.0.1:8000/users -X POST -H 'Content-type: application/json' 
   -d '{"username":"testuser","email":"testuser@gmail.com",
-       "password_hash":"testpassword","full_name":"Test User",
+       "password":"testpassword","full_name":"Test User",
        "country":"USA","date_of_birth":"1990-01-01"}'
 */
 
+// a changed address is unverified until the link mailed to it is opened,
+// the account can't log in again before that
+async fn verify_changed_email(mail_sender: &dyn MailSender, config: &VerificationConfig, before: &User, user: &User) {
+    if before.email == user.email {
+        return;
+    }
+    if let Err(e) = send_verification_mail(mail_sender, config, user).await {
+        rocket::warn!("Could not send the verification mail to user {}: {}", user.user_id, e);
+    }
+}
+
 //------------- update endpoint -------------
-#[rocket::put("/users/<id>", format="json", data="<user>")]
-pub async fn update_user(mut db: Connection<DbConn>, id: i32, user: Json<User>, _user: User) -> Result<Value, Custom<Value>> {
-    UserRepository::update(&mut db, id, user.into_inner()).await
-        .map(|user| json!(user))
-        .map_err(|e| server_error(e.into()))
+#[rocket::put("/users/<id>", format="json", data="<user_data>")]
+pub async fn update_user(mut db: Connection<DbConn>, mail_sender: &State<Box<dyn MailSender>>, config: &State<VerificationConfig>, id: i32, user_data: Json<User>, caller: AuthorizedUser) -> Result<Value, ApiError> {
+    user_data.validate()?;
+    let existing_user = UserRepository::find(&mut db, id).await?;
+    ensure_owner(&caller, &existing_user)?;
+
+    // is_active and is_admin are never taken from the body, see UserRepository::update
+    let user = UserRepository::update(&mut db, id, user_data.into_inner()).await?;
+    verify_changed_email(mail_sender.inner().as_ref(), config, &existing_user, &user).await;
+    Ok(user_view(user, &caller.user, caller.is_admin()))
 }
 /* Test Endpoint with:  working✅
   docker-compose exec app curl 127.0.0.1:8000/users/1 -X PUT -H 'Content-type: application/json' 
   -d '{"auth_token":null,"avatar_id":null,"country":"USA","date_of_birth":"1990-01-01","email":"testuser@gmail.com","full_name":"Test User","is_active":true,"is_admin":false,"language":null,"last_login":null,"last_password_change":null,"password_hash":"testpassword","registration_date":"2024-02-16T23:47:54.691633","timezone":null,"two_factor_auth_enabled":false,"user_id":1,"username":"testuserupdated"}'
 */
 
+//------------- patch endpoint -------------
+#[rocket::patch("/users/<id>", data="<patch>")]
+pub async fn patch_user(mut db: Connection<DbConn>, mail_sender: &State<Box<dyn MailSender>>, config: &State<VerificationConfig>, id: i32, patch: MergePatch<UserPatch>, caller: AuthorizedUser) -> Result<Value, ApiError> {
+    patch.validate()?;
+    let existing_user = UserRepository::find(&mut db, id).await?;
+    ensure_owner(&caller, &existing_user)?;
+
+    let user = UserRepository::patch(&mut db, id, patch.into_inner()).await?;
+    verify_changed_email(mail_sender.inner().as_ref(), config, &existing_user, &user).await;
+    Ok(user_view(user, &caller.user, caller.is_admin()))
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/users/1 -X PATCH -H 'Content-type: application/merge-patch+json'
+  -d '{"full_name":"New Name","timezone":null}'
+*/
+
 //------------- delete endpoint -------------
 #[rocket::delete("/users/<id>")]
-pub async fn delete_user(mut db: Connection<DbConn>, id: i32, _user: User) -> Result<NoContent, Custom<Value>> {
-    UserRepository::delete(&mut db, id).await
+pub async fn delete_user(mut db: Connection<DbConn>, mut cache: Connection<CacheConn>, id: i32, _admin: AdminUser) -> Result<NoContent, ApiError> {
+    UserRepository::delete(&mut db, id).await?;
+    SessionRepository::revoke_all(&mut cache, id).await
         .map(|_|  NoContent)
-        .map_err(|e| server_error(e.into()))
 }
 /* Test Endpoint with:  working✅
   docker-compose exec app curl 127.0.0.1:8000/users/1 -X DELETE 
 */
 
+//------------- revoke sessions endpoint -------------
+// logs a user out everywhere, admins can do this for any user
+#[rocket::delete("/users/<id>/sessions")]
+pub async fn revoke_user_sessions(mut cache: Connection<CacheConn>, id: i32, caller: AuthorizedUser) -> Result<NoContent, ApiError> {
+    ensure_self(&caller, id)?;
+    SessionRepository::revoke_all(&mut cache, id).await
+        .map(|_| NoContent)
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/users/1/sessions -X DELETE
+*/
+
+// the existence checks are open for the sign up form, they are throttled per ip
+// so they can't be used to list the accounts, see LoginThrottleRepository::record_lookup
+async fn throttle_lookup(cache: &mut Connection<CacheConn>, throttle: &LoginThrottleConfig, client_ip: ClientIp) -> Result<(), ApiError> {
+    let Some(ip) = client_ip.0 else {
+        return Ok(());
+    };
+    if let Some(retry_after) = LoginThrottleRepository::lookup_locked_for(cache, ip).await? {
+        return Err(ApiError::TooManyRequests("Too many lookups, try again later".to_owned(), retry_after));
+    }
+    LoginThrottleRepository::record_lookup(cache, throttle, ip).await
+}
+
 // ------------- username exists endpoint -------------
 #[rocket::get("/users/username_exists/<username>")]
-pub async fn username_exists(mut db: Connection<DbConn>, username: String) -> Result<Json<bool>, Custom<Value>> {
+pub async fn username_exists(mut db: Connection<DbConn>, mut cache: Connection<CacheConn>, throttle: &State<LoginThrottleConfig>, client_ip: ClientIp, username: String) -> Result<Json<bool>, ApiError> {
+    throttle_lookup(&mut cache, throttle, client_ip).await?;
     UserRepository::username_exists(&mut db, username).await
         .map(Json)
-        .map_err(|e| server_error(e.into()))
+        .map_err(ApiError::from)
 }
 
 // ------------- email exists endpoint -------------
 #[rocket::get("/users/email_exists/<email>")]
-pub async fn email_exists(mut db: Connection<DbConn>, email: String) -> Result<Json<bool>, Custom<Value>> {
+pub async fn email_exists(mut db: Connection<DbConn>, mut cache: Connection<CacheConn>, throttle: &State<LoginThrottleConfig>, client_ip: ClientIp, email: String) -> Result<Json<bool>, ApiError> {
+    throttle_lookup(&mut cache, throttle, client_ip).await?;
     UserRepository::email_exists(&mut db, email).await
         .map(Json)
-        .map_err(|e| server_error(e.into()))
-}
\ No newline at end of file
+        .map_err(ApiError::from)
+}
@@ -0,0 +1,80 @@
+use std::error::Error;
+
+use crate::auth::{email_verification_token, verify_email_verification_token, VerificationResend};
+use crate::config::VerificationConfig;
+use crate::mail::{Mail, MailSender};
+use crate::models::User;
+use crate::repositories::{UserRepository, VerificationResendRepository};
+use crate::rocket_routes::{CacheConn, DbConn};
+use crate::rocket_routes::error::ApiError;
+use rocket::http::Status;
+use rocket::response::status::Custom;
+use rocket::serde::json::{json, Json, Value};
+use rocket::State;
+use rocket_db_pools::Connection;
+
+/*
+    Email verification
+    new accounts can't log in until GET /verify-email/<token> was opened and set email_verified_at,
+    the signed token is mailed on registration and again through POST /verify-email/resend
+*/
+
+pub(crate) async fn send_verification_mail(mail_sender: &dyn MailSender, config: &VerificationConfig, user: &User) -> Result<(), Box<dyn Error + Send + Sync>> {
+    let expires_at = chrono::Utc::now().timestamp() + config.verification_token_ttl as i64;
+    let token = email_verification_token(&config.verification_secret, user.user_id, &user.email, expires_at);
+    mail_sender.send(Mail {
+        to: user.email.clone(),
+        subject: "Verify your BattleGear email address".to_owned(),
+        body: format!(
+            "Hi {},\n\nopen GET /verify-email/{} to activate your account.\n\ntoken: {}",
+            user.username, token, token
+        ),
+    }).await
+}
+
+//------------- verify endpoint -------------
+#[rocket::get("/verify-email/<token>")]
+pub async fn verify_email(mut db: Connection<DbConn>, config: &State<VerificationConfig>, token: &str) -> Result<Value, ApiError> {
+    let invalid = || ApiError::BadRequest("Invalid or expired verification token".to_owned());
+    let (user_id, email) = verify_email_verification_token(&config.verification_secret, token, chrono::Utc::now().timestamp())
+        .ok_or_else(invalid)?;
+    let user = match UserRepository::find(&mut db, user_id).await {
+        Ok(user) => user,
+        Err(diesel::result::Error::NotFound) => return Err(invalid()),
+        Err(e) => return Err(e.into()),
+    };
+    // the address changed since the token was sent
+    if user.email != email {
+        return Err(invalid());
+    }
+
+    UserRepository::mark_email_verified(&mut db, user.user_id).await
+        .map(|_| json!("Email address verified"))
+        .map_err(ApiError::from)
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/verify-email/<token>
+*/
+
+//------------- resend endpoint -------------
+#[rocket::post("/verify-email/resend", format="json", data="<resend>")]
+pub async fn resend_verification(mut db: Connection<DbConn>, mut cache: Connection<CacheConn>, mail_sender: &State<Box<dyn MailSender>>, config: &State<VerificationConfig>, resend: Json<VerificationResend>) -> Result<Custom<Value>, ApiError> {
+    // counted per address whether it is known or not, so the limit gives nothing away
+    if let Some(retry_after) = VerificationResendRepository::record(&mut cache, &resend.email, config.max_verification_resends).await? {
+        return Err(ApiError::TooManyRequests("Too many verification mails, try again later".to_owned(), retry_after));
+    }
+
+    let accepted = Custom(Status::Accepted, json!("If the email is registered and not verified yet a new token is on its way"));
+    let user = match UserRepository::find_by_email(&mut db, &resend.email).await {
+        Ok(user) if user.email_verified_at.is_none() => user,
+        Ok(_) | Err(diesel::result::Error::NotFound) => return Ok(accepted),
+        Err(e) => return Err(e.into()),
+    };
+    send_verification_mail(mail_sender.inner().as_ref(), config, &user).await?;
+
+    Ok(accepted)
+}
+/* Test Endpoint with:
+  docker-compose exec app curl 127.0.0.1:8000/verify-email/resend
+  -H 'Content-type: application/json' -d '{"email":"testuser@gmail.com"}'
+*/
@@ -23,6 +23,33 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    currency_transactions (transaction_id) {
+        transaction_id -> Int4,
+        user_id -> Int4,
+        #[max_length = 50]
+        currency_type -> Varchar,
+        delta -> Int4,
+        #[max_length = 50]
+        reason -> Varchar,
+        #[max_length = 255]
+        reference -> Nullable<Varchar>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    currency_types (code) {
+        #[max_length = 50]
+        code -> Varchar,
+        #[max_length = 255]
+        display_name -> Varchar,
+        max_balance -> Nullable<Int4>,
+        tradeable -> Bool,
+        created_at -> Timestamp,
+    }
+}
+
 diesel::table! {
     friendships (friendship_id) {
         friendship_id -> Int4,
@@ -44,6 +71,16 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    recovery_codes (id) {
+        id -> Int4,
+        user_id -> Int4,
+        #[max_length = 128]
+        code_hash -> Varchar,
+        used_at -> Nullable<Timestamptz>,
+    }
+}
+
 diesel::table! {
     roles (id) {
         id -> Int4,
@@ -55,6 +92,44 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    room_bans (room_id, user_id) {
+        room_id -> Int4,
+        user_id -> Int4,
+        banned_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    room_members (room_id, user_id) {
+        room_id -> Int4,
+        user_id -> Int4,
+        #[max_length = 16]
+        role -> Varchar,
+        joined_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    room_messages (message_id) {
+        message_id -> Int4,
+        room_id -> Int4,
+        sender_id -> Int4,
+        #[max_length = 1000]
+        message -> Varchar,
+        timestamp -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    rooms (room_id) {
+        room_id -> Int4,
+        #[max_length = 255]
+        name -> Varchar,
+        created_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     total_throphies (total_throphies_id) {
         total_throphies_id -> Int4,
@@ -72,6 +147,16 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    two_factor_secrets (user_id) {
+        user_id -> Int4,
+        #[max_length = 64]
+        secret -> Varchar,
+        confirmed_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+    }
+}
+
 diesel::table! {
     user_levels (user_level_id) {
         user_level_id -> Int4,
@@ -106,6 +191,7 @@ diesel::table! {
         date_of_birth -> Date,
         two_factor_auth_enabled -> Nullable<Bool>,
         last_password_change -> Nullable<Timestamptz>,
+        email_verified_at -> Nullable<Timestamptz>,
     }
 }
 
@@ -117,9 +203,19 @@ diesel::table! {
     }
 }
 
+diesel::joinable!(currency -> currency_types (currency_type));
 diesel::joinable!(currency -> users (user_id));
+diesel::joinable!(currency_transactions -> users (user_id));
+diesel::joinable!(recovery_codes -> users (user_id));
+diesel::joinable!(room_bans -> rooms (room_id));
+diesel::joinable!(room_bans -> users (user_id));
+diesel::joinable!(room_members -> rooms (room_id));
+diesel::joinable!(room_members -> users (user_id));
+diesel::joinable!(room_messages -> rooms (room_id));
+diesel::joinable!(room_messages -> users (sender_id));
 diesel::joinable!(total_throphies -> users (user_id));
 diesel::joinable!(trophies -> users (user_id));
+diesel::joinable!(two_factor_secrets -> users (user_id));
 diesel::joinable!(user_levels -> users (user_id));
 diesel::joinable!(users -> images (avatar_id));
 diesel::joinable!(users_roles -> roles (role_id));
@@ -128,11 +224,19 @@ diesel::joinable!(users_roles -> users (user_id));
 diesel::allow_tables_to_appear_in_same_query!(
     chats,
     currency,
+    currency_transactions,
+    currency_types,
     friendships,
     images,
+    recovery_codes,
     roles,
+    room_bans,
+    room_members,
+    room_messages,
+    rooms,
     total_throphies,
     trophies,
+    two_factor_secrets,
     user_levels,
     users,
     users_roles,
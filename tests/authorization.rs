@@ -65,4 +65,124 @@ fn test_login() {
 
     
     
-}
\ No newline at end of file
+}
+#[test]
+fn test_login_registered_user() {
+    // users registered over the api get their password hashed server side
+    let client = Client::new();
+    let response = client.post(format!("{}/users", common::APP_HOST))
+        .json(&json!({
+            "username":"testRegisteredUser",
+            "email":"testRegisteredUser@gmail.com",
+            "password":"testpassword",
+            "full_name":"Test User",
+            "country":"USA",
+            "date_of_birth":"1990-01-01"
+        }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    common::verify_email(&client, "testRegisteredUser@gmail.com");
+
+    let response = client.post(format!("{}/login", common::APP_HOST))
+        .json(&json!({
+            "username":"testRegisteredUser",
+            "password":"testpassword"
+        }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // clean up, through the cli so the shared admin user is left alone
+    let output = Command::new("cargo")
+        .arg("run")
+        .arg("--bin")
+        .arg("cli")
+        .arg("users")
+        .arg("delete_by_username")
+        .arg("testRegisteredUser")
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+}
+
+// every run gets its own address so earlier runs can't have locked it already,
+// the server only reads it from X-Real-IP with ROCKET_TRUST_IP_HEADER, see docker-compose.yml
+fn unique_ip() -> String {
+    let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().subsec_nanos();
+    format!("10.{}.{}.{}", (nanos >> 16) & 0xff, (nanos >> 8) & 0xff, nanos & 0xff)
+}
+
+fn login_from(client: &Client, ip: &str, username: &str, password: &str) -> reqwest::blocking::Response {
+    client.post(format!("{}/login", common::APP_HOST))
+        .header("X-Real-IP", ip)
+        .json(&json!({
+            "username":username,
+            "password":password
+        }))
+        .send()
+        .unwrap()
+}
+
+#[test]
+fn test_login_unknown_username() {
+    let client = Client::new();
+    let ip = unique_ip();
+    let response = login_from(&client, &ip, &format!("testUnknownUser{}", ip), "testpassword");
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn test_login_lockout_per_username() {
+    // setup
+    let client = Client::new();
+    let username = format!("testLockoutUser{}", unique_ip().replace('.', ""));
+    let response = client.post(format!("{}/users", common::APP_HOST))
+        .json(&json!({
+            "username":username,
+            "email":format!("{}@gmail.com", username),
+            "password":"testpassword",
+            "full_name":"Test User",
+            "country":"USA",
+            "date_of_birth":"1990-01-01"
+        }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+
+    // test
+    for _ in 0..5 {
+        let response = login_from(&client, &unique_ip(), &username, "wrongpassword");
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+    // locked, even with the right password and from another address
+    let response = login_from(&client, &unique_ip(), &username, "testpassword");
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    let retry_after: u64 = response.headers()["Retry-After"].to_str().unwrap().parse().unwrap();
+    assert!(retry_after > 0);
+
+    // clean up
+    let output = Command::new("cargo")
+        .arg("run")
+        .arg("--bin")
+        .arg("cli")
+        .arg("users")
+        .arg("delete_by_username")
+        .arg(&username)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+}
+
+#[test]
+fn test_login_lockout_per_ip() {
+    let client = Client::new();
+    let ip = unique_ip();
+    for i in 0..20 {
+        let response = login_from(&client, &ip, &format!("testUnknownUser{}-{}", ip, i), "testpassword");
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+    let response = login_from(&client, &ip, &format!("testUnknownUser{}", ip), "testpassword");
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers().get("Retry-After").is_some());
+}
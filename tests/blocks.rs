@@ -0,0 +1,128 @@
+use reqwest::{blocking::Client, StatusCode};
+use rocket::serde::json::serde_json::json;
+use serde_json::Value;
+
+mod common;
+use common::{create_test_user, delete_test_user, APP_HOST};
+
+fn block(client: &Client, user: &Value) -> StatusCode {
+    client.post(format!("{}/users/{}/block", APP_HOST, user["user_id"])).send().unwrap().status()
+}
+
+fn unblock(client: &Client, user: &Value) -> StatusCode {
+    client.delete(format!("{}/users/{}/block", APP_HOST, user["user_id"])).send().unwrap().status()
+}
+
+fn send_chat(client: &Client, sender: &Value, receiver: &Value) -> StatusCode {
+    client.post(format!("{}/chats", APP_HOST))
+        .json(&json!({ "sender_id":sender["user_id"], "receiver_id":receiver["user_id"], "message":"hello" }))
+        .send()
+        .unwrap()
+        .status()
+}
+
+fn send_friend_request(client: &Client, friend: &Value) -> StatusCode {
+    client.post(format!("{}/users/{}/friend-request", APP_HOST, friend["user_id"])).send().unwrap().status()
+}
+
+#[test]
+fn test_block_endpoints() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let blocker: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let blocked: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let blocker_client = common::get_client_with_logged_in_user(&blocker);
+    let blocked_client = common::get_client_with_logged_in_user(&blocked);
+
+    // test
+    let response = Client::new().post(format!("{}/users/{}/block", APP_HOST, blocked["user_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(block(&blocker_client, &blocker), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(block(&blocker_client, &json!({ "user_id":i32::MAX })), StatusCode::NOT_FOUND);
+    assert_eq!(unblock(&blocker_client, &blocked), StatusCode::NOT_FOUND);
+
+    // blocking replaces the friendship, blocking twice keeps one block
+    assert_eq!(send_friend_request(&blocker_client, &blocked), StatusCode::CREATED);
+    assert_eq!(block(&blocker_client, &blocked), StatusCode::CREATED);
+    assert_eq!(block(&blocker_client, &blocked), StatusCode::CREATED);
+    let response = blocker_client.get(format!("{}/friendships", APP_HOST)).send().unwrap();
+    let json: Value = response.json().unwrap();
+    let friendships = json["items"].as_array().unwrap();
+    assert_eq!(friendships.len(), 1);
+    assert_eq!(friendships[0]["status"], "blocked");
+    assert_eq!(friendships[0]["friend_id"], blocked["user_id"]);
+    let block_id = friendships[0]["friendship_id"].clone();
+
+    // only the blocker sees and lifts the block
+    let response = blocked_client.get(format!("{}/friendships", APP_HOST)).send().unwrap();
+    let json: Value = response.json().unwrap();
+    assert_eq!(json["items"], json!([]));
+    let response = blocked_client.get(format!("{}/friendships/{}", APP_HOST, block_id)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = blocked_client.delete(format!("{}/friendships/{}", APP_HOST, block_id)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_eq!(unblock(&blocked_client, &blocker), StatusCode::NOT_FOUND);
+    let response = common::merge_patch(&blocker_client, format!("{}/friendships/{}", APP_HOST, block_id), json!({ "status":"accepted" }));
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // both may block each other, each block is lifted on its own
+    assert_eq!(block(&blocked_client, &blocker), StatusCode::CREATED);
+    assert_eq!(unblock(&blocker_client, &blocked), StatusCode::NO_CONTENT);
+    assert_eq!(unblock(&blocker_client, &blocked), StatusCode::NOT_FOUND);
+    assert_eq!(send_chat(&blocker_client, &blocker, &blocked), StatusCode::FORBIDDEN);
+    assert_eq!(unblock(&blocked_client, &blocker), StatusCode::NO_CONTENT);
+
+    // clean up
+    delete_test_user(&admin_client, blocker);
+    delete_test_user(&admin_client, blocked);
+}
+
+#[test]
+fn test_block_works_both_ways() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let blocker: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let blocked: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let blocker_client = common::get_client_with_logged_in_user(&blocker);
+    let blocked_client = common::get_client_with_logged_in_user(&blocked);
+    assert_eq!(block(&blocker_client, &blocked), StatusCode::CREATED);
+
+    // test
+    // no chats
+    assert_eq!(send_chat(&blocked_client, &blocked, &blocker), StatusCode::FORBIDDEN);
+    assert_eq!(send_chat(&blocker_client, &blocker, &blocked), StatusCode::FORBIDDEN);
+    // no friend requests
+    assert_eq!(send_friend_request(&blocked_client, &blocker), StatusCode::FORBIDDEN);
+    assert_eq!(send_friend_request(&blocker_client, &blocked), StatusCode::FORBIDDEN);
+    // no profiles
+    let response = blocked_client.get(format!("{}/users/{}", APP_HOST, blocker["user_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = blocker_client.get(format!("{}/users/{}", APP_HOST, blocked["user_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = blocked_client.get(format!("{}/users?limit=100", APP_HOST)).send().unwrap();
+    let json: Value = response.json().unwrap();
+    let user_ids: Vec<&Value> = json["items"].as_array().unwrap().iter().map(|user| &user["user_id"]).collect();
+    assert!(!user_ids.contains(&&blocker["user_id"]));
+    assert!(user_ids.contains(&&blocked["user_id"]));
+    // admins still see both
+    let response = admin_client.get(format!("{}/users/{}", APP_HOST, blocker["user_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // unblocking lifts all of it
+    assert_eq!(unblock(&blocker_client, &blocked), StatusCode::NO_CONTENT);
+    let response = blocked_client.post(format!("{}/chats", APP_HOST))
+        .json(&json!({ "sender_id":blocked["user_id"], "receiver_id":blocker["user_id"], "message":"sorry" }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let chat: Value = response.json().unwrap();
+    assert_eq!(send_friend_request(&blocked_client, &blocker), StatusCode::CREATED);
+    let response = blocked_client.get(format!("{}/users/{}", APP_HOST, blocker["user_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // clean up
+    let response = admin_client.delete(format!("{}/chats/{}", APP_HOST, chat["chat_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    delete_test_user(&admin_client, blocker);
+    delete_test_user(&admin_client, blocked);
+}
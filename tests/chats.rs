@@ -1,4 +1,5 @@
 use reqwest::{blocking::Client, StatusCode};
+use std::io::{BufRead, BufReader};
 use rocket::serde::json::serde_json::json;
 use serde_json::Value;
 
@@ -60,8 +61,8 @@ fn test_get_chats() {
     let response = client.get(format!("{}/chats", APP_HOST)).send().unwrap();
     assert_eq!(response.status(), StatusCode::OK);
     let json: Value = response.json().unwrap();
-    assert!(json.as_array().unwrap().contains(&chat1));
-    assert!(json.as_array().unwrap().contains(&chat2));
+    assert!(json["items"].as_array().unwrap().contains(&chat1));
+    assert!(json["items"].as_array().unwrap().contains(&chat2));
 
     // clean up
     delete_test_chat(&client, chat1);
@@ -184,4 +185,103 @@ fn test_delete_chats() {
     // cleans up itself
     delete_test_user(&client, user);
     delete_test_user(&client, user2);
-}
\ No newline at end of file
+}
+#[test]
+fn test_chat_ownership() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let sender: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let receiver: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let stranger: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let sender_client = common::get_client_with_logged_in_user(&sender);
+    let receiver_client = common::get_client_with_logged_in_user(&receiver);
+    let stranger_client = common::get_client_with_logged_in_user(&stranger);
+    let chat: Value = create_test_chat(&sender_client, sender["user_id"].as_i64().unwrap(), receiver["user_id"].as_i64().unwrap());
+
+    // test
+    // both ends can read the chat, a stranger can't
+    let response = receiver_client.get(format!("{}/chats/{}", APP_HOST, chat["chat_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = stranger_client.get(format!("{}/chats/{}", APP_HOST, chat["chat_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = stranger_client.get(format!("{}/chats", APP_HOST)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value = response.json().unwrap();
+    assert!(!json["items"].as_array().unwrap().contains(&chat));
+
+    // only the sender can change the chat
+    let update = json!({
+        "sender_id":sender["user_id"],
+        "receiver_id":receiver["user_id"],
+        "message":"changed",
+        "is_read":true
+    });
+    let response = receiver_client.put(format!("{}/chats/{}", APP_HOST, chat["chat_id"])).json(&update).send().unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = stranger_client.delete(format!("{}/chats/{}", APP_HOST, chat["chat_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = sender_client.put(format!("{}/chats/{}", APP_HOST, chat["chat_id"])).json(&update).send().unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = common::merge_patch(&receiver_client, format!("{}/chats/{}", APP_HOST, chat["chat_id"]), json!({"is_read":true}));
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = common::merge_patch(&sender_client, format!("{}/chats/{}", APP_HOST, chat["chat_id"]), json!({"message":"patched"}));
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value = response.json().unwrap();
+    assert_eq!(json["message"], "patched");
+    assert_eq!(json["is_read"], true);
+
+    // no sending in someone else's name
+    let response = stranger_client.post(format!("{}/chats", APP_HOST))
+        .json(&json!({
+            "sender_id":sender["user_id"],
+            "receiver_id":receiver["user_id"],
+            "message":"hello"
+        }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // clean up
+    delete_test_chat(&sender_client, chat);
+    delete_test_user(&admin_client, sender);
+    delete_test_user(&admin_client, receiver);
+    delete_test_user(&admin_client, stranger);
+}
+
+#[test]
+fn test_chat_stream() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let sender: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let receiver: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let stranger: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let sender_client = common::get_client_with_logged_in_user(&sender);
+    let receiver_client = common::get_client_with_logged_in_user(&receiver);
+
+    // test
+    let response = Client::new().get(format!("{}/chats/stream", APP_HOST)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    // the receiver is subscribed once the response has started
+    let response = receiver_client.get(format!("{}/chats/stream", APP_HOST)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["Content-Type"], "text/event-stream");
+    let mut events = BufReader::new(response).lines();
+    // chats to someone else are not streamed to the receiver
+    let other_chat = create_test_chat(&sender_client, sender["user_id"].as_i64().unwrap(), stranger["user_id"].as_i64().unwrap());
+    let chat = create_test_chat(&sender_client, sender["user_id"].as_i64().unwrap(), receiver["user_id"].as_i64().unwrap());
+
+    let data = events.by_ref()
+        .map(|line| line.unwrap())
+        .find_map(|line| line.strip_prefix("data:").map(|data| data.trim().to_owned()))
+        .unwrap();
+    let streamed: Value = serde_json::from_str(&data).unwrap();
+    assert_eq!(streamed, chat);
+    drop(events);
+
+    // clean up
+    delete_test_chat(&sender_client, chat);
+    delete_test_chat(&sender_client, other_chat);
+    delete_test_user(&admin_client, sender);
+    delete_test_user(&admin_client, receiver);
+    delete_test_user(&admin_client, stranger);
+}
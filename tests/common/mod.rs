@@ -1,3 +1,6 @@
+// shared between every test binary, not all of them use every helper
+#![allow(dead_code)]
+
 use serde_json::{json, Value};
 use std::process::Command;
 use std::sync::atomic::{AtomicUsize, Ordering};
@@ -19,7 +22,7 @@ pub fn create_test_user(client: &Client, email: &str) -> Value {
         .json(&json!({
             "username": unique_username,
             "email": unique_email,
-            "password_hash":"testpassword",
+            "password":"testpassword",
             "full_name":"Test User",
             "country":"USA",
             "date_of_birth":"1990-01-01"
@@ -30,9 +33,19 @@ pub fn create_test_user(client: &Client, email: &str) -> Value {
     assert_eq!(status, StatusCode::CREATED);
     let user: Value = response.json().unwrap();
     println!("{:#?}", user);
+    verify_email(client, &unique_email);
     user
 }
 
+// opens the verification link the server mailed to the address
+pub fn verify_email(client: &Client, email: &str) {
+    let token = token_from_mail(&latest_mail(email));
+    let response = client.get(format!("{}/verify-email/{}", APP_HOST, token))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
 pub fn delete_test_user(client: &Client, user: Value) {
     let response = client.delete(format!("http://127.0.0.1:8000/users/{}", user["user_id"]))
         .send()
@@ -58,12 +71,34 @@ pub fn get_client_with_logged_in_admin() -> Client {
         .output()
         .unwrap();
 
-    // setup
+    login("testAdminUser", "testAdminUserPassword")
+}
+
+// the body of a password login of a user created through create_test_user,
+// a session or a two factor challenge
+pub fn login_json(user: &Value) -> Value {
+    let response = Client::new().post(format!("{}/login", APP_HOST))
+        .json(&json!({
+            "username":user["username"],
+            "password":"testpassword"
+        }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    response.json().unwrap()
+}
+
+// logs in a user created through create_test_user
+pub fn get_client_with_logged_in_user(user: &Value) -> Client {
+    login(user["username"].as_str().unwrap(), "testpassword")
+}
+
+pub fn login(username: &str, password: &str) -> Client {
     let client = Client::new();
     let response = client.post(format!("{}/login", APP_HOST))
         .json(&json!({
-            "username":"testAdminUser",
-            "password":"testAdminUserPassword"
+            "username":username,
+            "password":password
         }))
         .send()
         .unwrap();
@@ -79,3 +114,46 @@ pub fn get_client_with_logged_in_admin() -> Client {
     );
     ClientBuilder::new().default_headers(headers).build().unwrap()
 }
+
+// every mail the file mail sender wrote for the address, oldest first, the server writes to ./outbox
+pub fn mails_for(email: &str) -> Vec<std::path::PathBuf> {
+    let suffix = format!("_{}.txt", email);
+    let mut mails: Vec<std::path::PathBuf> = std::fs::read_dir("outbox")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.to_string_lossy().ends_with(&suffix))
+        .collect();
+    mails.sort();
+    mails
+}
+
+// the newest mail the file mail sender wrote for the address
+pub fn latest_mail(email: &str) -> String {
+    let mails = mails_for(email);
+    std::fs::read_to_string(mails.last().expect("no mail for address")).unwrap()
+}
+
+// pulls the value of a "token: ..." line out of a mail
+pub fn token_from_mail(mail: &str) -> String {
+    mail.lines()
+        .find_map(|line| line.strip_prefix("token: "))
+        .expect("no token in mail")
+        .to_owned()
+}
+
+// sends a JSON merge patch, PATCH routes only take application/merge-patch+json
+pub fn merge_patch(client: &Client, url: String, patch: Value) -> reqwest::blocking::Response {
+    client.patch(url)
+        .header(header::CONTENT_TYPE, "application/merge-patch+json")
+        .body(patch.to_string())
+        .send()
+        .unwrap()
+}
+
+// posts to the credit, debit or transfer route of the user's currency
+pub fn change_balance(client: &Client, user: &Value, operation: &str, body: Value) -> reqwest::blocking::Response {
+    client.post(format!("{}/users/{}/currency/{}", APP_HOST, user["user_id"], operation))
+        .json(&body)
+        .send()
+        .unwrap()
+}
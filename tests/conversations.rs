@@ -0,0 +1,95 @@
+use reqwest::{blocking::Client, StatusCode};
+use rocket::serde::json::serde_json::json;
+use serde_json::Value;
+
+mod common;
+use common::{create_test_user, delete_test_user, APP_HOST};
+
+fn send_chat(client: &Client, sender: &Value, receiver: &Value, message: &str) -> Value {
+    let response = client.post(format!("{}/chats", APP_HOST))
+        .json(&json!({
+            "sender_id":sender["user_id"],
+            "receiver_id":receiver["user_id"],
+            "message":message
+        }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    response.json().unwrap()
+}
+
+fn get_json(client: &Client, url: String) -> Value {
+    let response = client.get(url).send().unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    response.json().unwrap()
+}
+
+#[test]
+fn test_endpoints_protected() {
+    let client = Client::new();
+    let response = client.get(format!("{}/conversations", APP_HOST)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = client.post(format!("{}/conversations/1/read", APP_HOST)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn test_conversations() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let friend: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let other: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let client = common::get_client_with_logged_in_user(&user);
+    let friend_client = common::get_client_with_logged_in_user(&friend);
+    let other_client = common::get_client_with_logged_in_user(&other);
+    let chats = vec![
+        send_chat(&friend_client, &friend, &user, "hi"),
+        send_chat(&friend_client, &friend, &user, "are you there?"),
+        send_chat(&other_client, &other, &user, "hello"),
+        send_chat(&client, &user, &friend, "yes"),
+    ];
+
+    // test
+    // one entry per participant, newest first
+    let json = get_json(&client, format!("{}/conversations", APP_HOST));
+    assert_eq!(json["items"], json!([
+        { "user_id":friend["user_id"], "last_message":chats[3], "unread_count":2 },
+        { "user_id":other["user_id"], "last_message":chats[2], "unread_count":1 },
+    ]));
+    let json = get_json(&client, format!("{}/conversations?limit=1", APP_HOST));
+    assert_eq!(json["items"][0]["user_id"], friend["user_id"]);
+    let json = get_json(&client, format!("{}/conversations?limit=1&cursor={}", APP_HOST, json["next_cursor"].as_str().unwrap()));
+    assert_eq!(json["items"][0]["user_id"], other["user_id"]);
+    assert!(json["next_cursor"].is_null());
+
+    // the messages of one conversation, in both directions
+    let json = get_json(&client, format!("{}/conversations/{}/messages?sort=id", APP_HOST, friend["user_id"]));
+    assert_eq!(json["items"], json!([chats[0], chats[1], chats[3]]));
+    let json = get_json(&friend_client, format!("{}/conversations/{}/messages?sort=id", APP_HOST, user["user_id"]));
+    assert_eq!(json["items"], json!([chats[0], chats[1], chats[3]]));
+    let response = client.get(format!("{}/conversations/{}/messages", APP_HOST, i32::MAX)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // reading a conversation only marks what was received
+    let response = client.post(format!("{}/conversations/{}/read", APP_HOST, friend["user_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let json = get_json(&client, format!("{}/conversations", APP_HOST));
+    assert_eq!(json["items"][0]["unread_count"], 0);
+    assert_eq!(json["items"][1]["unread_count"], 1);
+    let json = get_json(&client, format!("{}/chats/{}", APP_HOST, chats[0]["chat_id"]));
+    assert_eq!(json["is_read"], true);
+    let json = get_json(&client, format!("{}/chats/{}", APP_HOST, chats[3]["chat_id"]));
+    assert_eq!(json["is_read"], false);
+    let response = client.post(format!("{}/conversations/{}/read", APP_HOST, i32::MAX)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // clean up
+    for chat in chats {
+        let response = admin_client.delete(format!("{}/chats/{}", APP_HOST, chat["chat_id"])).send().unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    }
+    delete_test_user(&admin_client, user);
+    delete_test_user(&admin_client, friend);
+    delete_test_user(&admin_client, other);
+}
@@ -3,7 +3,7 @@ use rocket::serde::json::serde_json::json;
 use serde_json::Value;
 
 mod common;
-use common::{create_test_user, delete_test_user, APP_HOST};
+use common::{change_balance, create_test_user, delete_test_user, APP_HOST};
 
 /*          Date: 2024-02-21
     Works perfectly, all endpoints are tested and working✅
@@ -57,8 +57,8 @@ fn test_get_currencies() {
     let response = client.get(format!("{}/currencies", APP_HOST)).send().unwrap();
     assert_eq!(response.status(), StatusCode::OK);
     let json: Value = response.json().unwrap();
-    assert!(json.as_array().unwrap().contains(&currency1));
-    assert!(json.as_array().unwrap().contains(&currency2));
+    assert!(json["items"].as_array().unwrap().contains(&currency1));
+    assert!(json["items"].as_array().unwrap().contains(&currency2));
 
     // clean up
     delete_test_currency(&client, currency1);
@@ -168,4 +168,323 @@ fn test_delete_currencies() {
     assert_eq!(response.status(), StatusCode::NO_CONTENT);
     // cleans up itself
     delete_test_user(&client, user);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_non_admin_cannot_change_currencies() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let currency: Value = create_test_currency(&admin_client, user["user_id"].as_i64().unwrap());
+    let client = common::get_client_with_logged_in_user(&user);
+
+    // test
+    let response = client.post(format!("{}/currencies", APP_HOST))
+        .json(&json!({
+            "user_id":user["user_id"],
+            "currency_type":"gold",
+            "amount":1000000
+        }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = client.put(format!("{}/currencies/{}", APP_HOST, currency["currency_id"]))
+        .json(&json!({
+            "currency_id":currency["currency_id"],
+            "user_id":user["user_id"],
+            "currency_type":"gold",
+            "amount":1000000
+        }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = client.delete(format!("{}/currencies/{}", APP_HOST, currency["currency_id"]))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    // reading is still fine
+    let response = client.get(format!("{}/currencies/{}", APP_HOST, currency["currency_id"]))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // clean up
+    delete_test_currency(&admin_client, currency);
+    delete_test_user(&admin_client, user);
+}
+
+#[test]
+fn test_currency_amount_validated() {
+    // setup
+    let client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&client, "testuser@gmail.com");
+
+    // test
+    let response = client.post(format!("{}/currencies", APP_HOST))
+        .json(&json!({
+            "user_id":user["user_id"],
+            "currency_type":"gold",
+            "amount":-1
+        }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let json: Value = response.json().unwrap();
+    assert_eq!(json["details"]["amount"][0]["message"], "must be at least 0");
+
+    // clean up
+    delete_test_user(&client, user);
+}
+
+#[test]
+fn test_currency_pagination() {
+    // setup
+    let client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&client, "testuser@gmail.com");
+    let silver = format!("silver_{}", chrono::Utc::now().timestamp_nanos_opt().unwrap());
+    let response = client.post(format!("{}/currency-types", APP_HOST))
+        .json(&json!({ "code":silver, "display_name":"Silver" }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    // a user has one wallet per type
+    let currencies: Vec<Value> = [("gold", 10), ("gems", 30), (silver.as_str(), 20)].iter().map(|(currency_type, amount)| {
+        let response = client.post(format!("{}/currencies", APP_HOST))
+            .json(&json!({
+                "user_id":user["user_id"],
+                "currency_type":currency_type,
+                "amount":amount
+            }))
+            .send()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        response.json().unwrap()
+    }).collect();
+
+    // test
+    // filtered by user, sorted by amount, two per page
+    let url = format!("{}/currencies?user_id={}&sort=-amount&limit=2", APP_HOST, user["user_id"]);
+    let response = client.get(&url).send().unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value = response.json().unwrap();
+    let amounts: Vec<&Value> = json["items"].as_array().unwrap().iter().map(|c| &c["amount"]).collect();
+    assert_eq!(amounts, [&json!(30), &json!(20)]);
+    let cursor = json["next_cursor"].as_str().unwrap().to_owned();
+
+    // the last page has no next cursor
+    let response = client.get(format!("{}&cursor={}", url, cursor)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value = response.json().unwrap();
+    let amounts: Vec<&Value> = json["items"].as_array().unwrap().iter().map(|c| &c["amount"]).collect();
+    assert_eq!(amounts, [&json!(10)]);
+    assert!(json["next_cursor"].is_null());
+
+    // a cursor only works with the sort it was made for
+    let response = client.get(format!("{}/currencies?sort=amount&cursor={}", APP_HOST, cursor)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    // unknown sort keys and limits out of range
+    let response = client.get(format!("{}/currencies?sort=currency_type", APP_HOST)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let json: Value = response.json().unwrap();
+    assert!(json["details"]["sort"].is_array());
+    let response = client.get(format!("{}/currencies?limit=0", APP_HOST)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let response = client.get(format!("{}/currencies?limit=101", APP_HOST)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    // clean up
+    for currency in currencies {
+        delete_test_currency(&client, currency);
+    }
+    let response = client.delete(format!("{}/currency-types/{}", APP_HOST, silver)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    delete_test_user(&client, user);
+}
+
+#[test]
+fn test_currency_ledger() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let other: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let client = common::get_client_with_logged_in_user(&user);
+    let other_client = common::get_client_with_logged_in_user(&other);
+    let history_url = format!("{}/users/{}/currency-transactions?sort=id", APP_HOST, user["user_id"]);
+    let currency: Value = create_test_currency(&admin_client, user["user_id"].as_i64().unwrap());
+
+    // test
+    // every change is booked
+    let response = common::merge_patch(&admin_client, format!("{}/currencies/{}", APP_HOST, currency["currency_id"]), json!({"amount":1500}));
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = common::merge_patch(&admin_client, format!("{}/currencies/{}", APP_HOST, currency["currency_id"]), json!({"amount":1500}));
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = admin_client.put(format!("{}/currencies/{}", APP_HOST, currency["currency_id"]))
+        .json(&json!({ "user_id":user["user_id"], "currency_type":"gems", "amount":20 }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    delete_test_currency(&admin_client, currency.clone());
+
+    let response = client.get(&history_url).send().unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value = response.json().unwrap();
+    let bookings: Vec<(Value, Value)> = json["items"].as_array().unwrap().iter()
+        .map(|booking| (booking["currency_type"].clone(), booking["delta"].clone()))
+        .collect();
+    assert_eq!(bookings, [
+        (json!("gold"), json!(1000)),
+        (json!("gold"), json!(500)),
+        (json!("gold"), json!(-1500)),
+        (json!("gems"), json!(20)),
+        (json!("gems"), json!(-20)),
+    ]);
+    assert_eq!(json["items"][0]["reason"], "adjustment");
+    assert_eq!(json["items"][0]["reference"], format!("currency/{}", currency["currency_id"]));
+    let response = client.get(format!("{}&currency_type=gems", history_url)).send().unwrap();
+    let json: Value = response.json().unwrap();
+    assert_eq!(json["items"].as_array().unwrap().len(), 2);
+
+    // users read their own history only
+    let response = other_client.get(&history_url).send().unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = admin_client.get(&history_url).send().unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = admin_client.get(format!("{}/users/{}/currency-transactions", APP_HOST, i32::MAX)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // clean up
+    delete_test_user(&admin_client, user);
+    delete_test_user(&admin_client, other);
+}
+
+#[test]
+fn test_currency_credit_debit() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let client = common::get_client_with_logged_in_user(&user);
+
+    // test
+    // the wallet is created on the first credit
+    let response = change_balance(&admin_client, &user, "credit", json!({ "currency_type":"gold", "amount":100, "reason":"match_reward", "reference":"matches/42" }));
+    assert_eq!(response.status(), StatusCode::OK);
+    let wallet: Value = response.json().unwrap();
+    assert_eq!(wallet["user_id"], user["user_id"]);
+    assert_eq!(wallet["amount"], 100);
+    let response = change_balance(&admin_client, &user, "debit", json!({ "currency_type":"gold", "amount":30 }));
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value = response.json().unwrap();
+    assert_eq!(json["currency_id"], wallet["currency_id"]);
+    assert_eq!(json["amount"], 70);
+
+    // no overdraw, no empty changes, no users changing their own balance
+    let response = change_balance(&admin_client, &user, "debit", json!({ "currency_type":"gold", "amount":71 }));
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let json: Value = response.json().unwrap();
+    assert_eq!(json["details"], json!({ "balance":70, "amount":71 }));
+    let response = change_balance(&admin_client, &user, "debit", json!({ "currency_type":"gems", "amount":1 }));
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let response = change_balance(&admin_client, &user, "credit", json!({ "currency_type":"gold", "amount":0 }));
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let response = change_balance(&client, &user, "credit", json!({ "currency_type":"gold", "amount":1000 }));
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = change_balance(&admin_client, &json!({ "user_id":i32::MAX }), "credit", json!({ "currency_type":"gold", "amount":1 }));
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // both are booked
+    let response = client.get(format!("{}/users/{}/currency-transactions?sort=id", APP_HOST, user["user_id"])).send().unwrap();
+    let json: Value = response.json().unwrap();
+    let bookings: Vec<(Value, Value, Value)> = json["items"].as_array().unwrap().iter()
+        .map(|booking| (booking["delta"].clone(), booking["reason"].clone(), booking["reference"].clone()))
+        .collect();
+    assert_eq!(bookings, [
+        (json!(100), json!("match_reward"), json!("matches/42")),
+        (json!(-30), json!("debit"), json!(null)),
+    ]);
+
+    // clean up
+    delete_test_user(&admin_client, user);
+}
+
+#[test]
+fn test_currency_transfer() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let sender: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let receiver: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let sender_client = common::get_client_with_logged_in_user(&sender);
+    let receiver_client = common::get_client_with_logged_in_user(&receiver);
+    let response = change_balance(&admin_client, &sender, "credit", json!({ "currency_type":"gold", "amount":100 }));
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // test
+    let response = change_balance(&sender_client, &sender, "transfer", json!({ "to_user_id":receiver["user_id"], "currency_type":"gold", "amount":40 }));
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value = response.json().unwrap();
+    assert_eq!(json["from"]["amount"], 60);
+    assert_eq!(json["to"]["user_id"], receiver["user_id"]);
+    assert_eq!(json["to"]["amount"], 40);
+    // back, the other way around
+    let response = change_balance(&receiver_client, &receiver, "transfer", json!({ "to_user_id":sender["user_id"], "currency_type":"gold", "amount":40 }));
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value = response.json().unwrap();
+    assert_eq!(json["from"]["amount"], 0);
+    assert_eq!(json["to"]["amount"], 100);
+
+    // nothing moves when the sender can't pay
+    let response = change_balance(&receiver_client, &receiver, "transfer", json!({ "to_user_id":sender["user_id"], "currency_type":"gold", "amount":1 }));
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let response = change_balance(&sender_client, &sender, "transfer", json!({ "to_user_id":receiver["user_id"], "currency_type":"gems", "amount":1 }));
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    // only from your own wallet, to someone else
+    let response = change_balance(&receiver_client, &sender, "transfer", json!({ "to_user_id":receiver["user_id"], "currency_type":"gold", "amount":1 }));
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = change_balance(&sender_client, &sender, "transfer", json!({ "to_user_id":sender["user_id"], "currency_type":"gold", "amount":1 }));
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let response = change_balance(&sender_client, &sender, "transfer", json!({ "to_user_id":i32::MAX, "currency_type":"gold", "amount":1 }));
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    let response = receiver_client.get(format!("{}/users/{}/currency-transactions?sort=id", APP_HOST, receiver["user_id"])).send().unwrap();
+    let json: Value = response.json().unwrap();
+    let bookings: Vec<(Value, Value, Value)> = json["items"].as_array().unwrap().iter()
+        .map(|booking| (booking["delta"].clone(), booking["reason"].clone(), booking["reference"].clone()))
+        .collect();
+    assert_eq!(bookings, [
+        (json!(40), json!("transfer_in"), json!(format!("users/{}", sender["user_id"]))),
+        (json!(-40), json!("transfer_out"), json!(format!("users/{}", sender["user_id"]))),
+    ]);
+
+    // clean up
+    delete_test_user(&admin_client, sender);
+    delete_test_user(&admin_client, receiver);
+}
+
+#[test]
+fn test_concurrent_debits_never_overdraw() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let response = change_balance(&admin_client, &user, "credit", json!({ "currency_type":"gold", "amount":500 }));
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // test, ten debits of 100 race for a balance of 500
+    let statuses: Vec<StatusCode> = (0..10)
+        .map(|_| {
+            let client = admin_client.clone();
+            let user = user.clone();
+            std::thread::spawn(move || change_balance(&client, &user, "debit", json!({ "currency_type":"gold", "amount":100 })).status())
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .map(|thread| thread.join().unwrap())
+        .collect();
+    // requests may also time out waiting for a database connection, the ones that went through add up
+    let debited = statuses.iter().filter(|status| **status == StatusCode::OK).count() as i64;
+    assert!(debited <= 5);
+    let response = admin_client.get(format!("{}/currencies?user_id={}", APP_HOST, user["user_id"])).send().unwrap();
+    let json: Value = response.json().unwrap();
+    assert_eq!(json["items"][0]["amount"], 500 - 100 * debited);
+
+    // clean up
+    delete_test_user(&admin_client, user);
+}
@@ -0,0 +1,139 @@
+use reqwest::{blocking::Client, StatusCode};
+use rocket::serde::json::serde_json::json;
+use serde_json::Value;
+
+mod common;
+use common::{change_balance, create_test_user, delete_test_user, APP_HOST};
+
+// types outlive the test database's users, every run needs fresh codes
+fn new_code(name: &str) -> String {
+    format!("{}_{}", name, chrono::Utc::now().timestamp_nanos_opt().unwrap())
+}
+
+#[test]
+fn test_currency_type_endpoints() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let other: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let client = common::get_client_with_logged_in_user(&user);
+    let code = new_code("tokens");
+
+    // test
+    let response = client.get(format!("{}/currency-types", APP_HOST)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value = response.json().unwrap();
+    let codes: Vec<&Value> = json["items"].as_array().unwrap().iter().map(|currency_type| &currency_type["code"]).collect();
+    assert!(codes.contains(&&json!("gold")));
+    assert!(codes.contains(&&json!("gems")));
+
+    // only admins change the catalog
+    let body = json!({ "code":code, "display_name":"Tokens", "max_balance":100, "tradeable":false });
+    let response = client.post(format!("{}/currency-types", APP_HOST)).json(&body).send().unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = admin_client.post(format!("{}/currency-types", APP_HOST)).json(&body).send().unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let currency_type: Value = response.json().unwrap();
+    assert_eq!(currency_type["max_balance"], 100);
+    assert_eq!(currency_type["tradeable"], false);
+    let response = admin_client.post(format!("{}/currency-types", APP_HOST)).json(&body).send().unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let response = admin_client.post(format!("{}/currency-types", APP_HOST))
+        .json(&json!({ "code":"Bad Code", "display_name":"Bad" }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let json: Value = response.json().unwrap();
+    assert_eq!(json["details"]["code"][0]["code"], "currency_code");
+
+    // wallets can't grow past max_balance and the type can't be traded
+    let response = change_balance(&admin_client, &user, "credit", json!({ "currency_type":format!(" {} ", code.to_uppercase()), "amount":60 }));
+    assert_eq!(response.status(), StatusCode::OK);
+    let wallet: Value = response.json().unwrap();
+    assert_eq!(wallet["currency_type"], code);
+    let response = change_balance(&admin_client, &user, "credit", json!({ "currency_type":code, "amount":60 }));
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let json: Value = response.json().unwrap();
+    assert_eq!(json["message"], "Balance limit exceeded");
+    assert_eq!(json["details"], json!({ "max_balance":100, "balance":120 }));
+    let response = common::merge_patch(&admin_client, format!("{}/currencies/{}", APP_HOST, wallet["currency_id"]), json!({ "amount":101 }));
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let response = change_balance(&client, &user, "transfer", json!({ "to_user_id":other["user_id"], "currency_type":code, "amount":10 }));
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let json: Value = response.json().unwrap();
+    assert_eq!(json["message"], "This currency type can't be traded");
+
+    // lifting the limit
+    let response = common::merge_patch(&admin_client, format!("{}/currency-types/{}", APP_HOST, code), json!({ "max_balance":null }));
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = change_balance(&admin_client, &user, "credit", json!({ "currency_type":code, "amount":60 }));
+    assert_eq!(response.status(), StatusCode::OK);
+    let wallet: Value = response.json().unwrap();
+    assert_eq!(wallet["amount"], 120);
+
+    // a type that is held can't be deleted
+    let response = admin_client.delete(format!("{}/currency-types/{}", APP_HOST, code)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    // clean up
+    delete_test_user(&admin_client, user);
+    delete_test_user(&admin_client, other);
+    let response = admin_client.delete(format!("{}/currency-types/{}", APP_HOST, code)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = admin_client.get(format!("{}/currency-types/{}", APP_HOST, code)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn test_one_wallet_per_type() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let client = common::get_client_with_logged_in_user(&user);
+
+    // test
+    // "Gold " is gold, a second gold wallet is a conflict
+    let response = admin_client.post(format!("{}/currencies", APP_HOST))
+        .json(&json!({ "user_id":user["user_id"], "currency_type":"Gold ", "amount":1000 }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let currency: Value = response.json().unwrap();
+    assert_eq!(currency["currency_type"], "gold");
+    let response = admin_client.post(format!("{}/currencies", APP_HOST))
+        .json(&json!({ "user_id":user["user_id"], "currency_type":"gold", "amount":1 }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+    let json: Value = response.json().unwrap();
+    assert_eq!(json["details"]["constraint"], "currency_user_type_unique");
+    // credits go into the one wallet
+    let response = change_balance(&admin_client, &user, "credit", json!({ "currency_type":"GOLD", "amount":1 }));
+    assert_eq!(response.status(), StatusCode::OK);
+    let wallet: Value = response.json().unwrap();
+    assert_eq!(wallet["currency_id"], currency["currency_id"]);
+    // types have to be in the catalog
+    let response = admin_client.post(format!("{}/currencies", APP_HOST))
+        .json(&json!({ "user_id":user["user_id"], "currency_type":new_code("unknown"), "amount":1 }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let response = change_balance(&admin_client, &user, "credit", json!({ "currency_type":new_code("unknown"), "amount":1 }));
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let json: Value = response.json().unwrap();
+    assert_eq!(json["message"], "Unknown currency type");
+
+    // the wallet lists every type, 0 for the ones not held
+    let response = Client::new().get(format!("{}/me/wallet", APP_HOST)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = client.get(format!("{}/me/wallet", APP_HOST)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value = response.json().unwrap();
+    let balances = json["items"].as_array().unwrap();
+    let balance = |code: &str| balances.iter().find(|balance| balance["currency_type"] == code).unwrap().clone();
+    assert_eq!(balance("gold"), json!({ "currency_type":"gold", "display_name":"Gold", "balance":1001, "max_balance":null, "tradeable":true }));
+    assert_eq!(balance("gems")["balance"], 0);
+
+    // clean up
+    delete_test_user(&admin_client, user);
+}
@@ -0,0 +1,82 @@
+use reqwest::{blocking::Client, StatusCode};
+use rocket::serde::json::serde_json::json;
+use serde_json::Value;
+
+mod common;
+use common::{create_test_user, delete_test_user, APP_HOST};
+
+// every error body has the same members, the request id is the one from the header
+fn assert_error_body(response: reqwest::blocking::Response, status: StatusCode, code: &str) -> Value {
+    assert_eq!(response.status(), status);
+    let request_id = response.headers()["X-Request-Id"].to_str().unwrap().to_owned();
+    let json: Value = response.json().unwrap();
+    assert_eq!(json["code"], code);
+    assert!(json["message"].is_string());
+    assert!(json.get("details").is_some());
+    assert_eq!(json["request_id"], request_id);
+    json
+}
+
+#[test]
+fn test_guard_errors() {
+    let client = Client::new();
+    let response = client.get(format!("{}/users", APP_HOST)).send().unwrap();
+    assert_error_body(response, StatusCode::UNAUTHORIZED, "unauthorized");
+    let response = client.get(format!("{}/no_such_route", APP_HOST)).send().unwrap();
+    assert_error_body(response, StatusCode::NOT_FOUND, "not_found");
+}
+
+#[test]
+fn test_request_id() {
+    let client = Client::new();
+    // a request id sent by the client is kept
+    let response = client.get(format!("{}/users", APP_HOST))
+        .header("X-Request-Id", "test-request-id-1")
+        .send()
+        .unwrap();
+    let json = assert_error_body(response, StatusCode::UNAUTHORIZED, "unauthorized");
+    assert_eq!(json["request_id"], "test-request-id-1");
+    // successful responses have one as well
+    let response = client.get(format!("{}/users/username_exists/nobody", APP_HOST)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().get("X-Request-Id").is_some());
+}
+
+#[test]
+fn test_database_errors() {
+    // setup
+    let client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&client, "testuser@gmail.com");
+
+    // test
+    // missing rows
+    let response = client.get(format!("{}/users/{}", APP_HOST, i32::MAX)).send().unwrap();
+    assert_error_body(response, StatusCode::NOT_FOUND, "not_found");
+    // unique violation
+    let response = client.post(format!("{}/users", APP_HOST))
+        .json(&json!({
+            "username":user["username"],
+            "email":"testDuplicateUser@gmail.com",
+            "password":"testpassword",
+            "full_name":"Test User",
+            "country":"USA",
+            "date_of_birth":"1990-01-01"
+        }))
+        .send()
+        .unwrap();
+    let json = assert_error_body(response, StatusCode::CONFLICT, "conflict");
+    assert_eq!(json["details"]["constraint"], "username_unique");
+    // foreign key violation
+    let response = client.post(format!("{}/chats", APP_HOST))
+        .json(&json!({
+            "sender_id":user["user_id"],
+            "receiver_id":i32::MAX,
+            "message":"hello"
+        }))
+        .send()
+        .unwrap();
+    assert_error_body(response, StatusCode::UNPROCESSABLE_ENTITY, "unprocessable_entity");
+
+    // clean up
+    delete_test_user(&client, user);
+}
@@ -0,0 +1,232 @@
+use reqwest::{blocking::Client, StatusCode};
+use rocket::serde::json::serde_json::json;
+use serde_json::Value;
+
+mod common;
+use common::{create_test_user, delete_test_user, APP_HOST};
+
+fn send_friend_request(client: &Client, friend: &Value) -> reqwest::blocking::Response {
+    client.post(format!("{}/users/{}/friend-request", APP_HOST, friend["user_id"])).send().unwrap()
+}
+
+fn answer(client: &Client, request: &Value, answer: &str) -> reqwest::blocking::Response {
+    client.post(format!("{}/friend-requests/{}/{}", APP_HOST, request["friendship_id"], answer)).send().unwrap()
+}
+
+fn list(client: &Client, path: &str) -> Value {
+    let response = client.get(format!("{}/{}", APP_HOST, path)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value = response.json().unwrap();
+    json["items"].clone()
+}
+
+fn friend_ids(client: &Client) -> Vec<Value> {
+    list(client, "friends").as_array().unwrap().iter().map(|friend| friend["user_id"].clone()).collect()
+}
+
+#[test]
+fn test_endpoints_protected() {
+    let client = Client::new();
+    let response = client.get(format!("{}/friends", APP_HOST)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = client.get(format!("{}/friend-requests/incoming", APP_HOST)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = client.post(format!("{}/users/1/friend-request", APP_HOST)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn test_friend_requests() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let sender: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let recipient: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let sender_client = common::get_client_with_logged_in_user(&sender);
+    let recipient_client = common::get_client_with_logged_in_user(&recipient);
+
+    // test
+    // requests are always pending and from the caller
+    let response = send_friend_request(&sender_client, &recipient);
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let request: Value = response.json().unwrap();
+    assert_eq!(request["user_id"], sender["user_id"]);
+    assert_eq!(request["friend_id"], recipient["user_id"]);
+    assert_eq!(request["status"], "pending");
+    assert_eq!(send_friend_request(&sender_client, &sender).status(), StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(send_friend_request(&sender_client, &json!({ "user_id":i32::MAX })).status(), StatusCode::NOT_FOUND);
+    // one request per pair, in either direction
+    assert_eq!(send_friend_request(&sender_client, &recipient).status(), StatusCode::CONFLICT);
+    assert_eq!(send_friend_request(&recipient_client, &sender).status(), StatusCode::CONFLICT);
+
+    assert_eq!(list(&sender_client, "friend-requests/outgoing"), json!([request]));
+    assert_eq!(list(&sender_client, "friend-requests/incoming"), json!([]));
+    assert_eq!(list(&recipient_client, "friend-requests/incoming"), json!([request]));
+    assert_eq!(list(&recipient_client, "friend-requests/outgoing"), json!([]));
+
+    // only the recipient answers
+    assert_eq!(answer(&sender_client, &request, "accept").status(), StatusCode::FORBIDDEN);
+    assert_eq!(answer(&sender_client, &request, "decline").status(), StatusCode::FORBIDDEN);
+    // not even an admin agrees on the recipient's behalf
+    assert_eq!(answer(&admin_client, &request, "accept").status(), StatusCode::FORBIDDEN);
+    assert_eq!(answer(&admin_client, &request, "decline").status(), StatusCode::FORBIDDEN);
+    assert_eq!(answer(&recipient_client, &request, "decline").status(), StatusCode::NO_CONTENT);
+    assert_eq!(answer(&recipient_client, &request, "accept").status(), StatusCode::NOT_FOUND);
+    assert_eq!(list(&recipient_client, "friend-requests/incoming"), json!([]));
+    assert_eq!(friend_ids(&sender_client), Vec::<Value>::new());
+
+    // a declined request can be sent again
+    let response = send_friend_request(&sender_client, &recipient);
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let request: Value = response.json().unwrap();
+    let response = answer(&recipient_client, &request, "accept");
+    assert_eq!(response.status(), StatusCode::OK);
+    let friendship: Value = response.json().unwrap();
+    assert_eq!(friendship["status"], "accepted");
+    assert_eq!(answer(&recipient_client, &request, "accept").status(), StatusCode::NOT_FOUND);
+
+    // clean up
+    delete_test_user(&admin_client, sender);
+    delete_test_user(&admin_client, recipient);
+}
+
+#[test]
+fn test_friends() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let friend1: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let friend2: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let stranger: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let user_client = common::get_client_with_logged_in_user(&user);
+    let friend1_client = common::get_client_with_logged_in_user(&friend1);
+    let friend2_client = common::get_client_with_logged_in_user(&friend2);
+    let stranger_client = common::get_client_with_logged_in_user(&stranger);
+    // one request sent by the user, one received
+    let request: Value = send_friend_request(&user_client, &friend1).json().unwrap();
+    assert_eq!(answer(&friend1_client, &request, "accept").status(), StatusCode::OK);
+    let request: Value = send_friend_request(&friend2_client, &user).json().unwrap();
+    assert_eq!(answer(&user_client, &request, "accept").status(), StatusCode::OK);
+    // pending requests are no friendships
+    assert_eq!(send_friend_request(&stranger_client, &user).status(), StatusCode::CREATED);
+
+    // test
+    assert_eq!(friend_ids(&user_client), [friend2["user_id"].clone(), friend1["user_id"].clone()]);
+    assert_eq!(friend_ids(&friend1_client), [user["user_id"].clone()]);
+    assert_eq!(friend_ids(&friend2_client), [user["user_id"].clone()]);
+    // friends see public profiles only
+    let friends = list(&friend1_client, "friends");
+    assert!(friends[0].get("email").is_none());
+
+    // either side unfriends
+    let response = friend1_client.delete(format!("{}/friends/{}", APP_HOST, user["user_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = user_client.delete(format!("{}/friends/{}", APP_HOST, friend2["user_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = user_client.delete(format!("{}/friends/{}", APP_HOST, friend2["user_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = user_client.delete(format!("{}/friends/{}", APP_HOST, stranger["user_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(friend_ids(&user_client), Vec::<Value>::new());
+
+    // clean up
+    delete_test_user(&admin_client, user);
+    delete_test_user(&admin_client, friend1);
+    delete_test_user(&admin_client, friend2);
+    delete_test_user(&admin_client, stranger);
+}
+
+// accepted friendship between a and b, set up by an admin
+fn make_friends(admin_client: &Client, a: &Value, b: &Value) {
+    let response = admin_client.post(format!("{}/friendships", APP_HOST))
+        .json(&json!({ "user_id":a["user_id"], "friend_id":b["user_id"], "status":"accepted" }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+}
+
+#[test]
+fn test_mutual_friends() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let other: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let shared1: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let shared2: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let only_mine: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    for shared in [&shared1, &shared2] {
+        make_friends(&admin_client, &user, shared);
+        make_friends(&admin_client, shared, &other);
+    }
+    make_friends(&admin_client, &only_mine, &user);
+    let user_client = common::get_client_with_logged_in_user(&user);
+    let other_client = common::get_client_with_logged_in_user(&other);
+
+    // test
+    let mutual_ids = |client: &Client, of: &Value| -> Vec<Value> {
+        list(client, &format!("users/{}/mutual-friends?sort=id", of["user_id"])).as_array().unwrap().iter()
+            .map(|friend| friend["user_id"].clone())
+            .collect()
+    };
+    assert_eq!(mutual_ids(&user_client, &other), [shared1["user_id"].clone(), shared2["user_id"].clone()]);
+    assert_eq!(mutual_ids(&other_client, &user), [shared1["user_id"].clone(), shared2["user_id"].clone()]);
+    assert_eq!(mutual_ids(&user_client, &only_mine), Vec::<Value>::new());
+    let response = user_client.get(format!("{}/users/{}/mutual-friends", APP_HOST, i32::MAX)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    // not with users that blocked you
+    let response = other_client.post(format!("{}/users/{}/block", APP_HOST, user["user_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let response = user_client.get(format!("{}/users/{}/mutual-friends", APP_HOST, other["user_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // clean up
+    for user in [user, other, shared1, shared2, only_mine] {
+        delete_test_user(&admin_client, user);
+    }
+}
+
+#[test]
+fn test_friend_suggestions() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let friend1: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let friend2: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let two_mutual: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let one_mutual: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let requested: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let blocked: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    make_friends(&admin_client, &user, &friend1);
+    make_friends(&admin_client, &friend2, &user);
+    make_friends(&admin_client, &friend1, &two_mutual);
+    make_friends(&admin_client, &two_mutual, &friend2);
+    for candidate in [&one_mutual, &requested, &blocked] {
+        make_friends(&admin_client, &friend1, candidate);
+    }
+    let user_client = common::get_client_with_logged_in_user(&user);
+    assert_eq!(send_friend_request(&user_client, &requested).status(), StatusCode::CREATED);
+    let response = user_client.post(format!("{}/users/{}/block", APP_HOST, blocked["user_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+
+    // test
+    let response = user_client.get(format!("{}/friends/suggestions?limit=0", APP_HOST)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let suggestions = list(&user_client, "friends/suggestions?limit=50");
+    let suggestions = suggestions.as_array().unwrap();
+    // shared friends rank first, everyone here has the same country and language and no trophies
+    assert_eq!(suggestions[0]["user"]["user_id"], two_mutual["user_id"]);
+    assert_eq!(suggestions[0]["mutual_friends"], 2);
+    assert_eq!(suggestions[0]["score"], 3 * 2 + 2 + 1 + 1);
+    assert!(suggestions[0]["user"].get("email").is_none());
+    assert_eq!(suggestions[1]["user"]["user_id"], one_mutual["user_id"]);
+    assert_eq!(suggestions[1]["mutual_friends"], 1);
+    // no friends, pending requests, blocked users or the user themselves
+    let suggested_ids: Vec<&Value> = suggestions.iter().map(|suggestion| &suggestion["user"]["user_id"]).collect();
+    for excluded in [&user, &friend1, &friend2, &requested, &blocked] {
+        assert!(!suggested_ids.contains(&&excluded["user_id"]));
+    }
+
+    // clean up
+    for user in [user, friend1, friend2, two_mutual, one_mutual, requested, blocked] {
+        delete_test_user(&admin_client, user);
+    }
+}
@@ -51,22 +51,24 @@ fn test_get_friendships() {
     let client = common::get_client_with_logged_in_admin();
     let user1: Value = create_test_user(&client, "testuser@gmail.com");
     let user2: Value = create_test_user(&client, "testuser@gmail.com");
-    // friend with each other
+    let user3: Value = create_test_user(&client, "testuser@gmail.com");
+    // user1 is friends with both
     let friendship1: Value = create_test_friendships(&client, user1["user_id"].as_i64().unwrap(), user2["user_id"].as_i64().unwrap());
-    let friendship2: Value = create_test_friendships(&client, user2["user_id"].as_i64().unwrap(), user1["user_id"].as_i64().unwrap());
+    let friendship2: Value = create_test_friendships(&client, user3["user_id"].as_i64().unwrap(), user1["user_id"].as_i64().unwrap());
 
     // test
     let response = client.get(format!("{}/friendships", APP_HOST)).send().unwrap();
     assert_eq!(response.status(), StatusCode::OK);
     let json: Value = response.json().unwrap();
-    assert!(json.as_array().unwrap().contains(&friendship1));
-    assert!(json.as_array().unwrap().contains(&friendship2));
+    assert!(json["items"].as_array().unwrap().contains(&friendship1));
+    assert!(json["items"].as_array().unwrap().contains(&friendship2));
 
     // clean up
     delete_test_friendship(&client, friendship1);
     delete_test_friendship(&client, friendship2);
     delete_test_user(&client, user1);
     delete_test_user(&client, user2);
+    delete_test_user(&client, user3);
 }
 
 #[test]
@@ -183,4 +185,104 @@ fn test_delete_friendships() {
     // cleans up itself
     delete_test_user(&client, user);
     delete_test_user(&client, user2);
-}
\ No newline at end of file
+}
+#[test]
+fn test_friendship_ownership() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let friend: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let stranger: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let friendship: Value = create_test_friendships(&admin_client, user["user_id"].as_i64().unwrap(), friend["user_id"].as_i64().unwrap());
+    let friend_client = common::get_client_with_logged_in_user(&friend);
+    let stranger_client = common::get_client_with_logged_in_user(&stranger);
+
+    // test
+    let response = friend_client.get(format!("{}/friendships/{}", APP_HOST, friendship["friendship_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = stranger_client.get(format!("{}/friendships/{}", APP_HOST, friendship["friendship_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = stranger_client.put(format!("{}/friendships/{}", APP_HOST, friendship["friendship_id"]))
+        .json(&json!({
+            "user_id":stranger["user_id"],
+            "friend_id":friend["user_id"],
+            "status":"accepted"
+        }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = stranger_client.delete(format!("{}/friendships/{}", APP_HOST, friendship["friendship_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = stranger_client.post(format!("{}/friendships", APP_HOST))
+        .json(&json!({
+            "user_id":user["user_id"],
+            "friend_id":stranger["user_id"],
+            "status":"accepted"
+        }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    // users don't change friendships directly, they send and answer friend requests
+    let response = common::merge_patch(&friend_client, format!("{}/friendships/{}", APP_HOST, friendship["friendship_id"]), json!({"status":"pending"}));
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // clean up, either side may end the friendship
+    delete_test_friendship(&friend_client, friendship);
+    delete_test_user(&admin_client, user);
+    delete_test_user(&admin_client, friend);
+    delete_test_user(&admin_client, stranger);
+}
+
+#[test]
+fn test_friendship_status_validated() {
+    // setup
+    let client = common::get_client_with_logged_in_admin();
+    let user1: Value = create_test_user(&client, "testuser@gmail.com");
+    let user2: Value = create_test_user(&client, "testuser@gmail.com");
+    let friendship: Value = create_test_friendships(&client, user1["user_id"].as_i64().unwrap(), user2["user_id"].as_i64().unwrap());
+
+    // test
+    let response = client.post(format!("{}/friendships", APP_HOST))
+        .json(&json!({
+            "user_id":user1["user_id"],
+            "friend_id":user2["user_id"],
+            "status":"best friends"
+        }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let json: Value = response.json().unwrap();
+    assert_eq!(json["details"]["status"][0]["code"], "friendship_status");
+    let response = common::merge_patch(&client, format!("{}/friendships/{}", APP_HOST, friendship["friendship_id"]), json!({"status":"unknown"}));
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    // clean up
+    delete_test_friendship(&client, friendship);
+    delete_test_user(&client, user1);
+    delete_test_user(&client, user2);
+}
+
+#[test]
+fn test_friendship_pair_unique() {
+    // setup
+    let client = common::get_client_with_logged_in_admin();
+    let user1: Value = create_test_user(&client, "testuser@gmail.com");
+    let user2: Value = create_test_user(&client, "testuser@gmail.com");
+    let friendship: Value = create_test_friendships(&client, user1["user_id"].as_i64().unwrap(), user2["user_id"].as_i64().unwrap());
+
+    // test, in either direction
+    for (user_id, friend_id) in [(&user1, &user2), (&user2, &user1)] {
+        let response = client.post(format!("{}/friendships", APP_HOST))
+            .json(&json!({ "user_id":user_id["user_id"], "friend_id":friend_id["user_id"], "status":"pending" }))
+            .send()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let json: Value = response.json().unwrap();
+        assert_eq!(json["details"]["constraint"], "friendships_pair_unique");
+    }
+
+    // clean up
+    delete_test_friendship(&client, friendship);
+    delete_test_user(&client, user1);
+    delete_test_user(&client, user2);
+}
@@ -0,0 +1,99 @@
+use reqwest::{blocking::Client, StatusCode};
+use rocket::serde::json::serde_json::json;
+use serde_json::Value;
+
+mod common;
+use common::{create_test_user, delete_test_user, APP_HOST};
+
+// keys outlive the test users in redis, every run needs fresh ones
+fn new_key(name: &str) -> String {
+    format!("test-{}-{}", name, chrono::Utc::now().timestamp_nanos_opt().unwrap())
+}
+
+fn post(client: &Client, path: String, key: &str, body: Value) -> reqwest::blocking::Response {
+    client.post(format!("{}{}", APP_HOST, path))
+        .header("Idempotency-Key", key)
+        .json(&body)
+        .send()
+        .unwrap()
+}
+
+#[test]
+fn test_idempotent_create_is_replayed() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let key = new_key("currency");
+    let body = json!({ "user_id":user["user_id"], "currency_type":"gold", "amount":1000 });
+
+    // test
+    let response = post(&admin_client, "/currencies".to_owned(), &key, body.clone());
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let currency: Value = response.json().unwrap();
+
+    // the retry gets the first response and creates nothing
+    let response = post(&admin_client, "/currencies".to_owned(), &key, body.clone());
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let replayed: Value = response.json().unwrap();
+    assert_eq!(replayed, currency);
+    let response = admin_client.get(format!("{}/currencies?user_id={}", APP_HOST, user["user_id"])).send().unwrap();
+    let json: Value = response.json().unwrap();
+    assert_eq!(json["items"].as_array().unwrap().len(), 1);
+
+    // the key can't be reused for another body or another route
+    let response = post(&admin_client, "/currencies".to_owned(), &key, json!({ "user_id":user["user_id"], "currency_type":"gold", "amount":1 }));
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let response = post(&admin_client, "/throphies".to_owned(), &key, json!({ "user_id":user["user_id"], "points":10 }));
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    // a malformed key is rejected
+    let response = post(&admin_client, "/throphies".to_owned(), &"x".repeat(256), json!({ "user_id":user["user_id"], "points":10 }));
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // clean up
+    delete_test_user(&admin_client, user);
+}
+
+#[test]
+fn test_idempotent_balance_change_applies_once() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let credit = format!("/users/{}/currency/credit", user["user_id"]);
+    let debit = format!("/users/{}/currency/debit", user["user_id"]);
+
+    // test
+    let key = new_key("credit");
+    for _ in 0..3 {
+        let response = post(&admin_client, credit.clone(), &key, json!({ "currency_type":"gold", "amount":100 }));
+        assert_eq!(response.status(), StatusCode::OK);
+        let wallet: Value = response.json().unwrap();
+        assert_eq!(wallet["amount"], 100);
+    }
+
+    // a failed request doesn't keep its key
+    let key = new_key("debit");
+    let response = post(&admin_client, debit.clone(), &key, json!({ "currency_type":"gold", "amount":150 }));
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let response = post(&admin_client, credit.clone(), &new_key("credit"), json!({ "currency_type":"gold", "amount":100 }));
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = post(&admin_client, debit.clone(), &key, json!({ "currency_type":"gold", "amount":150 }));
+    assert_eq!(response.status(), StatusCode::OK);
+    let wallet: Value = response.json().unwrap();
+    assert_eq!(wallet["amount"], 50);
+
+    // without a key every request counts
+    for _ in 0..2 {
+        let response = admin_client.post(format!("{}{}", APP_HOST, credit))
+            .json(&json!({ "currency_type":"gold", "amount":10 }))
+            .send()
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+    let response = admin_client.get(format!("{}/users/{}/currency-transactions", APP_HOST, user["user_id"])).send().unwrap();
+    let json: Value = response.json().unwrap();
+    assert_eq!(json["items"].as_array().unwrap().len(), 5);
+
+    // clean up
+    delete_test_user(&admin_client, user);
+}
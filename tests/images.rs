@@ -56,8 +56,8 @@ fn test_get_images() {
     let response = client.get(format!("{}/images", APP_HOST)).send().unwrap();
     assert_eq!(response.status(), StatusCode::OK);
     let json: Value = response.json().unwrap();
-    assert!(json.as_array().unwrap().contains(&image1));
-    assert!(json.as_array().unwrap().contains(&image2));
+    assert!(json["items"].as_array().unwrap().contains(&image1));
+    assert!(json["items"].as_array().unwrap().contains(&image2));
 
     // clean up
     delete_test_image(&client, image1);
@@ -142,6 +142,42 @@ fn test_update_images() {
 
 }
 
+#[test]
+fn test_patch_images() {
+    // setup
+    let client = common::get_client_with_logged_in_admin();
+    let image: Value = create_test_image(&client);
+    let url = format!("{}/images/{}", APP_HOST, image["image_id"]);
+
+    // test, null clears the description and the url is left as it was
+    let response = common::merge_patch(&client, url.clone(), json!({"description":null}));
+    assert_eq!(response.status(), StatusCode::OK);
+    let patched: Value = response.json().unwrap();
+    assert_eq!(patched, json!({
+        "image_id": image["image_id"],
+        "image_url":"https://www.google.com",
+        "description":null,
+        "upload_date": image["upload_date"]
+    }));
+    // image_url is not nullable
+    let response = common::merge_patch(&client, url.clone(), json!({"image_url":null}));
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    // an empty patch changes nothing
+    let response = common::merge_patch(&client, url.clone(), json!({}));
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value = response.json().unwrap();
+    assert_eq!(json, patched);
+    // plain json is not a merge patch
+    let response = client.patch(url)
+        .json(&json!({"description":"hello"}))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    // clean up
+    delete_test_image(&client, image);
+}
+
 #[test]
 fn test_delete_images() {
     let client = common::get_client_with_logged_in_admin();
@@ -0,0 +1,99 @@
+use reqwest::{blocking::Client, StatusCode};
+use rocket::serde::json::serde_json::json;
+use serde_json::Value;
+
+mod common;
+use common::{create_test_user, delete_test_user, merge_patch, APP_HOST};
+
+#[test]
+fn test_endpont_protected() {
+    let client = Client::new();
+    let response = client.get(format!("{}/me", APP_HOST)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn test_get_me() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let client = common::get_client_with_logged_in_user(&user);
+
+    // test
+    let response = client.get(format!("{}/me", APP_HOST)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let me: Value = response.json().unwrap();
+    assert_eq!(me["user_id"], user["user_id"]);
+    assert_eq!(me["email"], user["email"]);
+    assert!(me.get("password_hash").is_none());
+
+    // clean up
+    delete_test_user(&admin_client, user);
+}
+
+#[test]
+fn test_update_me() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let client = common::get_client_with_logged_in_user(&user);
+
+    // test
+    let response = merge_patch(&client, format!("{}/me", APP_HOST), json!({
+        "full_name":"Patched User",
+        "timezone":"Europe/Stockholm"
+    }));
+    assert_eq!(response.status(), StatusCode::OK);
+    let me: Value = response.json().unwrap();
+    assert_eq!(me["full_name"], "Patched User");
+    assert_eq!(me["timezone"], "Europe/Stockholm");
+    // fields that were left out are untouched
+    assert_eq!(me["country"], user["country"]);
+    assert_eq!(me["username"], user["username"]);
+    // null clears a nullable column
+    let response = merge_patch(&client, format!("{}/me", APP_HOST), json!({"timezone":null, "country":null}));
+    assert_eq!(response.status(), StatusCode::OK);
+    let me: Value = response.json().unwrap();
+    assert_eq!(me["timezone"], Value::Null);
+    assert_eq!(me["country"], Value::Null);
+    assert_eq!(me["full_name"], "Patched User");
+    // an empty patch changes nothing
+    let response = merge_patch(&client, format!("{}/me", APP_HOST), json!({}));
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value = response.json().unwrap();
+    assert_eq!(json, me);
+    // but not a required one
+    let response = merge_patch(&client, format!("{}/me", APP_HOST), json!({"full_name":null}));
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    // only merge patch documents are taken
+    let response = client.patch(format!("{}/me", APP_HOST)).json(&json!({"timezone":null})).send().unwrap();
+    assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    // clean up
+    delete_test_user(&admin_client, user);
+}
+
+#[test]
+fn test_update_me_rejects_other_fields() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let client = common::get_client_with_logged_in_user(&user);
+
+    // test
+    for body in [
+        json!({"password_hash":"not a hash"}),
+        json!({"registration_date":"2020-01-01T00:00:00"}),
+        json!({"is_admin":true}),
+    ] {
+        let response = merge_patch(&client, format!("{}/me", APP_HOST), body);
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+    let response = merge_patch(&client, format!("{}/me", APP_HOST), json!({"full_name":"   "}));
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let response = merge_patch(&client, format!("{}/me", APP_HOST), json!({"avatar_id":999999}));
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    // clean up
+    delete_test_user(&admin_client, user);
+}
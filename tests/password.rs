@@ -0,0 +1,178 @@
+use reqwest::{blocking::Client, StatusCode};
+use rocket::serde::json::serde_json::json;
+use serde_json::Value;
+
+mod common;
+use common::{create_test_user, delete_test_user, APP_HOST};
+
+fn login_status(username: &Value, password: &str) -> StatusCode {
+    Client::new().post(format!("{}/login", APP_HOST))
+        .json(&json!({
+            "username":username,
+            "password":password
+        }))
+        .send()
+        .unwrap()
+        .status()
+}
+
+// reset requests are counted per address for an hour, every run needs a new one
+fn unique_email(name: &str) -> String {
+    format!("{}{}@gmail.com", name, chrono::Utc::now().timestamp_nanos_opt().unwrap())
+}
+
+fn forgot_password(email: &Value) -> reqwest::blocking::Response {
+    Client::new().post(format!("{}/password/forgot", APP_HOST))
+        .json(&json!({
+            "email":email
+        }))
+        .send()
+        .unwrap()
+}
+
+#[test]
+fn test_change_password() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let client = common::get_client_with_logged_in_user(&user);
+    let other_device = common::get_client_with_logged_in_user(&user);
+
+    // test
+    let response = client.post(format!("{}/password/change", APP_HOST))
+        .json(&json!({
+            "old_password":"wrongpassword",
+            "new_password":"newtestpassword"
+        }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = client.post(format!("{}/password/change", APP_HOST))
+        .json(&json!({
+            "old_password":"testpassword",
+            "new_password":"short"
+        }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let response = client.post(format!("{}/password/change", APP_HOST))
+        .json(&json!({
+            "old_password":"testpassword",
+            "new_password":"newtestpassword"
+        }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value = response.json().unwrap();
+    assert!(json.get("token").is_some());
+
+    // every earlier session is gone
+    let response = other_device.get(format!("{}/users", APP_HOST)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(login_status(&user["username"], "testpassword"), StatusCode::UNAUTHORIZED);
+    assert_eq!(login_status(&user["username"], "newtestpassword"), StatusCode::OK);
+
+    // clean up
+    delete_test_user(&admin_client, user);
+}
+
+#[test]
+fn test_change_password_is_throttled() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let client = common::get_client_with_logged_in_user(&user);
+    let change = |old_password: &str| client.post(format!("{}/password/change", APP_HOST))
+        .json(&json!({
+            "old_password":old_password,
+            "new_password":"newtestpassword"
+        }))
+        .send()
+        .unwrap();
+
+    // test, wrong old passwords share the lockout of /login
+    for _ in 0..5 {
+        assert_eq!(change("wrongpassword").status(), StatusCode::FORBIDDEN);
+    }
+    let response = change("testpassword");
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers().get("Retry-After").is_some());
+    assert_eq!(login_status(&user["username"], "testpassword"), StatusCode::TOO_MANY_REQUESTS);
+
+    // clean up
+    delete_test_user(&admin_client, user);
+}
+
+#[test]
+fn test_reset_password() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, &unique_email("testreset"));
+    let client = common::get_client_with_logged_in_user(&user);
+    let email = user["email"].as_str().unwrap();
+
+    // test
+    let response = forgot_password(&user["email"]);
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    let first_token = common::token_from_mail(&common::latest_mail(email));
+    let response = forgot_password(&user["email"]);
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    let token = common::token_from_mail(&common::latest_mail(email));
+
+    // only the newest token works
+    let reset = |token: &str| json!({
+        "token":token,
+        "new_password":"resettestpassword"
+    });
+    let response = Client::new().post(format!("{}/password/reset", APP_HOST)).json(&reset(&first_token)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = Client::new().post(format!("{}/password/reset", APP_HOST)).json(&reset(&token)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    // tokens are single use
+    let response = Client::new().post(format!("{}/password/reset", APP_HOST)).json(&reset(&token)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = client.get(format!("{}/users", APP_HOST)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(login_status(&user["username"], "resettestpassword"), StatusCode::OK);
+
+    // clean up
+    delete_test_user(&admin_client, user);
+}
+
+#[test]
+fn test_forgot_password_is_limited_per_address() {
+    // known or not, an address gets three mails an hour
+    let email = json!(unique_email("nobody"));
+    for _ in 0..3 {
+        assert_eq!(forgot_password(&email).status(), StatusCode::ACCEPTED);
+    }
+    let response = forgot_password(&email);
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers().get("Retry-After").is_some());
+}
+
+#[test]
+fn test_update_user_keeps_password() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+
+    // test
+    let response = admin_client.put(format!("{}/users/{}", APP_HOST, user["user_id"]))
+        .json(&json!({
+            "username":user["username"],
+            "email":user["email"],
+            "password_hash":"not a hash",
+            "full_name":"Test User",
+            "country":"USA",
+            "date_of_birth":"1990-01-01"
+        }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(login_status(&user["username"], "testpassword"), StatusCode::OK);
+
+    // clean up
+    delete_test_user(&admin_client, user);
+}
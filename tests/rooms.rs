@@ -0,0 +1,198 @@
+use reqwest::{blocking::Client, StatusCode};
+use rocket::serde::json::serde_json::json;
+use serde_json::Value;
+
+mod common;
+use common::{create_test_user, delete_test_user, APP_HOST};
+
+fn create_test_room(client: &Client) -> Value {
+    let response = client.post(format!("{}/rooms", APP_HOST))
+        .json(&json!({ "name":"squad" }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    response.json().unwrap()
+}
+
+fn delete_test_room(client: &Client, room: Value) {
+    let response = client.delete(format!("{}/rooms/{}", APP_HOST, room["room_id"]))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+}
+
+fn join(client: &Client, room: &Value) -> StatusCode {
+    client.post(format!("{}/rooms/{}/join", APP_HOST, room["room_id"])).send().unwrap().status()
+}
+
+fn member_ids(client: &Client, room: &Value) -> Vec<Value> {
+    let response = client.get(format!("{}/rooms/{}/members?sort=id", APP_HOST, room["room_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value = response.json().unwrap();
+    json["items"].as_array().unwrap().iter().map(|member| member["user_id"].clone()).collect()
+}
+
+#[test]
+fn test_endpoints_protected() {
+    let client = Client::new();
+    let response = client.get(format!("{}/rooms", APP_HOST)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = client.post(format!("{}/rooms", APP_HOST)).json(&json!({ "name":"squad" })).send().unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn test_rooms() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let owner: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let member: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let owner_client = common::get_client_with_logged_in_user(&owner);
+    let member_client = common::get_client_with_logged_in_user(&member);
+
+    // test
+    let room = create_test_room(&owner_client);
+    assert_eq!(room["name"], "squad");
+    let response = owner_client.post(format!("{}/rooms", APP_HOST)).json(&json!({ "name":" " })).send().unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    // the creator owns the room
+    let response = owner_client.get(format!("{}/rooms/{}/members", APP_HOST, room["room_id"])).send().unwrap();
+    let json: Value = response.json().unwrap();
+    assert_eq!(json["items"][0]["user_id"], owner["user_id"]);
+    assert_eq!(json["items"][0]["role"], "owner");
+    // rooms are listed for their members only
+    let response = owner_client.get(format!("{}/rooms", APP_HOST)).send().unwrap();
+    let json: Value = response.json().unwrap();
+    assert_eq!(json["items"], json!([room]));
+    let response = member_client.get(format!("{}/rooms", APP_HOST)).send().unwrap();
+    let json: Value = response.json().unwrap();
+    assert_eq!(json["items"], json!([]));
+    let response = member_client.get(format!("{}/rooms/{}", APP_HOST, room["room_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // only the owner renames
+    assert_eq!(join(&member_client, &room), StatusCode::CREATED);
+    let response = common::merge_patch(&member_client, format!("{}/rooms/{}", APP_HOST, room["room_id"]), json!({ "name":"mine" }));
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = common::merge_patch(&owner_client, format!("{}/rooms/{}", APP_HOST, room["room_id"]), json!({ "name":"clan" }));
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value = response.json().unwrap();
+    assert_eq!(json["name"], "clan");
+
+    // and deletes
+    let response = member_client.delete(format!("{}/rooms/{}", APP_HOST, room["room_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    delete_test_room(&owner_client, room.clone());
+    let response = owner_client.get(format!("{}/rooms/{}", APP_HOST, room["room_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // clean up
+    delete_test_user(&admin_client, owner);
+    delete_test_user(&admin_client, member);
+}
+
+#[test]
+fn test_room_membership() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let owner: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let member: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let other: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let owner_client = common::get_client_with_logged_in_user(&owner);
+    let member_client = common::get_client_with_logged_in_user(&member);
+    let other_client = common::get_client_with_logged_in_user(&other);
+    let room = create_test_room(&owner_client);
+
+    // test
+    assert_eq!(join(&member_client, &room), StatusCode::CREATED);
+    assert_eq!(join(&member_client, &room), StatusCode::CONFLICT);
+    assert_eq!(join(&other_client, &room), StatusCode::CREATED);
+    let response = member_client.post(format!("{}/rooms/{}/join", APP_HOST, i32::MAX)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(member_ids(&member_client, &room), [owner["user_id"].clone(), member["user_id"].clone(), other["user_id"].clone()]);
+
+    // members can't kick, the owner can't be kicked and can't leave
+    let response = member_client.delete(format!("{}/rooms/{}/members/{}", APP_HOST, room["room_id"], other["user_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = owner_client.delete(format!("{}/rooms/{}/members/{}", APP_HOST, room["room_id"], owner["user_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let response = owner_client.post(format!("{}/rooms/{}/leave", APP_HOST, room["room_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    // the owner kicks, members leave
+    let response = owner_client.delete(format!("{}/rooms/{}/members/{}", APP_HOST, room["room_id"], other["user_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = owner_client.delete(format!("{}/rooms/{}/members/{}", APP_HOST, room["room_id"], other["user_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = member_client.post(format!("{}/rooms/{}/leave", APP_HOST, room["room_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = member_client.post(format!("{}/rooms/{}/leave", APP_HOST, room["room_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(member_ids(&owner_client, &room), [owner["user_id"].clone()]);
+    let response = other_client.get(format!("{}/rooms/{}/members", APP_HOST, room["room_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // a kicked member can't come back until the owner lifts the ban, leaving is no ban
+    assert_eq!(join(&other_client, &room), StatusCode::FORBIDDEN);
+    assert_eq!(join(&member_client, &room), StatusCode::CREATED);
+    let response = member_client.delete(format!("{}/rooms/{}/bans/{}", APP_HOST, room["room_id"], other["user_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = owner_client.delete(format!("{}/rooms/{}/bans/{}", APP_HOST, room["room_id"], other["user_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = owner_client.delete(format!("{}/rooms/{}/bans/{}", APP_HOST, room["room_id"], other["user_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(join(&other_client, &room), StatusCode::CREATED);
+
+    // clean up
+    delete_test_room(&owner_client, room);
+    delete_test_user(&admin_client, owner);
+    delete_test_user(&admin_client, member);
+    delete_test_user(&admin_client, other);
+}
+
+#[test]
+fn test_room_messages() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let owner: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let member: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let stranger: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let owner_client = common::get_client_with_logged_in_user(&owner);
+    let member_client = common::get_client_with_logged_in_user(&member);
+    let stranger_client = common::get_client_with_logged_in_user(&stranger);
+    let room = create_test_room(&owner_client);
+    assert_eq!(join(&member_client, &room), StatusCode::CREATED);
+    let messages_url = format!("{}/rooms/{}/messages", APP_HOST, room["room_id"]);
+
+    // test
+    // the sender is always the caller
+    let response = member_client.post(&messages_url)
+        .json(&json!({ "message":"gg", "sender_id":owner["user_id"] }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let message: Value = response.json().unwrap();
+    assert_eq!(message["sender_id"], member["user_id"]);
+    assert_eq!(message["room_id"], room["room_id"]);
+    let response = member_client.post(&messages_url).json(&json!({ "message":"" })).send().unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    // only members read and write
+    let response = stranger_client.post(&messages_url).json(&json!({ "message":"hi" })).send().unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = stranger_client.get(&messages_url).send().unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = owner_client.get(&messages_url).send().unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value = response.json().unwrap();
+    assert_eq!(json["items"], json!([message]));
+
+    // deleting the owner deletes their rooms
+    delete_test_user(&admin_client, owner);
+    let response = admin_client.get(format!("{}/rooms/{}", APP_HOST, room["room_id"])).send().unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // clean up
+    delete_test_user(&admin_client, member);
+    delete_test_user(&admin_client, stranger);
+}
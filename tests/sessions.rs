@@ -0,0 +1,173 @@
+use reqwest::{blocking::Client, header, StatusCode};
+use serde_json::{json, Value};
+
+mod common;
+use common::{create_test_user, delete_test_user, get_client_with_logged_in_user, login_json, APP_HOST};
+
+/*
+    Side note: every test logs in as a freshly created user,
+    so revoking sessions here never touches the shared admin session
+*/
+
+fn assert_logged_in(client: &Client, user: &Value, expected: StatusCode) {
+    let response = client.get(format!("{}/users/{}", APP_HOST, user["user_id"]))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), expected);
+}
+
+fn refresh(refresh_token: &Value) -> reqwest::blocking::Response {
+    Client::new().post(format!("{}/token/refresh", APP_HOST))
+        .json(&json!({
+            "refresh_token":refresh_token
+        }))
+        .send()
+        .unwrap()
+}
+
+fn client_with_token(token: &Value) -> Client {
+    let mut headers = header::HeaderMap::new();
+    headers.insert(
+        header::AUTHORIZATION,
+        header::HeaderValue::from_str(&format!("Bearer {}", token.as_str().unwrap())).unwrap()
+    );
+    Client::builder().default_headers(headers).build().unwrap()
+}
+
+#[test]
+fn test_logout_requires_session() {
+    let client = Client::new();
+    let response = client.post(format!("{}/logout", APP_HOST)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn test_logout() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let client = get_client_with_logged_in_user(&user);
+    let other_device = get_client_with_logged_in_user(&user);
+    assert_logged_in(&client, &user, StatusCode::OK);
+
+    // test
+    let response = client.post(format!("{}/logout", APP_HOST)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert_logged_in(&client, &user, StatusCode::UNAUTHORIZED);
+    // only the current session ends
+    assert_logged_in(&other_device, &user, StatusCode::OK);
+
+    // clean up
+    delete_test_user(&admin_client, user);
+}
+
+#[test]
+fn test_logout_all() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let client = get_client_with_logged_in_user(&user);
+    let other_device = get_client_with_logged_in_user(&user);
+
+    // test
+    let response = client.post(format!("{}/logout/all", APP_HOST)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert_logged_in(&client, &user, StatusCode::UNAUTHORIZED);
+    assert_logged_in(&other_device, &user, StatusCode::UNAUTHORIZED);
+
+    // clean up
+    delete_test_user(&admin_client, user);
+}
+
+#[test]
+fn test_admin_revokes_sessions() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let other: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let client = get_client_with_logged_in_user(&user);
+    let other_client = get_client_with_logged_in_user(&other);
+
+    // regular users can not log out someone else
+    let response = other_client.delete(format!("{}/users/{}/sessions", APP_HOST, user["user_id"]))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    assert_logged_in(&client, &user, StatusCode::OK);
+
+    // admins can
+    let response = admin_client.delete(format!("{}/users/{}/sessions", APP_HOST, user["user_id"]))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert_logged_in(&client, &user, StatusCode::UNAUTHORIZED);
+    assert_logged_in(&other_client, &other, StatusCode::OK);
+
+    // clean up
+    delete_test_user(&admin_client, user);
+    delete_test_user(&admin_client, other);
+}
+
+#[test]
+fn test_refresh_token() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let tokens = login_json(&user);
+    assert_eq!(tokens["refresh_token"].as_str().unwrap().len(), 128);
+
+    // test
+    let response = refresh(&tokens["refresh_token"]);
+    assert_eq!(response.status(), StatusCode::OK);
+    let refreshed: Value = response.json().unwrap();
+    assert_ne!(refreshed["token"], tokens["token"]);
+    assert_ne!(refreshed["refresh_token"], tokens["refresh_token"]);
+    assert_logged_in(&client_with_token(&refreshed["token"]), &user, StatusCode::OK);
+    // the old session was replaced
+    assert_logged_in(&client_with_token(&tokens["token"]), &user, StatusCode::UNAUTHORIZED);
+
+    // refresh tokens are single use
+    let response = refresh(&tokens["refresh_token"]);
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // clean up
+    delete_test_user(&admin_client, user);
+}
+
+#[test]
+fn test_logout_invalidates_refresh_token() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let tokens = login_json(&user);
+    let client = client_with_token(&tokens["token"]);
+
+    // test
+    let response = client.post(format!("{}/logout", APP_HOST)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = refresh(&tokens["refresh_token"]);
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // clean up
+    delete_test_user(&admin_client, user);
+}
+
+#[test]
+fn test_logout_all_invalidates_refresh_tokens() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let tokens = login_json(&user);
+    let other_device = login_json(&user);
+
+    // test
+    let response = client_with_token(&tokens["token"]).post(format!("{}/logout/all", APP_HOST))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(refresh(&tokens["refresh_token"]).status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(refresh(&other_device["refresh_token"]).status(), StatusCode::UNAUTHORIZED);
+
+    // clean up
+    delete_test_user(&admin_client, user);
+}
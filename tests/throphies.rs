@@ -3,7 +3,7 @@ use rocket::serde::json::serde_json::json;
 use serde_json::Value;
 
 mod common;
-use common::{create_test_user, delete_test_user, APP_HOST};
+use common::{create_test_user, delete_test_user, merge_patch, APP_HOST};
 
 
 /*          Date: 2024-02-21
@@ -58,8 +58,8 @@ fn test_get_throphies() {
     let response = client.get(format!("{}/throphies", APP_HOST)).send().unwrap();
     assert_eq!(response.status(), StatusCode::OK);
     let json: Value = response.json().unwrap();
-    assert!(json.as_array().unwrap().contains(&throphies1));
-    assert!(json.as_array().unwrap().contains(&throphies2));
+    assert!(json["items"].as_array().unwrap().contains(&throphies1));
+    assert!(json["items"].as_array().unwrap().contains(&throphies2));
 
     // clean up
     delete_test_throphies(&client, throphies1);
@@ -164,4 +164,65 @@ fn test_delete_throphies() {
     assert_eq!(response.status(), StatusCode::NO_CONTENT);
     // cleans up itself
     delete_test_user(&client, user);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_negative_points_rejected() {
+    // setup
+    let client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&client, "testuser@gmail.com");
+    let throphies: Value = create_test_throphies(&client, user["user_id"].as_i64().unwrap());
+
+    // test
+    let response = client.post(format!("{}/throphies", APP_HOST))
+        .json(&json!({
+            "user_id":user["user_id"],
+            "points":-1
+        }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let json: Value = response.json().unwrap();
+    assert_eq!(json["details"]["points"][0]["code"], "range");
+    let response = client.put(format!("{}/throphies/{}", APP_HOST, throphies["trophy_id"]))
+        .json(&json!({
+            "user_id":user["user_id"],
+            "points":-1
+        }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let response = merge_patch(&client, format!("{}/throphies/{}", APP_HOST, throphies["trophy_id"]), json!({ "points":-1 }));
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    // clean up
+    delete_test_throphies(&client, throphies);
+    delete_test_user(&client, user);
+}
+
+#[test]
+fn test_non_admin_cannot_change_throphies() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let throphies: Value = create_test_throphies(&admin_client, user["user_id"].as_i64().unwrap());
+    let client = common::get_client_with_logged_in_user(&user);
+
+    // test
+    let response = client.post(format!("{}/throphies", APP_HOST))
+        .json(&json!({
+            "user_id":user["user_id"],
+            "points":1000000
+        }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = client.delete(format!("{}/throphies/{}", APP_HOST, throphies["trophy_id"]))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // clean up
+    delete_test_throphies(&admin_client, throphies);
+    delete_test_user(&admin_client, user);
+}
@@ -59,8 +59,8 @@ fn test_get_total_throphies() {
     let response = client.get(format!("{}/total_throphies", APP_HOST)).send().unwrap();
     assert_eq!(response.status(), StatusCode::OK);
     let json: Value = response.json().unwrap();
-    assert!(json.as_array().unwrap().contains(&total_throphies1));
-    assert!(json.as_array().unwrap().contains(&total_throphies2));
+    assert!(json["items"].as_array().unwrap().contains(&total_throphies1));
+    assert!(json["items"].as_array().unwrap().contains(&total_throphies2));
 
     // clean up
     delete_test_total_throphies(&client, total_throphies1);
@@ -0,0 +1,195 @@
+use reqwest::{blocking::Client, StatusCode};
+use serde_json::{json, Value};
+use totp_rs::{Algorithm, Secret, TOTP};
+
+mod common;
+use common::{create_test_user, delete_test_user, get_client_with_logged_in_user, login_json, APP_HOST};
+
+fn current_code(secret: &Value) -> String {
+    let secret = Secret::Encoded(secret.as_str().unwrap().to_owned()).to_bytes().unwrap();
+    TOTP::new(Algorithm::SHA1, 6, 1, 30, secret, Some("BattleGear".to_owned()), "account".to_owned())
+        .unwrap()
+        .generate_current()
+        .unwrap()
+}
+
+fn login_two_factor(challenge: &Value, code: &str) -> reqwest::blocking::Response {
+    Client::new().post(format!("{}/login/2fa", APP_HOST))
+        .json(&json!({
+            "challenge":challenge,
+            "code":code
+        }))
+        .send()
+        .unwrap()
+}
+
+// enrolls the user and returns the secret and recovery codes
+fn enable_two_factor(client: &Client) -> (Value, Vec<String>) {
+    let response = client.post(format!("{}/2fa/enroll", APP_HOST)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let enrollment: Value = response.json().unwrap();
+    assert!(enrollment["provisioning_uri"].as_str().unwrap().starts_with("otpauth://totp/BattleGear:"));
+
+    let response = client.post(format!("{}/2fa/confirm", APP_HOST))
+        .json(&json!({ "code": current_code(&enrollment["secret"]) }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value = response.json().unwrap();
+    let recovery_codes = json["recovery_codes"].as_array().unwrap().iter()
+        .map(|code| code.as_str().unwrap().to_owned())
+        .collect();
+    (enrollment["secret"].clone(), recovery_codes)
+}
+
+#[test]
+fn test_enroll_requires_session() {
+    let response = Client::new().post(format!("{}/2fa/enroll", APP_HOST)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[test]
+fn test_confirm_rejects_wrong_code() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let client = get_client_with_logged_in_user(&user);
+
+    // test
+    let response = client.post(format!("{}/2fa/enroll", APP_HOST)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = client.post(format!("{}/2fa/confirm", APP_HOST))
+        .json(&json!({ "code": "000000" }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    // still a one step login
+    assert!(login_json(&user).get("token").is_some());
+
+    // clean up
+    delete_test_user(&admin_client, user);
+}
+
+#[test]
+fn test_two_step_login() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let client = get_client_with_logged_in_user(&user);
+    let (secret, recovery_codes) = enable_two_factor(&client);
+    assert_eq!(recovery_codes.len(), 10);
+
+    let response = client.get(format!("{}/users/{}", APP_HOST, user["user_id"])).send().unwrap();
+    let json: Value = response.json().unwrap();
+    assert_eq!(json["two_factor_auth_enabled"], true);
+
+    // step one no longer hands out a session
+    let step_one = login_json(&user);
+    assert_eq!(step_one["two_factor_required"], true);
+    assert!(step_one.get("token").is_none());
+
+    // wrong code
+    let response = login_two_factor(&step_one["challenge"], "000000");
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // right code
+    let code = current_code(&secret);
+    let response = login_two_factor(&step_one["challenge"], &code);
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value = response.json().unwrap();
+    assert_eq!(json["token"].as_str().unwrap().len(), 128);
+
+    // challenges are single use
+    let response = login_two_factor(&step_one["challenge"], &code);
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // codes can not be replayed on a new challenge
+    let step_one = login_json(&user);
+    let response = login_two_factor(&step_one["challenge"], &code);
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // clean up
+    delete_test_user(&admin_client, user);
+}
+
+#[test]
+fn test_wrong_codes_are_counted_per_user() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let client = get_client_with_logged_in_user(&user);
+    let (secret, _) = enable_two_factor(&client);
+
+    // test
+    // a challenge takes five wrong codes, a new one doesn't start the count over
+    for _ in 0..2 {
+        let step_one = login_json(&user);
+        for _ in 0..5 {
+            let response = login_two_factor(&step_one["challenge"], "000000");
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+        let response = login_two_factor(&step_one["challenge"], &current_code(&secret));
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+    let step_one = login_json(&user);
+    let response = login_two_factor(&step_one["challenge"], &current_code(&secret));
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers().get("Retry-After").is_some());
+
+    // clean up
+    delete_test_user(&admin_client, user);
+}
+
+#[test]
+fn test_recovery_codes_are_single_use() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let client = get_client_with_logged_in_user(&user);
+    let (_, recovery_codes) = enable_two_factor(&client);
+
+    // test
+    let step_one = login_json(&user);
+    let response = login_two_factor(&step_one["challenge"], &recovery_codes[0]);
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let step_one = login_json(&user);
+    let response = login_two_factor(&step_one["challenge"], &recovery_codes[0]);
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = login_two_factor(&step_one["challenge"], &recovery_codes[1]);
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = client.get(format!("{}/2fa", APP_HOST)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value = response.json().unwrap();
+    assert_eq!(json["enabled"], true);
+    assert_eq!(json["recovery_codes_remaining"], 8);
+
+    // clean up
+    delete_test_user(&admin_client, user);
+}
+
+#[test]
+fn test_disable_two_factor() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let client = get_client_with_logged_in_user(&user);
+    let (_, recovery_codes) = enable_two_factor(&client);
+
+    // test
+    let response = client.post(format!("{}/2fa/disable", APP_HOST))
+        .json(&json!({ "code": "000000" }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let response = client.post(format!("{}/2fa/disable", APP_HOST))
+        .json(&json!({ "code": recovery_codes[0] }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert!(login_json(&user).get("token").is_some());
+
+    // clean up
+    delete_test_user(&admin_client, user);
+}
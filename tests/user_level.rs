@@ -58,8 +58,8 @@ fn test_get_user_levels() {
     let response = client.get(format!("{}/user_levels", APP_HOST)).send().unwrap();
     assert_eq!(response.status(), StatusCode::OK);
     let json: Value = response.json().unwrap();
-    assert!(json.as_array().unwrap().contains(&user_level1));
-    assert!(json.as_array().unwrap().contains(&user_level2));
+    assert!(json["items"].as_array().unwrap().contains(&user_level1));
+    assert!(json["items"].as_array().unwrap().contains(&user_level2));
 
     // clean up
     delete_test_user_level(&client, user_level1);
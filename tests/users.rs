@@ -29,8 +29,9 @@ fn test_get_users() {
     let response = client.get(format!("{}/users", APP_HOST)).send().unwrap();
     assert_eq!(response.status(), StatusCode::OK);
     let json: Value = response.json().unwrap();
-    assert!(json.as_array().unwrap().contains(&user1));
-    assert!(json.as_array().unwrap().contains(&user2));
+    let users = json["items"].as_array().unwrap();
+    assert!(users.iter().any(|u| u["user_id"] == user1["user_id"] && u["email"] == user1["email"]));
+    assert!(users.iter().any(|u| u["user_id"] == user2["user_id"] && u["email"] == user2["email"]));
 
     // clean up
     delete_test_user(&client, user1);
@@ -53,7 +54,6 @@ fn test_view_user() {
         "user_id": user["user_id"],
         "username": user["username"],
         "email":user["email"],
-        "password_hash":"testpassword",
         "full_name":"Test User",
         "avatar_id": user["avatar_id"],
         "registration_date": user["registration_date"],
@@ -65,7 +65,8 @@ fn test_view_user() {
         "country":"USA",
         "date_of_birth":"1990-01-01",
         "two_factor_auth_enabled": user["two_factor_auth_enabled"],
-        "last_password_change": user["last_password_change"]
+        "last_password_change": user["last_password_change"],
+        "email_verified_at": user["email_verified_at"]
     }));
 
     // clean up
@@ -81,7 +82,7 @@ fn test_create_users() {
         .json(&json!({
             "username":"testuser123",
             "email":"testuser444@gmail.com",
-            "password_hash":"testpassword",
+            "password":"testpassword",
             "full_name":"Test User",
             "country":"USA",
             "date_of_birth":"1990-01-01"
@@ -95,25 +96,96 @@ fn test_create_users() {
         "user_id": user["user_id"],
         "username":"testuser123",
         "email":user["email"],
-        "password_hash":"testpassword",
         "full_name":"Test User",
         "avatar_id": user["avatar_id"],
         "registration_date": user["registration_date"],
         "last_login": user["last_login"],
-        "is_active": user["is_active"],
-        "is_admin": user["is_admin"],
         "timezone": user["timezone"],
         "language": user["language"],
         "country":"USA",
         "date_of_birth":"1990-01-01",
         "two_factor_auth_enabled": user["two_factor_auth_enabled"],
-        "last_password_change": user["last_password_change"]
+        "last_password_change": user["last_password_change"],
+        "email_verified_at": null
     }));
 
     // clean up
     delete_test_user(&client, user);
 }
 
+#[test]
+fn test_create_user_rejects_password_hash() {
+    let client = Client::new();
+    let response = client.post(format!("{}/users", APP_HOST))
+        .json(&json!({
+            "username":"testuserhash",
+            "email":"testuserhash@gmail.com",
+            "password":"testpassword",
+            "password_hash":"$argon2id$v=19$m=19456,t=2,p=1$c2FsdA$aGFzaA",
+            "full_name":"Test User",
+            "country":"USA",
+            "date_of_birth":"1990-01-01"
+        }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+
+    // a hash without a password is rejected as well
+    let response = client.post(format!("{}/users", APP_HOST))
+        .json(&json!({
+            "username":"testuserhash",
+            "email":"testuserhash@gmail.com",
+            "password_hash":"testpassword",
+            "full_name":"Test User",
+            "country":"USA",
+            "date_of_birth":"1990-01-01"
+        }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[test]
+fn test_create_user_rejects_short_password() {
+    let client = Client::new();
+    let response = client.post(format!("{}/users", APP_HOST))
+        .json(&json!({
+            "username":"testusershort",
+            "email":"testusershort@gmail.com",
+            "password":"short",
+            "full_name":"Test User",
+            "country":"USA",
+            "date_of_birth":"1990-01-01"
+        }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[test]
+fn test_create_user_validation() {
+    let client = Client::new();
+    let response = client.post(format!("{}/users", APP_HOST))
+        .json(&json!({
+            "username":" ",
+            "email":"not an email",
+            "password":"short",
+            "full_name":"x".repeat(256),
+            "country":"USA",
+            "date_of_birth":"1990-01-01"
+        }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    // every broken rule is listed under its field
+    let json: Value = response.json().unwrap();
+    assert_eq!(json["code"], "unprocessable_entity");
+    assert_eq!(json["details"]["username"][0]["code"], "blank");
+    assert_eq!(json["details"]["email"][0]["code"], "email");
+    assert_eq!(json["details"]["password"][0]["code"], "password");
+    assert_eq!(json["details"]["full_name"][0]["message"], "must be between 1 and 255 characters");
+    assert!(json["details"].get("country").is_none());
+}
 
 #[test]
 fn test_update_user() {
@@ -139,7 +211,6 @@ fn test_update_user() {
         "user_id": user["user_id"],
         "username":"testuser222",
         "email":user["email"],
-        "password_hash":"test2password",
         "full_name":"Test2 User2",
         "avatar_id": user["avatar_id"],
         "registration_date": user["registration_date"],
@@ -151,7 +222,8 @@ fn test_update_user() {
         "country":"USA2",
         "date_of_birth":"1990-01-01",
         "two_factor_auth_enabled": user["two_factor_auth_enabled"],
-        "last_password_change": user["last_password_change"]
+        "last_password_change": user["last_password_change"],
+        "email_verified_at": null
     }));
 
     // clean up
@@ -159,6 +231,134 @@ fn test_update_user() {
 
 }
 
+#[test]
+fn test_patch_user() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let other_user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let client = common::get_client_with_logged_in_user(&user);
+
+    // test, only the members that are sent change
+    let response = common::merge_patch(&client, format!("{}/users/{}", APP_HOST, user["user_id"]), json!({
+        "full_name":"Patched User",
+        "timezone":"Europe/Stockholm"
+    }));
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value = response.json().unwrap();
+    assert_eq!(json["full_name"], "Patched User");
+    assert_eq!(json["timezone"], "Europe/Stockholm");
+    assert_eq!(json["username"], user["username"]);
+    assert_eq!(json["country"], user["country"]);
+    // null clears a nullable column
+    let response = common::merge_patch(&client, format!("{}/users/{}", APP_HOST, user["user_id"]), json!({"timezone":null}));
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value = response.json().unwrap();
+    assert_eq!(json["timezone"], Value::Null);
+    assert_eq!(json["full_name"], "Patched User");
+    // unknown members and other users are rejected
+    let response = common::merge_patch(&client, format!("{}/users/{}", APP_HOST, user["user_id"]), json!({"is_admin":true}));
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    let response = common::merge_patch(&client, format!("{}/users/{}", APP_HOST, other_user["user_id"]), json!({"full_name":"Patched User"}));
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // sending the same address keeps it verified, a new one has to be verified again
+    let response = common::merge_patch(&client, format!("{}/users/{}", APP_HOST, user["user_id"]), json!({"email":user["email"]}));
+    let json: Value = response.json().unwrap();
+    assert!(!json["email_verified_at"].is_null());
+    let email = format!("patched{}@gmail.com", user["user_id"]);
+    let response = common::merge_patch(&client, format!("{}/users/{}", APP_HOST, user["user_id"]), json!({"email":email}));
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value = response.json().unwrap();
+    assert_eq!(json["email_verified_at"], Value::Null);
+    let response = Client::new().post(format!("{}/login", APP_HOST))
+        .json(&json!({ "username":user["username"], "password":"testpassword" }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    common::verify_email(&client, &email);
+    common::get_client_with_logged_in_user(&user);
+
+    // clean up
+    delete_test_user(&admin_client, user);
+    delete_test_user(&admin_client, other_user);
+}
+
+#[test]
+fn test_views_depend_on_caller() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let other: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let client = common::get_client_with_logged_in_user(&user);
+
+    // own profile includes private fields but not admin flags
+    let response = client.get(format!("{}/users/{}", APP_HOST, user["user_id"]))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value = response.json().unwrap();
+    assert_eq!(json["email"], user["email"]);
+    assert!(json.get("is_admin").is_none());
+
+    // other players only get the public profile
+    let response = client.get(format!("{}/users/{}", APP_HOST, other["user_id"]))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let json: Value = response.json().unwrap();
+    assert_eq!(json, json!({
+        "user_id": other["user_id"],
+        "username": other["username"],
+        "full_name": "Test User",
+        "avatar_id": other["avatar_id"],
+        "country": "USA",
+        "registration_date": other["registration_date"]
+    }));
+
+    // clean up
+    delete_test_user(&admin_client, user);
+    delete_test_user(&admin_client, other);
+}
+
+#[test]
+fn test_password_hash_never_exposed() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    assert!(user.get("password_hash").is_none());
+    let client = common::get_client_with_logged_in_user(&user);
+
+    let assert_no_hash = |response: reqwest::blocking::Response| {
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.text().unwrap();
+        assert!(!body.contains("password_hash"), "password_hash leaked: {}", body);
+        assert!(!body.contains("$argon2"), "argon2 hash leaked: {}", body);
+    };
+
+    // as admin
+    assert_no_hash(admin_client.get(format!("{}/users", APP_HOST)).send().unwrap());
+    assert_no_hash(admin_client.get(format!("{}/users/{}", APP_HOST, user["user_id"])).send().unwrap());
+    // as the user themselves
+    assert_no_hash(client.get(format!("{}/users", APP_HOST)).send().unwrap());
+    assert_no_hash(client.get(format!("{}/users/{}", APP_HOST, user["user_id"])).send().unwrap());
+    // after an update
+    assert_no_hash(admin_client.put(format!("{}/users/{}", APP_HOST, user["user_id"]))
+        .json(&json!({
+            "username":user["username"],
+            "email":user["email"],
+            "password_hash":"ignored",
+            "full_name":"Test User",
+            "country":"USA",
+            "date_of_birth":"1990-01-01"
+        }))
+        .send()
+        .unwrap());
+
+    // clean up
+    delete_test_user(&admin_client, user);
+}
+
 #[test]
 fn test_delete_user() {
     let client = common::get_client_with_logged_in_admin();
@@ -177,3 +377,83 @@ fn test_delete_user() {
 
 
 
+
+#[test]
+fn test_non_admin_cannot_delete_user() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let other_user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let client = common::get_client_with_logged_in_user(&user);
+
+    // test
+    let response = client.delete(format!("{}/users/{}", APP_HOST, other_user["user_id"]))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+    // clean up
+    delete_test_user(&admin_client, user);
+    delete_test_user(&admin_client, other_user);
+}
+
+#[test]
+fn test_user_can_only_update_themselves() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let other_user: Value = create_test_user(&admin_client, "testuser@gmail.com");
+    let client = common::get_client_with_logged_in_user(&user);
+    let update = |user: &Value| json!({
+        "username":user["username"],
+        "email":user["email"],
+        "full_name":"Updated User",
+        "country":"USA",
+        "date_of_birth":"1990-01-01",
+        "is_active":true,
+        "is_admin":true
+    });
+
+    // test
+    let response = client.put(format!("{}/users/{}", APP_HOST, other_user["user_id"]))
+        .json(&update(&other_user))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let response = client.put(format!("{}/users/{}", APP_HOST, user["user_id"]))
+        .json(&update(&user))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    // the admin flag is not something users can hand themselves
+    let response = admin_client.get(format!("{}/users/{}", APP_HOST, user["user_id"])).send().unwrap();
+    let json: Value = response.json().unwrap();
+    assert_eq!(json["full_name"], "Updated User");
+    assert_eq!(json["is_admin"], false);
+
+    // clean up
+    delete_test_user(&admin_client, user);
+    delete_test_user(&admin_client, other_user);
+}
+
+#[test]
+fn test_existence_checks_throttled_per_ip() {
+    // every run gets its own address, read from X-Real-IP with ROCKET_TRUST_IP_HEADER
+    let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().subsec_nanos();
+    let ip = format!("10.{}.{}.{}", (nanos >> 16) & 0xff, (nanos >> 8) & 0xff, nanos & 0xff);
+    let client = Client::new();
+    let lookup = |path: &str, value: &str| client.get(format!("{}/users/{}/{}", APP_HOST, path, value))
+        .header("X-Real-IP", &ip)
+        .send()
+        .unwrap();
+
+    // username and email checks share the limit
+    for i in 0..30 {
+        assert_eq!(lookup("username_exists", &format!("nobody{}", i)).status(), StatusCode::OK);
+        assert_eq!(lookup("email_exists", &format!("nobody{}@gmail.com", i)).status(), StatusCode::OK);
+    }
+    let response = lookup("username_exists", "nobody");
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers().get("Retry-After").is_some());
+    assert_eq!(lookup("email_exists", "nobody@gmail.com").status(), StatusCode::TOO_MANY_REQUESTS);
+}
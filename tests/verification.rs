@@ -0,0 +1,173 @@
+use reqwest::{blocking::Client, StatusCode};
+use rocket::serde::json::serde_json::json;
+use serde_json::Value;
+use std::process::Command;
+
+mod common;
+use common::APP_HOST;
+
+fn register(client: &Client, username: &str) -> Value {
+    let response = client.post(format!("{}/users", APP_HOST))
+        .json(&json!({
+            "username":username,
+            "email":format!("{}@gmail.com", username),
+            "password":"testpassword",
+            "full_name":"Test User",
+            "country":"USA",
+            "date_of_birth":"1990-01-01"
+        }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    response.json().unwrap()
+}
+
+fn login_status(client: &Client, username: &str) -> StatusCode {
+    client.post(format!("{}/login", APP_HOST))
+        .json(&json!({
+            "username":username,
+            "password":"testpassword"
+        }))
+        .send()
+        .unwrap()
+        .status()
+}
+
+fn delete_by_username(username: &str) {
+    let output = Command::new("cargo")
+        .arg("run")
+        .arg("--bin")
+        .arg("cli")
+        .arg("users")
+        .arg("delete_by_username")
+        .arg(username)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+}
+
+fn set_active(user: &Value, active: bool) {
+    let output = Command::new("cargo")
+        .arg("run")
+        .arg("--bin")
+        .arg("cli")
+        .arg("users")
+        .arg("set_active")
+        .arg(user["user_id"].to_string())
+        .arg(active.to_string())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+}
+
+#[test]
+fn test_login_needs_verified_email() {
+    // setup
+    let client = Client::new();
+    register(&client, "testUnverifiedUser");
+
+    // test
+    assert_eq!(login_status(&client, "testUnverifiedUser"), StatusCode::FORBIDDEN);
+    let response = client.get(format!("{}/verify-email/{}", APP_HOST, "not.a-token")).send().unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    // a token can't be moved to another account
+    let token = common::token_from_mail(&common::latest_mail("testUnverifiedUser@gmail.com"));
+    let (payload, signature) = token.split_once('.').unwrap();
+    let response = client.get(format!("{}/verify-email/{}x.{}", APP_HOST, payload, signature)).send().unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    common::verify_email(&client, "testUnverifiedUser@gmail.com");
+    assert_eq!(login_status(&client, "testUnverifiedUser"), StatusCode::OK);
+
+    // clean up
+    delete_by_username("testUnverifiedUser");
+}
+
+#[test]
+fn test_resend_verification() {
+    // setup, resends are counted per address for an hour so every run needs a new one
+    let client = Client::new();
+    let nanos = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().subsec_nanos();
+    let username = format!("testResendUser{}", nanos);
+    let email = format!("{}@gmail.com", username);
+    register(&client, &username);
+    let resend = || client.post(format!("{}/verify-email/resend", APP_HOST))
+        .json(&json!({
+            "email":email
+        }))
+        .send()
+        .unwrap();
+
+    // test
+    for _ in 0..2 {
+        assert_eq!(resend().status(), StatusCode::ACCEPTED);
+    }
+    assert_eq!(common::mails_for(&email).len(), 3);
+
+    // the resent token works like the first one
+    common::verify_email(&client, &email);
+    assert_eq!(login_status(&client, &username), StatusCode::OK);
+
+    // a verified address gets no more tokens, the answer stays the same
+    assert_eq!(resend().status(), StatusCode::ACCEPTED);
+    assert_eq!(common::mails_for(&email).len(), 3);
+    let response = resend();
+    assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(response.headers().get("Retry-After").is_some());
+
+    // clean up
+    delete_by_username(&username);
+}
+
+#[test]
+fn test_deactivated_account_stays_deactivated() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = common::create_test_user(&admin_client, "testuser@gmail.com");
+    let username = user["username"].as_str().unwrap();
+    let email = user["email"].as_str().unwrap();
+    set_active(&user, false);
+
+    // test
+    let client = Client::new();
+    assert_eq!(login_status(&client, username), StatusCode::FORBIDDEN);
+    // verification can't activate it again
+    let mails = common::mails_for(email).len();
+    let response = client.post(format!("{}/verify-email/resend", APP_HOST))
+        .json(&json!({ "email":email }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::ACCEPTED);
+    assert_eq!(common::mails_for(email).len(), mails);
+    common::verify_email(&client, email);
+    assert_eq!(login_status(&client, username), StatusCode::FORBIDDEN);
+
+    set_active(&user, true);
+    assert_eq!(login_status(&client, username), StatusCode::OK);
+
+    // clean up
+    common::delete_test_user(&admin_client, user);
+}
+
+#[test]
+fn test_deactivation_ends_sessions() {
+    // setup
+    let admin_client = common::get_client_with_logged_in_admin();
+    let user: Value = common::create_test_user(&admin_client, "testuser@gmail.com");
+    let client = common::get_client_with_logged_in_user(&user);
+    let tokens = common::login_json(&user);
+    assert_eq!(client.get(format!("{}/me", APP_HOST)).send().unwrap().status(), StatusCode::OK);
+
+    // test, neither the session nor the refresh token outlive the deactivation
+    set_active(&user, false);
+    assert_eq!(client.get(format!("{}/me", APP_HOST)).send().unwrap().status(), StatusCode::UNAUTHORIZED);
+    let response = Client::new().post(format!("{}/token/refresh", APP_HOST))
+        .json(&json!({ "refresh_token":tokens["refresh_token"] }))
+        .send()
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    // clean up
+    set_active(&user, true);
+    common::delete_test_user(&admin_client, user);
+}